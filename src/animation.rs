@@ -0,0 +1,137 @@
+//! animated gifs and pngs: every frame is sorted on its own with the same passes and the
+//! frames are saved back with their delays
+use crate::io::straight_rgba;
+use crate::video::{is_video_path, write_video};
+use image::codecs::gif::{GifDecoder, GifEncoder, Repeat};
+use image::codecs::png::PngDecoder;
+use image::AnimationDecoder;
+use std::path::Path;
+use std::time::Duration;
+
+/// a frame as it's shown, over whatever the frames before it left behind
+pub struct Frame {
+    pub image: egui::ColorImage,
+    /// how long it's shown before the next one
+    pub delay: Duration,
+}
+
+pub struct Animation {
+    pub frames: Vec<Frame>,
+}
+
+/// whether `format` can hold more than one frame
+pub fn is_animated_format(format: image::ImageFormat) -> bool {
+    matches!(format, image::ImageFormat::Gif | image::ImageFormat::Png)
+}
+
+impl Animation {
+    /// the frames of the gif or APNG at `path`, none for still images, other formats and
+    /// standard input
+    pub fn load(path: &str) -> Result<Option<Animation>, String> {
+        if path == "-" {
+            return Ok(None);
+        }
+        let reader = image::io::Reader::open(path)
+            .and_then(|reader| reader.with_guessed_format())
+            .map_err(|e| e.to_string())?;
+        let frames = match reader.format() {
+            Some(image::ImageFormat::Gif) => GifDecoder::new(reader.into_inner())
+                .and_then(|decoder| decoder.into_frames().collect_frames()),
+            Some(image::ImageFormat::Png) => {
+                let decoder = PngDecoder::new(reader.into_inner()).map_err(|e| e.to_string())?;
+                if !decoder.is_apng() {
+                    return Ok(None);
+                }
+                decoder.apng().into_frames().collect_frames()
+            }
+            _ => return Ok(None),
+        }
+        .map_err(|e| e.to_string())?;
+        if frames.len() < 2 {
+            return Ok(None);
+        }
+
+        let frames = frames
+            .into_iter()
+            .map(|frame| {
+                let delay = Duration::from(frame.delay());
+                let buffer = frame.into_buffer();
+                let size = [buffer.width() as usize, buffer.height() as usize];
+                Frame {
+                    image: egui::ColorImage::from_rgba_unmultiplied(size, buffer.as_raw()),
+                    delay,
+                }
+            })
+            .collect();
+        Ok(Some(Animation { frames }))
+    }
+
+    pub fn size(&self) -> [usize; 2] {
+        self.frames[0].image.size
+    }
+
+    /// a gif or an APNG playing forever. pngs are compressed as `compression` says
+    pub fn encode(
+        &self,
+        format: image::ImageFormat,
+        compression: png::Compression,
+    ) -> Result<Vec<u8>, String> {
+        let [width, height] = self.size();
+        let mut bytes = Vec::new();
+        match format {
+            image::ImageFormat::Gif => {
+                // the colors of every frame are picked from a sample of a tenth of its pixels,
+                // looking at all of them is much slower for hardly any difference
+                let mut encoder = GifEncoder::new_with_speed(&mut bytes, 10);
+                encoder
+                    .set_repeat(Repeat::Infinite)
+                    .map_err(|e| e.to_string())?;
+                for frame in &self.frames {
+                    let buffer = image::RgbaImage::from_raw(
+                        width as u32,
+                        height as u32,
+                        straight_rgba(&frame.image.pixels),
+                    )
+                    .ok_or("image buffer has the wrong size")?;
+                    let delay = image::Delay::from_saturating_duration(frame.delay);
+                    encoder
+                        .encode_frame(image::Frame::from_parts(buffer, 0, 0, delay))
+                        .map_err(|e| e.to_string())?;
+                }
+            }
+            image::ImageFormat::Png => {
+                let mut encoder = png::Encoder::new(&mut bytes, width as u32, height as u32);
+                encoder.set_color(png::ColorType::Rgba);
+                encoder.set_depth(png::BitDepth::Eight);
+                encoder.set_compression(compression);
+                encoder
+                    .set_animated(self.frames.len() as u32, 0)
+                    .map_err(|e| e.to_string())?;
+                let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+                for frame in &self.frames {
+                    // in milliseconds, the longest delay an APNG can hold is a minute
+                    let delay = frame.delay.as_millis().min(u16::MAX as u128) as u16;
+                    writer
+                        .set_frame_delay(delay, 1000)
+                        .map_err(|e| e.to_string())?;
+                    writer
+                        .write_image_data(&straight_rgba(&frame.image.pixels))
+                        .map_err(|e| e.to_string())?;
+                }
+                writer.finish().map_err(|e| e.to_string())?;
+            }
+            format => return Err(format!("{:?} files cannot be animated", format)),
+        }
+        Ok(bytes)
+    }
+
+    /// writes a video when `path` has a video extension, else a gif or an APNG
+    pub fn save(&self, path: &Path, compression: png::Compression) -> Result<(), String> {
+        if is_video_path(path) {
+            return write_video(self, path);
+        }
+        let format = image::ImageFormat::from_path(path).map_err(|e| e.to_string())?;
+        let bytes = self.encode(format, compression)?;
+        std::fs::write(path, bytes).map_err(|e| e.to_string())
+    }
+}
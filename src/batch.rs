@@ -0,0 +1,646 @@
+use crate::{cli, config, exit_with_error, pipeline, report};
+use psorter::animation::{is_animated_format, Animation};
+use psorter::exif::{embed_exif, orientation, read_exif_file};
+use psorter::hdr::{is_hdr_format, HdrImage};
+use psorter::icc::{embed_profile, read_profile, ColorProfile};
+use psorter::io::{
+    basename, encode_image, is_image_file, load_image_from_path, output_format, replace_file,
+    temporary_path,
+};
+use psorter::sort::Pass;
+use psorter::stream;
+use psorter::temporal::TemporalSmoothing;
+use rayon::prelude::*;
+use std::env;
+use std::io::Write;
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+use web_time::Instant;
+
+/// an image to sort, along with its path relative to the directory or pattern it was found
+/// in, which is kept under the output directory
+pub struct InputImage {
+    pub path: String,
+    pub relative_path: std::path::PathBuf,
+}
+
+impl InputImage {
+    pub fn new(path: String) -> Result<InputImage, String> {
+        let name = basename(&path).ok_or_else(|| format!("{} is not a file", path))?;
+        Ok(InputImage {
+            relative_path: name.into(),
+            path,
+        })
+    }
+}
+
+/// the given files, with directories replaced by the images inside of them and glob patterns
+/// by the images they match. `extensions` replaces the usual image extensions when not empty
+pub fn image_files(inputs: &[String], recursive: bool, extensions: &[String]) -> Vec<InputImage> {
+    let is_image = |path: &Path| is_image_file(path, extensions);
+    let relative_to = |root: &Path, path: std::path::PathBuf| InputImage {
+        relative_path: path.strip_prefix(root).unwrap_or(&path).to_path_buf(),
+        path: path.display().to_string(),
+    };
+
+    let mut images = Vec::new();
+    for input in inputs {
+        let path = Path::new(input);
+        if path.is_dir() {
+            let mut found = Vec::new();
+            directory_images(path, recursive, &is_image, &mut found);
+            found.sort();
+            images.extend(found.into_iter().map(|image| relative_to(path, image)));
+        } else if path.exists() || !input.contains(['*', '?', '[']) {
+            images.push(InputImage::new(input.clone()).unwrap_or_else(|e| exit_with_error(e)));
+        } else {
+            // everything up to the first component with a wildcard
+            let root: std::path::PathBuf = path
+                .components()
+                .take_while(|c| !c.as_os_str().to_string_lossy().contains(['*', '?', '[']))
+                .collect();
+            let matches = glob::glob(input)
+                .unwrap_or_else(|e| exit_with_error(format!("invalid pattern {}: {}", input, e)));
+            images.extend(
+                matches
+                    .filter_map(Result::ok)
+                    .filter(|image| is_image(image))
+                    .map(|image| relative_to(&root, image)),
+            );
+        }
+    }
+    images
+}
+
+fn directory_images(
+    directory: &Path,
+    recursive: bool,
+    is_image: &dyn Fn(&Path) -> bool,
+    found: &mut Vec<std::path::PathBuf>,
+) {
+    let entries = std::fs::read_dir(directory)
+        .unwrap_or_else(|e| exit_with_error(format!("cannot read {}: {}", directory.display(), e)));
+    for path in entries.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+        if recursive && path.is_dir() {
+            directory_images(&path, recursive, is_image, found);
+        } else if is_image(&path) {
+            found.push(path);
+        }
+    }
+}
+
+/// `psorter run <pipeline file> [images]`
+pub fn run_pipeline(
+    path: &Path,
+    extra_images: &[String],
+    force: bool,
+    jobs: &cli::JobArgs,
+    config: &config::Config,
+) {
+    let mut pipeline = pipeline::Pipeline::load(path).unwrap_or_else(|e| {
+        exit_with_error(format!("cannot load pipeline {}: {}", path.display(), e))
+    });
+
+    let passes: Vec<Pass> = pipeline
+        .passes
+        .iter()
+        .map(|pass| {
+            pipeline::pass_arguments(pass)
+                .and_then(|args| cli::PassOnly::parse_pass(&args, config))
+                .unwrap_or_else(|e| {
+                    exit_with_error(format!("invalid pass in {}: {}", path.display(), e))
+                })
+        })
+        .collect();
+
+    let images: Vec<InputImage> = pipeline
+        .images
+        .iter()
+        .chain(extra_images)
+        .cloned()
+        .map(InputImage::new)
+        .collect::<Result<_, _>>()
+        .unwrap_or_else(|e| exit_with_error(e));
+    if images.is_empty() {
+        exit_with_error("no images to sort, list them in the pipeline or after its path");
+    }
+
+    pipeline.output.force |= force;
+    if pipeline.output.directory.is_none() && !pipeline.output.in_place {
+        pipeline.output.directory = config.out_dir.clone();
+    }
+    sort_images(&passes, &images, &pipeline.output, jobs);
+}
+
+/// sorts the images in parallel, with `--keep-going` a failed image doesn't stop the others
+/// and the failures are listed at the end
+pub fn sort_images(
+    passes: &[Pass],
+    images: &[InputImage],
+    output: &pipeline::Output,
+    jobs: &cli::JobArgs,
+) {
+    // check everything up front instead of failing halfway through a batch
+    let problems = check_images(images, output, jobs.dry_run);
+    if jobs.dry_run && !problems.is_empty() {
+        exit_with_error(problems.join("\n"));
+    } else if jobs.dry_run {
+        return;
+    } else if let Some(problem) = problems.first() {
+        exit_with_error(problem);
+    }
+
+    for image in images {
+        let output_path = output.path(&image.path, &image.relative_path);
+        if let Some(directory) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Err(e) = std::fs::create_dir_all(directory) {
+                exit_with_error(format!("cannot create {}: {}", directory.display(), e));
+            }
+        }
+    }
+
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(jobs.threads.unwrap_or(0) as usize)
+        .build()
+        .unwrap_or_else(|e| exit_with_error(format!("cannot start threads: {}", e)));
+
+    // progress bars are only drawn when stderr is a terminal, and they'd get in the way of
+    // -v and -q
+    let progress = indicatif::MultiProgress::new();
+    if log::max_level() != log::LevelFilter::Warn {
+        progress.set_draw_target(indicatif::ProgressDrawTarget::hidden());
+    }
+    let overall = if images.len() > 1 {
+        progress.add(
+            indicatif::ProgressBar::new(images.len() as u64).with_style(
+                indicatif::ProgressStyle::with_template(
+                    "[{bar:40}] {pos}/{len} images, {eta} left",
+                )
+                .unwrap()
+                .progress_chars("=> "),
+            ),
+        )
+    } else {
+        indicatif::ProgressBar::hidden()
+    };
+    let failed = AtomicBool::new(false);
+    let smoothing = jobs
+        .smooth
+        .map(|amount| Mutex::new(TemporalSmoothing::new(amount)));
+    let sort = |image: &InputImage| {
+        // without --keep-going, images that haven't started yet are skipped after a failure
+        if failed.load(Ordering::Relaxed) && !jobs.keep_going {
+            return None;
+        }
+
+        let bar = progress.add(image_progress_bar(&image.path, passes.len()));
+        let start = Instant::now();
+        let max_memory = jobs
+            .max_memory
+            .map(|megabytes| megabytes as usize * 1024 * 1024);
+        let result = sort_image_file(
+            passes,
+            image,
+            output,
+            max_memory,
+            jobs.srgb_keys,
+            smoothing.as_ref(),
+            &bar,
+        );
+        bar.finish_and_clear();
+        overall.inc(1);
+        if result.is_err() {
+            failed.store(true, Ordering::Relaxed);
+        }
+        Some(report::ImageReport::new(
+            image.path.clone(),
+            output.path(&image.path, &image.relative_path),
+            result,
+            start.elapsed(),
+        ))
+    };
+
+    let report = report::Report {
+        arguments: env::args().skip(1).collect(),
+        // frames of a sequence are smoothed in order
+        images: pool.install(|| match smoothing {
+            Some(_) => images.iter().filter_map(sort).collect(),
+            None => images.par_iter().filter_map(sort).collect(),
+        }),
+    };
+    overall.finish_and_clear();
+
+    if let Some(path) = &jobs.report {
+        report.save(path).unwrap_or_else(|e| {
+            exit_with_error(format!("cannot write report {}: {}", path.display(), e))
+        });
+    }
+
+    let failures: Vec<&str> = report
+        .images
+        .iter()
+        .filter_map(|image| image.error.as_deref())
+        .collect();
+    if !failures.is_empty() && !jobs.keep_going {
+        exit_with_error(failures[0]);
+    } else if !failures.is_empty() {
+        exit_with_error(format!(
+            "{} of {} images failed:\n  {}",
+            failures.len(),
+            images.len(),
+            failures.join("\n  ")
+        ));
+    }
+}
+
+/// lists whatever would stop the images from being sorted and saved: outputs colliding with
+/// each other or with existing files and, with `dry_run`, unreadable inputs and unknown
+/// formats. `dry_run` also prints where every image would go
+fn check_images(images: &[InputImage], output: &pipeline::Output, dry_run: bool) -> Vec<String> {
+    let mut problems = Vec::new();
+    if images.iter().filter(|image| image.path == "-").count() > 1 {
+        problems.push("standard input can only be read once".to_string());
+    }
+
+    let mut output_paths = std::collections::HashMap::new();
+    for InputImage {
+        path,
+        relative_path,
+    } in images
+    {
+        let output_path = output.path(path, relative_path);
+        if dry_run {
+            println!("{} -> {}", path, output_path.display());
+            if path != "-" {
+                if let Err(e) = std::fs::File::open(path) {
+                    problems.push(format!("cannot read {}: {}", path, e));
+                } else if image::ImageFormat::from_path(path).is_err() {
+                    problems.push(format!("cannot tell the format of {}", path));
+                }
+            }
+            if output_path != Path::new("-")
+                && output.format.is_none()
+                && image::ImageFormat::from_path(&output_path).is_err()
+            {
+                problems.push(format!(
+                    "cannot tell the format of {}, pass --format",
+                    output_path.display()
+                ));
+            }
+        }
+
+        if output_path == Path::new("-") {
+            continue;
+        }
+        if let Some(other) = output_paths.insert(output_path.clone(), path) {
+            problems.push(format!(
+                "{} and {} would both be saved as {}",
+                other,
+                path,
+                output_path.display()
+            ));
+        }
+
+        let clobbered = match output.backup_path(path) {
+            Some(backup_path) => Some(backup_path),
+            None if output.in_place => None,
+            None => Some(output_path.clone()),
+        };
+        if let Some(clobbered) = clobbered.filter(|p| p.exists() && !output.force) {
+            problems.push(format!(
+                "{} already exists, pass --force to overwrite it",
+                clobbered.display()
+            ));
+        }
+    }
+    problems
+}
+
+/// counts the steps of sorting a single image: decoding, every pass, then encoding
+fn image_progress_bar(path: &str, pass_count: usize) -> indicatif::ProgressBar {
+    let bar = indicatif::ProgressBar::new(pass_count as u64 + 2)
+        .with_style(
+            indicatif::ProgressStyle::with_template(
+                "{spinner} [{bar:20}] {prefix} {msg} {elapsed}",
+            )
+            .unwrap()
+            .progress_chars("=> "),
+        )
+        .with_prefix(path.to_string());
+    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+    bar
+}
+
+/// images that would take more than `max_memory` bytes are sorted in bands, see
+/// `sort_in_bands`. the color profile of the image is kept, with `srgb_keys` keys are computed
+/// on its colors in sRGB. with `smoothing` the image is the next frame of a sequence
+pub fn sort_image_file(
+    passes: &[Pass],
+    input: &InputImage,
+    output: &pipeline::Output,
+    max_memory: Option<usize>,
+    srgb_keys: bool,
+    smoothing: Option<&Mutex<TemporalSmoothing>>,
+    progress: &indicatif::ProgressBar,
+) -> Result<[usize; 2], String> {
+    let path = &input.path;
+    if let Some(max_memory) = max_memory {
+        if let Some(size) = sort_in_bands(passes, input, output, max_memory, srgb_keys, progress)? {
+            return Ok(size);
+        }
+    }
+    if let Some(size) = sort_hdr(passes, input, output, progress)? {
+        return Ok(size);
+    }
+    let smooth = smoothing.map(|smoothing| smoothing.lock().unwrap().amount);
+    if let Some(size) = sort_animation(passes, input, output, smooth, progress)? {
+        return Ok(size);
+    }
+
+    progress.set_message("decoding");
+    let mut image =
+        load_image_from_path(path).map_err(|e| format!("cannot load image {}: {}", path, e))?;
+    let profile = read_profile(path);
+    let exif = read_exif_file(path).filter(|_| !output.strip_metadata);
+    progress.inc(1);
+    log::info!("sorting {} ({}x{})", path, image.width(), image.height());
+
+    let color_profile = profile
+        .as_deref()
+        .filter(|_| srgb_keys)
+        .and_then(|profile| {
+            ColorProfile::parse(profile)
+                .map_err(|e| {
+                    log::warn!("the keys of {} are computed on its own colors: {}", path, e)
+                })
+                .ok()
+        });
+    if let Some(smoothing) = smoothing {
+        progress.set_message(format!("{} passes", passes.len()));
+        smoothing.lock().unwrap().apply(passes, &mut image);
+        progress.inc(passes.len() as u64);
+    } else {
+        for (i, pass) in passes.iter().enumerate() {
+            progress.set_message(format!("pass {}/{}", i + 1, passes.len()));
+            if let Some(color_profile) = &color_profile {
+                let key_image = color_profile.to_srgb(&image);
+                pass.apply_with_keys(&mut image, &key_image);
+            } else {
+                pass.apply(&mut image);
+            }
+            progress.inc(1);
+        }
+    }
+
+    progress.set_message("encoding");
+    let size = image.size;
+    let new_file_name = output.path(path, &input.relative_path);
+    let save = || {
+        let format = output_format(&new_file_name, output.format)?;
+        let mut bytes = encode_image(&image, &new_file_name, Some(format), &output.encoding)?;
+        if let Some(profile) = &profile {
+            if let Err(e) = embed_profile(&mut bytes, format, profile) {
+                log::warn!(
+                    "{} is saved without its color profile: {}",
+                    new_file_name.display(),
+                    e
+                );
+            }
+        }
+        if let Some(exif) = &exif {
+            if let Err(e) = embed_exif(&mut bytes, format, exif) {
+                log::warn!(
+                    "{} is saved without its EXIF data: {}",
+                    new_file_name.display(),
+                    e
+                );
+            }
+        }
+        save_output(&bytes, path, &new_file_name, output)
+    };
+    save().map_err(|e| format!("failed to save file {}: {}", new_file_name.display(), e))?;
+    log::info!("saved {}", new_file_name.display());
+    Ok(size)
+}
+
+/// writes the encoded image sorted from `path` to `output_path`, `-` being standard output
+fn save_output(
+    bytes: &[u8],
+    path: &str,
+    output_path: &Path,
+    output: &pipeline::Output,
+) -> Result<(), String> {
+    if output_path == Path::new("-") {
+        std::io::stdout()
+            .lock()
+            .write_all(bytes)
+            .map_err(|e| e.to_string())
+    } else if output.in_place {
+        replace_file(output_path, bytes, output.backup_path(path).as_deref())
+    } else {
+        std::fs::write(output_path, bytes).map_err(|e| e.to_string())
+    }
+}
+
+/// sorts OpenEXR and Radiance HDR images saved as either in linear light, see
+/// `Pass::apply_hdr`. none for other images, which go through 8 bit colors
+fn sort_hdr(
+    passes: &[Pass],
+    input: &InputImage,
+    output: &pipeline::Output,
+    progress: &indicatif::ProgressBar,
+) -> Result<Option<[usize; 2]>, String> {
+    let path = &input.path;
+    let output_path = output.path(path, &input.relative_path);
+    let hdr_input = path != "-" && image::ImageFormat::from_path(path).is_ok_and(is_hdr_format);
+    let format = output
+        .format
+        .or_else(|| image::ImageFormat::from_path(&output_path).ok())
+        .filter(|&format| hdr_input && is_hdr_format(format));
+    let Some(format) = format else {
+        return Ok(None);
+    };
+
+    progress.set_message("decoding");
+    let mut image = HdrImage::load(Path::new(path))
+        .map_err(|e| format!("cannot load image {}: {}", path, e))?;
+    progress.inc(1);
+    let [width, height] = image.size;
+    log::info!("sorting {} ({}x{}) in linear light", path, width, height);
+
+    for (i, pass) in passes.iter().enumerate() {
+        progress.set_message(format!("pass {}/{}", i + 1, passes.len()));
+        pass.apply_hdr(&mut image);
+        progress.inc(1);
+    }
+
+    progress.set_message("encoding");
+    image
+        .encode(format)
+        .and_then(|bytes| save_output(&bytes, path, &output_path, output))
+        .map_err(|e| format!("failed to save file {}: {}", output_path.display(), e))?;
+    log::info!("saved {}", output_path.display());
+    Ok(Some(image.size))
+}
+
+/// sorts every frame of animated gifs and pngs saved as either, see `Animation`. none for still
+/// images, and for animations saved in other formats, which only get their first frame. with
+/// `smooth` the frames are sorted in order, see `TemporalSmoothing`
+fn sort_animation(
+    passes: &[Pass],
+    input: &InputImage,
+    output: &pipeline::Output,
+    smooth: Option<f32>,
+    progress: &indicatif::ProgressBar,
+) -> Result<Option<[usize; 2]>, String> {
+    let path = &input.path;
+    progress.set_message("decoding");
+    let Some(mut animation) =
+        Animation::load(path).map_err(|e| format!("cannot load image {}: {}", path, e))?
+    else {
+        return Ok(None);
+    };
+    let output_path = output.path(path, &input.relative_path);
+    let format = output_format(&output_path, output.format)
+        .map_err(|e| format!("failed to save file {}: {}", output_path.display(), e))?;
+    if !is_animated_format(format) {
+        log::warn!(
+            "only the first frame of {} is sorted, {:?} files cannot be animated",
+            path,
+            format
+        );
+        return Ok(None);
+    }
+    progress.inc(1);
+    let [width, height] = animation.size();
+    let frame_count = animation.frames.len();
+    log::info!(
+        "sorting the {} frames of {} ({}x{})",
+        frame_count,
+        path,
+        width,
+        height
+    );
+
+    progress.set_length((frame_count * passes.len()) as u64 + 2);
+    progress.set_message(format!("{} frames", frame_count));
+    if let Some(amount) = smooth {
+        let mut smoothing = TemporalSmoothing::new(amount);
+        for frame in &mut animation.frames {
+            smoothing.apply(passes, &mut frame.image);
+            progress.inc(passes.len() as u64);
+        }
+    } else {
+        animation.frames.par_iter_mut().for_each(|frame| {
+            for pass in passes {
+                pass.apply(&mut frame.image);
+                progress.inc(1);
+            }
+        });
+    }
+
+    progress.set_message("encoding");
+    animation
+        .encode(format, output.encoding.png_compression.png())
+        .and_then(|bytes| save_output(&bytes, path, &output_path, output))
+        .map_err(|e| format!("failed to save file {}: {}", output_path.display(), e))?;
+    log::info!("saved {}", output_path.display());
+    Ok(Some([width, height]))
+}
+
+/// sorts pngs too big for `max_memory` a band of rows at a time, straight from the file into
+/// a png. none when the image fits, an error when it doesn't and can't be sorted in bands
+fn sort_in_bands(
+    passes: &[Pass],
+    input: &InputImage,
+    output: &pipeline::Output,
+    max_memory: usize,
+    srgb_keys: bool,
+    progress: &indicatif::ProgressBar,
+) -> Result<Option<[usize; 2]>, String> {
+    // the size of standard input isn't known before it's read whole
+    let path = &input.path;
+    if path == "-" {
+        return Ok(None);
+    }
+    let (width, height) =
+        image::image_dimensions(path).map_err(|e| format!("cannot load image {}: {}", path, e))?;
+    let (width, height) = (width as usize, height as usize);
+    let needed = width * height * stream::BYTES_PER_PIXEL;
+    if needed <= max_memory {
+        return Ok(None);
+    }
+
+    let too_big = |reason: String| {
+        format!(
+            "{} needs about {} MB, more than --max-memory, and cannot be sorted in bands: {}",
+            path,
+            needed / 1024 / 1024,
+            reason
+        )
+    };
+    if image::ImageFormat::from_path(path).ok() != Some(image::ImageFormat::Png) {
+        return Err(too_big(
+            "only pngs can be read a band at a time".to_string(),
+        ));
+    }
+    let output_path = output.path(path, &input.relative_path);
+    let format = output
+        .format
+        .or_else(|| image::ImageFormat::from_path(&output_path).ok());
+    if output_path == Path::new("-") || format != Some(image::ImageFormat::Png) {
+        return Err(too_big("bands are only saved into png files".to_string()));
+    }
+    if srgb_keys {
+        return Err(too_big("--srgb-keys needs the whole image".to_string()));
+    }
+    let exif = read_exif_file(path);
+    if exif
+        .as_deref()
+        .and_then(orientation)
+        .is_some_and(|o| o != 1)
+    {
+        return Err(too_big("rotated images need the whole image".to_string()));
+    }
+    let exif = exif.filter(|_| !output.strip_metadata);
+    for pass in passes {
+        stream::check_bands(pass).map_err(too_big)?;
+    }
+
+    let band_height = stream::band_height(width, max_memory);
+    log::info!(
+        "sorting {} ({}x{}) {} rows at a time",
+        path,
+        width,
+        height,
+        band_height
+    );
+    progress.set_message("sorting in bands");
+    // in place, the original is only replaced once the whole image is sorted
+    let written_path = if output.in_place {
+        temporary_path(&output_path)
+    } else {
+        output_path.clone()
+    };
+    let sorted = stream::sort_png(
+        passes,
+        Path::new(path),
+        &written_path,
+        band_height,
+        exif.as_deref(),
+        output.encoding.png_compression,
+    );
+    if let Err(e) = sorted {
+        let _ = std::fs::remove_file(&written_path);
+        return Err(format!("cannot sort {}: {}", path, e));
+    }
+    if output.in_place {
+        if let Some(backup_path) = output.backup_path(path) {
+            std::fs::copy(path, backup_path).map_err(|e| e.to_string())?;
+        }
+        std::fs::rename(&written_path, &output_path).map_err(|e| e.to_string())?;
+    }
+    log::info!("saved {}", output_path.display());
+    Ok(Some([width, height]))
+}
@@ -0,0 +1,47 @@
+//! how fast the stages of a pass run, for `psorter bench`
+use crate::intervals::selection_mask;
+use crate::sort::{numbered_paths, Pass};
+use std::time::Duration;
+use web_time::Instant;
+
+/// the average time of every stage of a pass
+pub struct StageTimes {
+    /// finding the pixels within the thresholds
+    pub select: Duration,
+    /// laying out the paths of the traversal
+    pub paths: Duration,
+    /// the whole pass, the stages above included
+    pub pass: Duration,
+}
+
+/// sorts copies of `image` with `pass` `runs` times, timing every stage
+pub fn time_pass(pass: &Pass, image: &egui::ColorImage, runs: u32) -> StageTimes {
+    let mut select = Duration::ZERO;
+    let mut paths = Duration::ZERO;
+    let mut whole = Duration::ZERO;
+    for _ in 0..runs.max(1) {
+        let start = Instant::now();
+        selection_mask(&pass.thresholds, image, &pass.sort_by);
+        select += start.elapsed();
+
+        let start = Instant::now();
+        numbered_paths(image, &pass.traversal, pass.block_size);
+        paths += start.elapsed();
+
+        let mut sorted = image.clone();
+        let start = Instant::now();
+        pass.apply(&mut sorted);
+        whole += start.elapsed();
+    }
+
+    let runs = runs.max(1);
+    StageTimes {
+        select: select / runs,
+        paths: paths / runs,
+        pass: whole / runs,
+    }
+}
+
+pub fn megapixels_per_second(pixels: usize, time: Duration) -> f64 {
+    pixels as f64 / 1_000_000.0 / time.as_secs_f64().max(f64::MIN_POSITIVE)
+}
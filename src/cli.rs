@@ -0,0 +1,1006 @@
+use crate::{config, pipeline, preset};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use psorter::hdr::is_hdr_format;
+use psorter::intervals::{
+    parse_point, IntervalOperation, IntervalOptions, IntervalSource, ScanOrder, SmearPixel,
+    Thresholds, Traversal,
+};
+use psorter::io::{load_image_from_path, EncodeOptions, PngCompression};
+use psorter::jpeg::ChromaSubsampling;
+use psorter::keys::{
+    parse_color, parse_sort_by, parse_threshold, threshold_upper_boundary, AutoThreshold, SortBy,
+};
+use psorter::sort::{Pass, SortOrder};
+use psorter::sweep::{Sweep, SweepParameter};
+use psorter::timeline::Timeline;
+use psorter::{convolution, mask, noise, script, svg, PixelSorter};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// pixel sorter, opens the gui when run without arguments
+///
+/// `psorter l 0 69 image.png` is short for `psorter sort l 0 69 image.png`. several passes
+/// can be chained by separating them with `--pass`, the images follow the last one.
+/// `--preset NAME` starts a pass from the options saved under NAME in
+/// ~/.config/porter/presets.yaml, the options given next to it win.
+#[derive(Parser)]
+#[command(name = "psorter", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+    /// only print errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+    /// print every sorted image, -vv adds how long decoding, sorting and encoding took
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    pub verbose: u8,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// sort images, writing sorted-<name> into the working directory
+    Sort(SortArgs),
+    /// sort every image in the given directories and glob patterns, like 'photos/**/*.jpg'
+    Batch(BatchArgs),
+    /// sort a single image and show the result in a window instead of saving it
+    Preview(PreviewArgs),
+    /// sort images as they're added to or changed in the given directories, until stopped
+    Watch(WatchArgs),
+    /// sort images uploaded over http, see the readme for the parameters
+    Serve(ServeArgs),
+    /// run the passes of a yaml or json pipeline file
+    Run {
+        pipeline: PathBuf,
+        /// sorted along with the images listed in the pipeline
+        images: Vec<String>,
+        /// overwrite existing files
+        #[arg(long)]
+        force: bool,
+        #[command(flatten)]
+        jobs: JobArgs,
+    },
+    /// time every key along every traversal over an image, in megapixels per second
+    Bench(BenchArgs),
+    /// sort an image once for every frame of an animation, moving a parameter of the last
+    /// pass from --from to --to, or several of them through keyframes
+    Sweep(SweepArgs),
+    /// sort every frame of a video into another one with the same audio, through ffmpeg
+    Video(VideoArgs),
+    /// open the graphical interface
+    Gui,
+}
+
+#[derive(Args)]
+pub struct SortArgs {
+    #[command(flatten)]
+    pub pass: PassArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub jobs: JobArgs,
+    /// the sort key (l, h, s, r, g, b, a, v, lightness, y, d, x or a weighted sum like
+    /// 0.7*l+0.3*s), the thresholds unless they're picked automatically or not used,
+    /// then the images
+    #[arg(value_name = "KEY [LOWER HIGHER] FILES", required = true)]
+    pub positional: Vec<String>,
+}
+
+/// how several images are sorted
+#[derive(Args)]
+pub struct JobArgs {
+    /// sort the other images when one fails, listing the failures at the end
+    #[arg(long)]
+    pub keep_going: bool,
+    /// how many images to sort at once, one per cpu core by default
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+    /// write a json report with the paths, size, timing and errors of every image
+    #[arg(long, value_name = "FILE")]
+    pub report: Option<PathBuf>,
+    /// only print where every image would be saved and what would go wrong
+    #[arg(long, conflicts_with = "report")]
+    pub dry_run: bool,
+    /// megabytes an image may take while it's sorted, bigger pngs are sorted a band of rows
+    /// at a time and other images fail instead of running out of memory
+    #[arg(long, value_name = "MB", value_parser = clap::value_parser!(u64).range(1..))]
+    pub max_memory: Option<u64>,
+    /// compute keys on the colors converted from the color profile of every image to sRGB,
+    /// so wide gamut images sort the way they look. the colors saved stay as they are
+    #[arg(long)]
+    pub srgb_keys: bool,
+    /// sort the images one after another as the frames of an animation, like frame_0001.png
+    /// and on, with interval boundaries following an average of the frames so far instead of
+    /// jumping around. from 0 (every frame on its own) to 1, animated gifs and pngs get it too
+    #[arg(long, value_name = "AMOUNT", value_parser = fraction_parser, conflicts_with_all = ["max_memory", "srgb_keys"])]
+    pub smooth: Option<f32>,
+}
+
+#[derive(Args)]
+pub struct BatchArgs {
+    #[command(flatten)]
+    pub sort: SortArgs,
+    /// also sort the images in subdirectories, keeping their layout under --out-dir
+    #[arg(short, long, help_heading = "Input")]
+    pub recursive: bool,
+    /// only pick files with these extensions from directories and patterns, e.g. png,jpg
+    #[arg(
+        long,
+        value_name = "EXTENSIONS",
+        value_delimiter = ',',
+        help_heading = "Input"
+    )]
+    pub ext: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct WatchArgs {
+    #[command(flatten)]
+    pub pass: PassArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    /// also watch subdirectories, keeping their layout under --out-dir
+    #[arg(short, long, help_heading = "Input")]
+    pub recursive: bool,
+    /// only sort files with these extensions, e.g. png,jpg
+    #[arg(
+        long,
+        value_name = "EXTENSIONS",
+        value_delimiter = ',',
+        help_heading = "Input"
+    )]
+    pub ext: Vec<String>,
+    /// the sort key, the thresholds unless they're picked automatically or not used, then
+    /// the directories to watch
+    #[arg(value_name = "KEY [LOWER HIGHER] DIRECTORIES", required = true)]
+    pub positional: Vec<String>,
+}
+
+#[derive(Args, Clone)]
+pub struct ServeArgs {
+    #[arg(long, default_value_t = 8080)]
+    pub port: u16,
+    /// address to listen on, only this machine by default
+    #[arg(long, default_value = "127.0.0.1")]
+    pub host: String,
+    /// how many images to sort at once, one per cpu core by default
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..))]
+    pub threads: Option<u32>,
+    /// largest accepted upload in megabytes
+    #[arg(long, value_name = "MB", default_value_t = 32)]
+    pub max_upload: usize,
+    /// largest accepted image in megapixels, checked before it's decoded
+    #[arg(long, value_name = "N", default_value_t = 64)]
+    pub max_megapixels: u64,
+    /// lets requests sort by a key-script, code anyone reaching the server can send
+    #[arg(long)]
+    pub allow_scripts: bool,
+}
+
+#[derive(Args)]
+pub struct BenchArgs {
+    /// the image to sort, every pixel of it is selected so intervals are as long as they get
+    pub image: String,
+    /// how many times every pass is timed, the times are averaged
+    #[arg(long, value_name = "N", default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..))]
+    pub runs: u32,
+    /// the keys to time, every built-in one by default
+    #[arg(long, value_name = "KEYS", value_delimiter = ',')]
+    keys: Vec<String>,
+    /// the traversals to time, all of them by default
+    #[arg(long, value_name = "TRAVERSALS", value_delimiter = ',')]
+    traversals: Vec<TraversalArg>,
+}
+
+/// the keys `psorter bench` times unless told otherwise
+const BENCH_KEYS: [&str; 11] = [
+    "l",
+    "h",
+    "s",
+    "r",
+    "g",
+    "b",
+    "a",
+    "v",
+    "lightness",
+    "y",
+    "d",
+];
+
+impl BenchArgs {
+    /// the passes to time along with the names of their key and traversal
+    pub fn passes(&self) -> Result<Vec<(String, String, Pass)>, String> {
+        let keys = if self.keys.is_empty() {
+            BENCH_KEYS.map(str::to_string).to_vec()
+        } else {
+            self.keys.clone()
+        };
+        let traversals = if self.traversals.is_empty() {
+            TraversalArg::value_variants().to_vec()
+        } else {
+            self.traversals.clone()
+        };
+
+        let mut passes = Vec::new();
+        for key in keys {
+            let sort_by = parse_sort_by(&key).ok_or_else(|| format!("unknown key {}", key))?;
+            for traversal in &traversals {
+                let name = traversal
+                    .to_possible_value()
+                    .expect("no traversal is skipped")
+                    .get_name()
+                    .to_string();
+                let pass = PixelSorter::new()
+                    .key(sort_by.clone())
+                    .range(0..=threshold_upper_boundary(&sort_by))
+                    .traversal(traversal.traversal(None))
+                    .build();
+                passes.push((key.clone(), name, pass));
+            }
+        }
+        Ok(passes)
+    }
+}
+
+#[derive(Args)]
+pub struct SweepArgs {
+    #[command(flatten)]
+    pub pass: PassArgs,
+    /// the parameter of the last pass that changes from frame to frame
+    #[arg(long, required_unless_present = "keyframes", requires_all = ["from", "to"], help_heading = "Sweep")]
+    parameter: Option<SweepParameterArg>,
+    /// its value on the first frame, thresholds and lengths in the units of their flags and
+    /// strength in percent
+    #[arg(
+        long,
+        value_name = "VALUE",
+        allow_negative_numbers = true,
+        requires = "parameter",
+        help_heading = "Sweep"
+    )]
+    from: Option<f32>,
+    /// its value on the last frame
+    #[arg(
+        long,
+        value_name = "VALUE",
+        allow_negative_numbers = true,
+        requires = "parameter",
+        help_heading = "Sweep"
+    )]
+    to: Option<f32>,
+    /// instead of --parameter, --from and --to, the value of a parameter on a frame counted
+    /// from 0, like strength:10:50. parameters move in a straight line from one keyframe to
+    /// the next and keep their value before the first and after the last
+    #[arg(long = "keyframe", value_name = "PARAMETER:FRAME:VALUE", value_parser = keyframe_parser, conflicts_with = "parameter", help_heading = "Sweep")]
+    keyframes: Vec<(SweepParameter, usize, f32)>,
+    #[arg(long, value_name = "N", default_value_t = 30, value_parser = clap::value_parser!(u32).range(2..), help_heading = "Sweep")]
+    frames: u32,
+    /// how long every frame is shown, in milliseconds
+    #[arg(long, value_name = "MS", default_value_t = 100, value_parser = clap::value_parser!(u64).range(1..), help_heading = "Sweep")]
+    delay: u64,
+    /// the animation: a gif, an animated png or a video like mp4, which needs ffmpeg
+    #[arg(short, long, value_name = "FILE", help_heading = "Output")]
+    pub output: PathBuf,
+    /// overwrite an existing file
+    #[arg(long, help_heading = "Output")]
+    pub force: bool,
+    /// how hard pngs are compressed, smaller files take longer to write
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        default_value = "fast",
+        help_heading = "Output"
+    )]
+    png_compression: PngCompressionArg,
+    #[arg(value_name = "KEY [LOWER HIGHER] FILE", required = true)]
+    pub positional: Vec<String>,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SweepParameterArg {
+    Lower,
+    Higher,
+    Shift,
+    Strength,
+    MaxLength,
+    SplitChance,
+}
+
+impl SweepParameterArg {
+    fn parameter(self) -> SweepParameter {
+        match self {
+            SweepParameterArg::Lower => SweepParameter::LowerThreshold,
+            SweepParameterArg::Higher => SweepParameter::HigherThreshold,
+            SweepParameterArg::Shift => SweepParameter::Shift,
+            SweepParameterArg::Strength => SweepParameter::Strength,
+            SweepParameterArg::MaxLength => SweepParameter::MaxLength,
+            SweepParameterArg::SplitChance => SweepParameter::SplitChance,
+        }
+    }
+}
+
+impl SweepArgs {
+    pub fn timeline(&self) -> Timeline {
+        let frames = self.frames as usize;
+        let delay = Duration::from_millis(self.delay);
+        if let (Some(parameter), Some(from), Some(to)) = (self.parameter, self.from, self.to) {
+            return Sweep {
+                parameter: parameter.parameter(),
+                from,
+                to,
+                frames,
+                delay,
+            }
+            .timeline();
+        }
+
+        let mut timeline = Timeline::new(frames, delay);
+        for &(parameter, frame, value) in &self.keyframes {
+            timeline.track_mut(parameter).set(frame, value);
+        }
+        timeline
+    }
+
+    pub fn png_compression(&self) -> PngCompression {
+        self.png_compression.compression()
+    }
+}
+
+#[derive(Args)]
+pub struct VideoArgs {
+    #[command(flatten)]
+    pub pass: PassArgs,
+    /// where to start, in seconds or as [HH:]MM:SS[.ms], the beginning by default
+    #[arg(long, value_name = "TIME", help_heading = "Video")]
+    pub start: Option<String>,
+    /// where to stop, in seconds or as [HH:]MM:SS[.ms], the end by default
+    #[arg(long, value_name = "TIME", help_heading = "Video")]
+    pub end: Option<String>,
+    /// keep interval boundaries from flickering, following an average of the frames so far,
+    /// from 0 (every frame on its own) to 1. frames are sorted one at a time then
+    #[arg(long, value_name = "AMOUNT", value_parser = fraction_parser, help_heading = "Video")]
+    pub smooth: Option<f32>,
+    /// overwrite an existing file
+    #[arg(long, help_heading = "Output")]
+    pub force: bool,
+    /// the sort key, the thresholds unless they're picked automatically or not used, then
+    /// the video to sort and the one to write
+    #[arg(value_name = "KEY [LOWER HIGHER] INPUT OUTPUT", required = true)]
+    pub positional: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct PreviewArgs {
+    #[command(flatten)]
+    pub pass: PassArgs,
+    #[arg(value_name = "KEY [LOWER HIGHER] FILE", required = true)]
+    pub positional: Vec<String>,
+}
+
+#[derive(Args)]
+pub struct OutputArgs {
+    /// where to write the sorted image, only works for a single image, `-` for standard output
+    #[arg(
+        short,
+        long,
+        value_name = "FILE",
+        conflicts_with = "out_dir",
+        help_heading = "Output"
+    )]
+    output: Option<PathBuf>,
+    /// directory for the sorted images, created when missing
+    #[arg(long, value_name = "DIR", help_heading = "Output")]
+    out_dir: Option<PathBuf>,
+    /// overwrite existing files
+    #[arg(long, help_heading = "Output")]
+    force: bool,
+    /// replace the source images with the sorted ones
+    #[arg(long, conflicts_with_all = ["output", "out_dir"], help_heading = "Output")]
+    in_place: bool,
+    /// keep a copy of every replaced image with this added to its name, e.g. `.bak`
+    #[arg(
+        long,
+        value_name = "SUFFIX",
+        requires = "in_place",
+        help_heading = "Output"
+    )]
+    backup_suffix: Option<String>,
+    /// leave the EXIF data (camera, date, location...) out of the sorted images, the color
+    /// profile is kept
+    #[arg(long, help_heading = "Output")]
+    strip_metadata: bool,
+    /// image format to write, e.g. png or jpeg, defaults to the extension of the output file
+    /// and to png for standard output
+    #[arg(long, value_parser = format_parser, help_heading = "Output")]
+    format: Option<image::ImageFormat>,
+    /// quality of jpegs, from 1 to 100
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 75,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help_heading = "Output"
+    )]
+    jpeg_quality: u8,
+    /// how many pixels of jpegs share a color: none (4:4:4), every two of a row (4:2:2) or
+    /// every square of four (4:2:0), which makes smaller files
+    #[arg(
+        long,
+        value_name = "RATIO",
+        default_value = "4:4:4",
+        help_heading = "Output"
+    )]
+    chroma_subsampling: SubsamplingArg,
+    /// how hard pngs are compressed, smaller files take longer to write
+    #[arg(
+        long,
+        value_name = "LEVEL",
+        default_value = "fast",
+        help_heading = "Output"
+    )]
+    png_compression: PngCompressionArg,
+    /// writes lossy webps of this quality, from 1 to 100, instead of lossless ones
+    #[arg(
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help_heading = "Output"
+    )]
+    webp_quality: Option<u8>,
+    /// quality of avifs, from 1 to 100
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 80,
+        value_parser = clap::value_parser!(u8).range(1..=100),
+        help_heading = "Output"
+    )]
+    avif_quality: u8,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum SubsamplingArg {
+    #[value(name = "4:4:4")]
+    Full,
+    #[value(name = "4:2:2")]
+    Half,
+    #[value(name = "4:2:0")]
+    Quarter,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum PngCompressionArg {
+    Fast,
+    Balanced,
+    Best,
+}
+
+impl PngCompressionArg {
+    fn compression(self) -> PngCompression {
+        match self {
+            PngCompressionArg::Fast => PngCompression::Fast,
+            PngCompressionArg::Balanced => PngCompression::Balanced,
+            PngCompressionArg::Best => PngCompression::Best,
+        }
+    }
+}
+
+impl OutputArgs {
+    pub fn output(
+        self,
+        image_count: usize,
+        config: &config::Config,
+    ) -> Result<pipeline::Output, String> {
+        if self.output.is_some() && image_count > 1 {
+            return Err("--output only works for a single image, use --out-dir".to_string());
+        }
+
+        let default_directory = (self.output.is_none() && !self.in_place)
+            .then(|| config.out_dir.clone())
+            .flatten();
+        Ok(pipeline::Output {
+            file: self.output,
+            directory: self.out_dir.or(default_directory),
+            force: self.force,
+            in_place: self.in_place,
+            backup_suffix: self.backup_suffix,
+            strip_metadata: self.strip_metadata,
+            format: self.format,
+            encoding: EncodeOptions {
+                jpeg_quality: self.jpeg_quality,
+                chroma_subsampling: match self.chroma_subsampling {
+                    SubsamplingArg::Full => ChromaSubsampling::Full,
+                    SubsamplingArg::Half => ChromaSubsampling::Half,
+                    SubsamplingArg::Quarter => ChromaSubsampling::Quarter,
+                },
+                png_compression: self.png_compression.compression(),
+                webp_quality: self.webp_quality,
+                avif_quality: self.avif_quality,
+            },
+            ..Default::default()
+        })
+    }
+}
+
+/// a pass without any images, used for all but the last pass and for pipeline files
+#[derive(Parser)]
+#[command(name = "pass", no_binary_name = true)]
+pub struct PassOnly {
+    #[command(flatten)]
+    pass: PassArgs,
+    /// may be left out when the config file or a preset has them
+    #[arg(value_name = "KEY [LOWER HIGHER]")]
+    positional: Vec<String>,
+}
+
+impl PassOnly {
+    pub fn parse_pass(args: &[String], config: &config::Config) -> Result<Pass, String> {
+        let (args, config) = preset::expand(args, config)?;
+        let PassOnly {
+            pass,
+            mut positional,
+        } = PassOnly::try_parse_from(&args).map_err(|e| e.to_string().trim().to_string())?;
+
+        let pass = pass.build(&mut positional, &config)?;
+        if !positional.is_empty() {
+            return Err(format!(
+                "unexpected arguments {}, images must come after the last pass",
+                positional.join(" ")
+            ));
+        }
+        Ok(pass)
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum TraversalArg {
+    Rows,
+    Radial,
+    Spiral,
+    Hilbert,
+    WholeRows,
+    WholeColumns,
+}
+
+impl TraversalArg {
+    fn traversal(self, center: Option<(usize, usize)>) -> Traversal {
+        match self {
+            TraversalArg::Rows => Traversal::Rows,
+            TraversalArg::Radial => Traversal::Radial { center },
+            TraversalArg::Spiral => Traversal::Spiral { center },
+            TraversalArg::Hilbert => Traversal::Hilbert,
+            TraversalArg::WholeRows => Traversal::Whole(ScanOrder::RowMajor),
+            TraversalArg::WholeColumns => Traversal::Whole(ScanOrder::ColumnMajor),
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OperationArg {
+    Sort,
+    Shuffle,
+    Smear,
+    SmearBrightest,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum EdgesArg {
+    Sobel,
+    Canny,
+}
+
+#[derive(Args)]
+pub struct PassArgs {
+    /// order in which pixels are visited, radial when only --center is given
+    #[arg(long, help_heading = "Traversal")]
+    traversal: Option<TraversalArg>,
+    /// sort along straight lines this many degrees clockwise from left to right, 90 sorts
+    /// down the columns
+    #[arg(long, value_name = "DEGREES", allow_negative_numbers = true, conflicts_with_all = ["traversal", "center"], help_heading = "Traversal")]
+    angle: Option<f32>,
+    /// center of radial and spiral traversals, the middle of the image by default
+    #[arg(long, value_name = "X,Y", value_parser = center_parser, help_heading = "Traversal")]
+    center: Option<(usize, usize)>,
+    /// sort along the lines of an svg file instead
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["traversal", "angle", "center"], help_heading = "Traversal")]
+    svg: Option<PathBuf>,
+    /// run the traversal in tiles of this size
+    #[arg(long, value_name = "N", value_parser = clap::value_parser!(u32).range(1..), help_heading = "Traversal")]
+    block: Option<u32>,
+    /// paths are circular, intervals reaching the end continue from the start
+    #[arg(long, help_heading = "Traversal")]
+    wrap: bool,
+
+    /// the key compared against the thresholds, the sort key by default
+    #[arg(long, value_name = "KEY", help_heading = "Selection")]
+    threshold_by: Option<String>,
+    /// pick thresholds for every image: otsu, percentile or percentile:N
+    #[arg(long, value_name = "METHOD", value_parser = auto_threshold_parser, help_heading = "Selection")]
+    auto_threshold: Option<AutoThreshold>,
+    /// select the pixels outside of the thresholds instead
+    #[arg(long, help_heading = "Selection")]
+    invert: bool,
+    /// operations on the selection, like blur:3,threshold:0.4,dilate:1,erode:1,invert
+    #[arg(long, value_name = "OPERATIONS", help_heading = "Selection")]
+    mask: Option<String>,
+    /// blend the sorted pixels in by the mask value
+    #[arg(long, help_heading = "Selection")]
+    soft_mask: bool,
+    /// only sort inside this rectangle, in pixels
+    #[arg(long, value_name = "X,Y,WIDTH,HEIGHT", value_parser = region_parser, help_heading = "Selection")]
+    region: Option<mask::Region>,
+    /// only sort where this grayscale image is white, fading out over gray
+    #[arg(
+        long,
+        value_name = "IMAGE",
+        conflicts_with = "region",
+        help_heading = "Selection"
+    )]
+    region_mask: Option<String>,
+    /// select with a noise pattern above this level (0-1) instead of the thresholds
+    #[arg(long, value_name = "LEVEL", help_heading = "Selection")]
+    noise: Option<f32>,
+    /// size of the coarsest noise features in pixels [default: 64]
+    #[arg(long, value_name = "N", help_heading = "Selection")]
+    noise_scale: Option<f32>,
+    /// layers of finer noise detail [default: 4]
+    #[arg(long, value_name = "N", help_heading = "Selection")]
+    noise_octaves: Option<u32>,
+    /// sort everything between detected edges instead
+    #[arg(long, help_heading = "Selection")]
+    edges: Option<EdgesArg>,
+    /// gradient (0-255) needed for a pixel to count as an edge [default: 64]
+    #[arg(long, value_name = "N", help_heading = "Selection")]
+    edge_threshold: Option<f32>,
+    /// compare the pixels of another image against the thresholds, by luminance by default
+    #[arg(long, value_name = "IMAGE", help_heading = "Selection")]
+    control: Option<String>,
+
+    /// intervals shorter than this are left unsorted
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help_heading = "Intervals"
+    )]
+    min_length: usize,
+    /// merge intervals separated by fewer than this many pixels
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help_heading = "Intervals"
+    )]
+    gap: usize,
+    /// split longer intervals into chunks
+    #[arg(long, value_name = "N", help_heading = "Intervals")]
+    max_length: Option<usize>,
+    /// split into chunks of random length up to --max-length
+    #[arg(long, help_heading = "Intervals")]
+    random_chunks: bool,
+    /// chance of cutting an interval short at every pixel
+    #[arg(long, value_name = "P", default_value_t = 0.0, value_parser = probability_parser, help_heading = "Intervals")]
+    split_chance: f64,
+    /// seed for shuffling, splitting, random shifts and noise, the same seed always gives
+    /// the same image
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        help_heading = "Intervals"
+    )]
+    seed: u64,
+
+    /// what happens to the pixels of every interval
+    #[arg(long, default_value = "sort", help_heading = "Effect")]
+    operation: OperationArg,
+    /// color the distance key measures from
+    #[arg(long, value_name = "#RRGGBB", value_parser = color_parser, help_heading = "Effect")]
+    ref_color: Option<egui::Color32>,
+    /// rhai expression used as the key x, like "r*0.5 + b - g"
+    #[arg(long, value_name = "EXPR", help_heading = "Effect")]
+    key_script: Option<String>,
+    /// key ordering pixels with the same primary key
+    #[arg(long, value_name = "KEY", help_heading = "Effect")]
+    then: Option<String>,
+    /// sort from the highest key to the lowest
+    #[arg(long, help_heading = "Effect")]
+    descending: bool,
+    /// reverse every other interval
+    #[arg(long, help_heading = "Effect")]
+    zigzag: bool,
+    /// sort every other path in the opposite direction
+    #[arg(long, help_heading = "Effect")]
+    alternate: bool,
+    /// move every interval along its path after sorting
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 0,
+        allow_negative_numbers = true,
+        help_heading = "Effect"
+    )]
+    shift: isize,
+    /// shift by a random amount up to --shift
+    #[arg(long, help_heading = "Effect")]
+    random_shift: bool,
+    /// sort the red, green and blue planes separately, each by its level. takes keys like l,
+    /// r, v or y, not h, s, a, d or x
+    #[arg(long, help_heading = "Effect")]
+    per_channel: bool,
+    /// blend the result with the original
+    #[arg(long, value_name = "PERCENT", default_value_t = 100.0, value_parser = percent_parser, help_heading = "Effect")]
+    strength: f32,
+}
+
+fn center_parser(arg: &str) -> Result<(usize, usize), String> {
+    parse_point(arg).ok_or_else(|| "expected a point in the form x,y".to_string())
+}
+
+fn region_parser(arg: &str) -> Result<mask::Region, String> {
+    mask::Region::parse_rect(arg)
+        .ok_or_else(|| "expected a rectangle in the form x,y,width,height".to_string())
+}
+
+fn color_parser(arg: &str) -> Result<egui::Color32, String> {
+    parse_color(arg).ok_or_else(|| "expected a color in the form #rrggbb".to_string())
+}
+
+fn auto_threshold_parser(arg: &str) -> Result<AutoThreshold, String> {
+    AutoThreshold::parse(arg).ok_or_else(|| "expected otsu, percentile or percentile:N".to_string())
+}
+
+fn probability_parser(arg: &str) -> Result<f64, String> {
+    match arg.parse::<f64>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err("expected a probability between 0 and 1".to_string()),
+    }
+}
+
+fn keyframe_parser(arg: &str) -> Result<(SweepParameter, usize, f32), String> {
+    let error = || "expected a keyframe in the form parameter:frame:value".to_string();
+    let [parameter, frame, value] = arg.split(':').collect::<Vec<_>>()[..] else {
+        return Err(error());
+    };
+    let parameter = SweepParameterArg::from_str(parameter, false)?;
+    let frame = frame.parse().map_err(|_| error())?;
+    let value = value.parse().map_err(|_| error())?;
+    Ok((parameter.parameter(), frame, value))
+}
+
+fn fraction_parser(arg: &str) -> Result<f32, String> {
+    match arg.parse::<f32>() {
+        Ok(value) if (0.0..=1.0).contains(&value) => Ok(value),
+        _ => Err("expected a number between 0 and 1".to_string()),
+    }
+}
+
+fn format_parser(arg: &str) -> Result<image::ImageFormat, String> {
+    match image::ImageFormat::from_extension(arg) {
+        // radiance hdr is written by `HdrImage` instead
+        Some(format) if format.can_write() || is_hdr_format(format) => Ok(format),
+        _ => Err("expected an image format like png, jpeg, bmp, tiff or webp".to_string()),
+    }
+}
+
+fn percent_parser(arg: &str) -> Result<f32, String> {
+    match arg.parse::<f32>() {
+        Ok(value) if (0.0..=100.0).contains(&value) => Ok(value),
+        _ => Err("expected a percentage between 0 and 100".to_string()),
+    }
+}
+
+impl PassArgs {
+    /// takes the sort key and the thresholds from the front of `positional`, leaving the rest.
+    /// they fall back to the ones in the config file
+    pub fn build(
+        self,
+        positional: &mut Vec<String>,
+        config: &config::Config,
+    ) -> Result<Pass, String> {
+        let with_reference_color = |mut key: SortBy| {
+            if let Some(color) = self.ref_color {
+                key.set_reference_color(color);
+            }
+            key
+        };
+        let parse_key = |arg: &String, name: &str| {
+            parse_sort_by(arg)
+                .map(with_reference_color)
+                .ok_or_else(|| format!("unknown key {} for {}", arg, name))
+        };
+
+        let traversal = match (self.svg, self.traversal) {
+            (Some(path), _) => Traversal::Svg(
+                svg::SvgPaths::load(&path)
+                    .map_err(|e| format!("cannot load svg paths from {}: {}", path.display(), e))?,
+            ),
+            (None, Some(traversal)) => traversal.traversal(self.center),
+            (None, None) => match (self.angle, self.center) {
+                (Some(angle), _) => Traversal::Lines { angle },
+                (None, Some(center)) => Traversal::Radial {
+                    center: Some(center),
+                },
+                (None, None) => Traversal::Rows,
+            },
+        };
+
+        let interval_options = IntervalOptions {
+            operation: match self.operation {
+                OperationArg::Sort => IntervalOperation::Sort,
+                OperationArg::Shuffle => IntervalOperation::Shuffle,
+                OperationArg::Smear => IntervalOperation::Smear(SmearPixel::First),
+                OperationArg::SmearBrightest => IntervalOperation::Smear(SmearPixel::Brightest),
+            },
+            min_length: self.min_length,
+            gap_tolerance: self.gap,
+            max_length: self.max_length,
+            random_chunks: self.random_chunks,
+            split_probability: self.split_chance,
+            seed: self.seed,
+            wrap_around: self.wrap,
+            shift: self.shift,
+            random_shift: self.random_shift,
+        };
+
+        let tie_breaker = self
+            .then
+            .as_ref()
+            .map(|arg| parse_key(arg, "--then"))
+            .transpose()?;
+        let mut threshold_key = self
+            .threshold_by
+            .as_ref()
+            .map(|arg| parse_key(arg, "--threshold-by"))
+            .transpose()?;
+        let mask_operations = match &self.mask {
+            Some(arg) => mask::MaskOperation::parse_list(arg)?,
+            None => Vec::new(),
+        };
+
+        let mut source = IntervalSource::Threshold;
+        if let Some(level) = self.noise {
+            let defaults = noise::NoiseMask::default();
+            source = IntervalSource::Noise(noise::NoiseMask {
+                scale: self.noise_scale.unwrap_or(defaults.scale),
+                octaves: self.noise_octaves.unwrap_or(defaults.octaves),
+                level,
+                seed: self.seed,
+            });
+        }
+        if let Some(edges) = self.edges {
+            source = IntervalSource::Edges(convolution::EdgeMask {
+                detector: match edges {
+                    EdgesArg::Sobel => convolution::EdgeDetector::Sobel,
+                    EdgesArg::Canny => convolution::EdgeDetector::Canny,
+                },
+                threshold: self
+                    .edge_threshold
+                    .unwrap_or(convolution::EdgeMask::default().threshold),
+            });
+        }
+        if let Some(path) = &self.control {
+            let control = load_image_from_path(path)
+                .map_err(|e| format!("cannot load control image {}: {}", path, e))?;
+            // control images are usually grayscale, so their luminance is the natural key
+            threshold_key.get_or_insert(SortBy::Luminance);
+            source = IntervalSource::Control(Arc::new(control));
+        }
+
+        let region = match &self.region_mask {
+            Some(path) => {
+                let mask = load_image_from_path(path)
+                    .map_err(|e| format!("cannot load region mask {}: {}", path, e))?;
+                Some(mask::Region::Mask(Arc::new(mask::RegionMask::from_image(
+                    &mask,
+                ))))
+            }
+            None => self.region,
+        };
+
+        let order = SortOrder {
+            descending: self.descending,
+            zigzag: self.zigzag,
+            alternate_paths: self.alternate,
+            tie_breaker,
+        };
+
+        let is_key = |arg: &String| parse_sort_by(arg).is_some() || arg == "x" || arg == "script";
+        if let Some(key) = &config.key {
+            if !positional.first().is_some_and(is_key) {
+                positional.insert(0, key.clone());
+            }
+        }
+        if positional.is_empty() {
+            return Err("missing the sort key".to_string());
+        }
+        let arg = positional.remove(0);
+        let sorting_method = match parse_sort_by(&arg) {
+            _ if arg == "x" || arg == "script" => match &self.key_script {
+                Some(source) => SortBy::Script(
+                    script::KeyScript::compile(source)
+                        .map_err(|e| format!("invalid key script: {}", e))?,
+                ),
+                None => return Err("sorting by a script requires --key-script".to_string()),
+            },
+            Some(method) => with_reference_color(method),
+            None => return Err(format!("unknown sort key {}, expected one of l (luminance), h (hue), s (saturation), r (red), g (green), b (blue), a (alpha), v (value), lightness, y (luma), d (distance) or x (script), or a weighted sum of them like 0.7*l+0.3*s", arg)),
+        };
+
+        // thresholds are optional when they're picked automatically or not used at all
+        let thresholds_given = self.auto_threshold.is_none()
+            && matches!(
+                source,
+                IntervalSource::Threshold | IntervalSource::Control(_)
+            );
+
+        let upper_boundary =
+            threshold_upper_boundary(threshold_key.as_ref().unwrap_or(&sorting_method));
+        let (lower_threshold, higher_threshold) = if thresholds_given {
+            let is_threshold = |arg: &String| parse_threshold(arg, upper_boundary).is_some();
+            if let Some(thresholds) = &config.thresholds {
+                if !positional.first().is_some_and(is_threshold) {
+                    positional.splice(0..0, thresholds.iter().map(|t| t.to_string()));
+                }
+            }
+            if positional.len() < 2 {
+                return Err("missing the lower and higher thresholds".to_string());
+            }
+            let mut threshold = || {
+                let arg = positional.remove(0);
+                parse_threshold(&arg, upper_boundary).ok_or_else(|| {
+                    format!(
+                        "invalid threshold {}, expected an integer or a percentage",
+                        arg
+                    )
+                })
+            };
+            (threshold()?, threshold()?)
+        } else {
+            (0, upper_boundary)
+        };
+
+        if lower_threshold > higher_threshold {
+            return Err("lower threshold cannot be bigger than a higher threshold".to_string());
+        }
+
+        // the planes are gray, other keys would sort or select nothing
+        if self.per_channel {
+            let threshold_key = threshold_key
+                .as_ref()
+                .filter(|_| !matches!(source, IntervalSource::Control(_)));
+            let keys = [
+                Some(&sorting_method),
+                threshold_key,
+                order.tie_breaker.as_ref(),
+            ];
+            if let Some(key) = keys.into_iter().flatten().find(|key| !key.is_gray_level()) {
+                return Err(format!(
+                    "--per-channel sorts every channel by its level, it cannot be used with key {}",
+                    key.name()
+                ));
+            }
+        }
+
+        Ok(Pass {
+            thresholds: Thresholds {
+                lower: lower_threshold,
+                higher: higher_threshold,
+                key: threshold_key,
+                invert: self.invert,
+                source,
+                mask_operations,
+                soft: self.soft_mask,
+                region,
+            },
+            auto_threshold: self.auto_threshold,
+            sort_by: sorting_method,
+            traversal,
+            block_size: self.block.map(|size| size as usize),
+            order,
+            interval_options,
+            per_channel: self.per_channel,
+            strength: self.strength / 100.0,
+        })
+    }
+}
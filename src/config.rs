@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+/// user defaults read from `~/.config/porter/config.toml`, e.g.
+///
+/// ```toml
+/// key = "h"
+/// thresholds = ["10%", 200]
+/// out-dir = "sorted"
+/// theme = "dark"
+/// webcam = "/dev/video1"
+/// ```
+///
+/// flags given on the command line win over these
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct Config {
+    /// sort key used when the command line doesn't start with one
+    pub key: Option<String>,
+    /// used when the command line has no thresholds, integers or percentages
+    pub thresholds: Option<[Threshold; 2]>,
+    /// where sorted images go unless --output, --out-dir or --in-place is given
+    pub out_dir: Option<PathBuf>,
+    pub theme: Theme,
+    /// the webcam of the gui as ffmpeg names it: /dev/video0 on linux, 0 on macos and
+    /// video=NAME on windows. the first one by default
+    pub webcam: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Threshold {
+    Value(u16),
+    Percent(String),
+}
+
+impl std::fmt::Display for Threshold {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Threshold::Value(value) => write!(f, "{}", value),
+            Threshold::Percent(percent) => write!(f, "{}", percent),
+        }
+    }
+}
+
+impl From<&str> for Threshold {
+    fn from(arg: &str) -> Threshold {
+        match arg.trim().parse() {
+            Ok(value) => Threshold::Value(value),
+            Err(_) => Threshold::Percent(arg.trim().to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    /// follows the system, light when it can't be told
+    #[default]
+    System,
+    Light,
+    Dark,
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|directory| directory.join("porter").join("config.toml"))
+    }
+
+    /// the defaults when there's no config file
+    pub fn load() -> Result<Config, String> {
+        let Some(path) = Config::path().filter(|path| path.exists()) else {
+            return Ok(Config::default());
+        };
+        let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+        toml::from_str(&source).map_err(|e| e.to_string())
+    }
+
+    pub fn save(&self) -> Result<PathBuf, String> {
+        let path = Config::path().ok_or("cannot find the config directory")?;
+        if let Some(directory) = path.parent() {
+            std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+        }
+        let source = toml::to_string(self).map_err(|e| e.to_string())?;
+        std::fs::write(&path, source).map_err(|e| e.to_string())?;
+        Ok(path)
+    }
+}
+
+/// the fields of the settings dialog, as typed
+pub struct Form {
+    pub key: String,
+    pub lower: String,
+    pub higher: String,
+    pub out_dir: String,
+    pub theme: Theme,
+    pub webcam: String,
+}
+
+impl Form {
+    pub fn new(config: &Config) -> Form {
+        let threshold = |i: usize| {
+            config
+                .thresholds
+                .as_ref()
+                .map_or(String::new(), |thresholds| thresholds[i].to_string())
+        };
+        Form {
+            key: config.key.clone().unwrap_or_default(),
+            lower: threshold(0),
+            higher: threshold(1),
+            out_dir: config
+                .out_dir
+                .as_ref()
+                .map_or(String::new(), |directory| directory.display().to_string()),
+            theme: config.theme,
+            webcam: config.webcam.clone().unwrap_or_default(),
+        }
+    }
+
+    /// checks the fields the way the command line would, empty ones are left unset
+    pub fn config(&self) -> Result<Config, String> {
+        let key = self.key.trim();
+        if !key.is_empty() && psorter::keys::parse_sort_by(key).is_none() {
+            return Err(format!("unknown sort key {}", key));
+        }
+
+        let thresholds = match (self.lower.trim(), self.higher.trim()) {
+            ("", "") => None,
+            (lower, higher) => {
+                for threshold in [lower, higher] {
+                    if psorter::keys::parse_threshold(threshold, u16::MAX).is_none() {
+                        return Err(format!(
+                            "invalid threshold {}, expected an integer or a percentage",
+                            threshold
+                        ));
+                    }
+                }
+                Some([Threshold::from(lower), Threshold::from(higher)])
+            }
+        };
+
+        Ok(Config {
+            key: (!key.is_empty()).then(|| key.to_string()),
+            thresholds,
+            out_dir: (!self.out_dir.trim().is_empty()).then(|| PathBuf::from(self.out_dir.trim())),
+            theme: self.theme,
+            webcam: (!self.webcam.trim().is_empty()).then(|| self.webcam.trim().to_string()),
+        })
+    }
+}
@@ -0,0 +1,155 @@
+const SOBEL_X: [[f32; 3]; 3] = [[-1.0, 0.0, 1.0], [-2.0, 0.0, 2.0], [-1.0, 0.0, 1.0]];
+const SOBEL_Y: [[f32; 3]; 3] = [[-1.0, -2.0, -1.0], [0.0, 0.0, 0.0], [1.0, 2.0, 1.0]];
+pub const GAUSSIAN: [[f32; 3]; 3] = [
+    [1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0],
+    [2.0 / 16.0, 4.0 / 16.0, 2.0 / 16.0],
+    [1.0 / 16.0, 2.0 / 16.0, 1.0 / 16.0],
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum EdgeDetector {
+    /// gradient magnitude above the threshold
+    Sobel,
+    /// thin edges with hysteresis, the weak threshold is half of the strong one
+    Canny,
+}
+
+/// marks the pixels on edges of the image, intervals then run between them
+#[derive(Clone, PartialEq)]
+pub struct EdgeMask {
+    pub detector: EdgeDetector,
+    /// gradient magnitude (0-255) needed for a pixel to count as an edge
+    pub threshold: f32,
+}
+
+impl Default for EdgeMask {
+    fn default() -> Self {
+        EdgeMask {
+            detector: EdgeDetector::Sobel,
+            threshold: 64.0,
+        }
+    }
+}
+
+impl EdgeMask {
+    /// `true` for the pixels that are not on an edge
+    pub fn mask(&self, image: &egui::ColorImage) -> Vec<bool> {
+        let [width, height] = image.size;
+        let luminance: Vec<f32> = image
+            .pixels
+            .iter()
+            .map(|pixel| crate::keys::luminance(crate::keys::straight(pixel)) as f32)
+            .collect();
+
+        let edges = match self.detector {
+            EdgeDetector::Sobel => {
+                let (gx, gy) = gradients(&luminance, width, height);
+                magnitudes(&gx, &gy)
+                    .into_iter()
+                    .map(|magnitude| magnitude > self.threshold)
+                    .collect()
+            }
+            EdgeDetector::Canny => canny(&luminance, width, height, self.threshold),
+        };
+
+        edges.into_iter().map(|edge: bool| !edge).collect()
+    }
+}
+
+/// convolves a single channel with a 3x3 kernel, clamping at the borders
+pub fn convolve(values: &[f32], width: usize, height: usize, kernel: &[[f32; 3]; 3]) -> Vec<f32> {
+    let mut result = vec![0.0; values.len()];
+
+    for y in 0..height {
+        for x in 0..width {
+            let mut sum = 0.0;
+            for (ky, row) in kernel.iter().enumerate() {
+                let sy = (y + ky).saturating_sub(1).min(height - 1);
+                for (kx, weight) in row.iter().enumerate() {
+                    let sx = (x + kx).saturating_sub(1).min(width - 1);
+                    sum += weight * values[sy * width + sx];
+                }
+            }
+            result[y * width + x] = sum;
+        }
+    }
+
+    result
+}
+
+fn gradients(values: &[f32], width: usize, height: usize) -> (Vec<f32>, Vec<f32>) {
+    (
+        convolve(values, width, height, &SOBEL_X),
+        convolve(values, width, height, &SOBEL_Y),
+    )
+}
+
+/// the sobel kernels scale the gradient by 4, so this brings it back to 0-255
+fn magnitudes(gx: &[f32], gy: &[f32]) -> Vec<f32> {
+    gx.iter().zip(gy).map(|(x, y)| x.hypot(*y) / 4.0).collect()
+}
+
+fn canny(values: &[f32], width: usize, height: usize, threshold: f32) -> Vec<bool> {
+    let blurred = convolve(values, width, height, &GAUSSIAN);
+    let (gx, gy) = gradients(&blurred, width, height);
+    let magnitude = magnitudes(&gx, &gy);
+
+    // non-maximum suppression: keep pixels that are the strongest along their gradient
+    let at = |x: isize, y: isize| {
+        if (0..width as isize).contains(&x) && (0..height as isize).contains(&y) {
+            magnitude[y as usize * width + x as usize]
+        } else {
+            0.0
+        }
+    };
+    let mut thin = vec![0.0; magnitude.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let i = y * width + x;
+            let angle = gy[i].atan2(gx[i]).to_degrees().rem_euclid(180.0);
+            let (dx, dy) = match angle {
+                a if !(22.5..157.5).contains(&a) => (1, 0),
+                a if a < 67.5 => (1, 1),
+                a if a < 112.5 => (0, 1),
+                _ => (-1, 1),
+            };
+            let (x, y) = (x as isize, y as isize);
+            if magnitude[i] >= at(x + dx, y + dy) && magnitude[i] >= at(x - dx, y - dy) {
+                thin[i] = magnitude[i];
+            }
+        }
+    }
+
+    // hysteresis: weak edges survive only when connected to a strong one
+    let low = threshold / 2.0;
+    let mut edges = vec![false; thin.len()];
+    let mut stack: Vec<usize> = (0..thin.len()).filter(|&i| thin[i] > threshold).collect();
+    while let Some(i) = stack.pop() {
+        if edges[i] {
+            continue;
+        }
+        edges[i] = true;
+
+        let (x, y) = ((i % width) as isize, (i / width) as isize);
+        for (dx, dy) in [
+            (-1, -1),
+            (0, -1),
+            (1, -1),
+            (-1, 0),
+            (1, 0),
+            (-1, 1),
+            (0, 1),
+            (1, 1),
+        ] {
+            let (nx, ny) = (x + dx, y + dy);
+            if (0..width as isize).contains(&nx) && (0..height as isize).contains(&ny) {
+                let neighbour = ny as usize * width + nx as usize;
+                if !edges[neighbour] && thin[neighbour] > low {
+                    stack.push(neighbour);
+                }
+            }
+        }
+    }
+
+    edges
+}
@@ -0,0 +1,161 @@
+//! EXIF metadata: its orientation turns images upright when they're loaded, and the rest is
+//! copied into the sorted file
+use crate::io::{insert_jpeg_segments, insert_png_chunk, jpeg_segment};
+use std::io::Read;
+
+/// the orientation tag
+const ORIENTATION: u16 = 0x0112;
+
+/// the EXIF data of a png or a jpeg, from its TIFF header on. only the start of the image is
+/// read, up to the pixels
+pub fn read_exif(mut reader: impl Read) -> Option<Vec<u8>> {
+    let mut signature = [0; 8];
+    reader.read_exact(&mut signature[..2]).ok()?;
+    if signature[..2] == [0xff, 0xd8] {
+        loop {
+            let mut marker = [0; 4];
+            reader.read_exact(&mut marker).ok()?;
+            // the pixels start at the start of scan
+            if marker[0] != 0xff || marker[1] == 0xda {
+                return None;
+            }
+            let length = u16::from_be_bytes([marker[2], marker[3]]) as u64;
+            let mut data = Vec::new();
+            (&mut reader)
+                .take(length.checked_sub(2)?)
+                .read_to_end(&mut data)
+                .ok()?;
+            if marker[1] == 0xe1 && data.starts_with(b"Exif\0\0") {
+                return Some(data.split_off(6));
+            }
+        }
+    }
+
+    reader.read_exact(&mut signature[2..]).ok()?;
+    if signature != *b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    loop {
+        let mut header = [0; 8];
+        reader.read_exact(&mut header).ok()?;
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as u64;
+        if &header[4..] == b"IDAT" {
+            return None;
+        }
+        // the chunk and its checksum
+        let mut data = Vec::new();
+        (&mut reader).take(length + 4).read_to_end(&mut data).ok()?;
+        if &header[4..] == b"eXIf" {
+            data.truncate(length as usize);
+            return Some(data);
+        }
+    }
+}
+
+/// `read_exif` of the file at `path`, none for standard input
+pub fn read_exif_file(path: &str) -> Option<Vec<u8>> {
+    if path == "-" {
+        return None;
+    }
+    read_exif(std::io::BufReader::new(std::fs::File::open(path).ok()?))
+}
+
+/// where the value of the orientation is in `exif` and whether it's big endian
+fn orientation_value(exif: &[u8]) -> Option<(usize, bool)> {
+    let big_endian = match exif.get(..2)? {
+        b"MM" => true,
+        b"II" => false,
+        _ => return None,
+    };
+    let read_u16 = |offset: usize| {
+        let bytes = exif.get(offset..offset + 2)?.try_into().ok()?;
+        Some(if big_endian {
+            u16::from_be_bytes(bytes)
+        } else {
+            u16::from_le_bytes(bytes)
+        })
+    };
+    let first_directory = exif.get(4..8)?.try_into().ok()?;
+    let first_directory = if big_endian {
+        u32::from_be_bytes(first_directory)
+    } else {
+        u32::from_le_bytes(first_directory)
+    } as usize;
+
+    let entries = read_u16(first_directory)? as usize;
+    (0..entries).find_map(|i| {
+        let entry = first_directory + 2 + i * 12;
+        (read_u16(entry)? == ORIENTATION).then_some((entry + 8, big_endian))
+    })
+}
+
+/// from 1 to 8, 1 being upright
+pub fn orientation(exif: &[u8]) -> Option<u16> {
+    let (offset, big_endian) = orientation_value(exif)?;
+    let bytes = exif.get(offset..offset + 2)?.try_into().ok()?;
+    Some(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+/// `image` the way it's meant to be seen according to `orientation`, rotated and mirrored
+pub fn upright(image: egui::ColorImage, orientation: u16) -> egui::ColorImage {
+    let [width, height] = image.size;
+    // the pixel of `image` at every column and row of the upright image
+    let source: fn(usize, usize, usize, usize) -> (usize, usize) = match orientation {
+        2 => |x, y, width, _| (width - 1 - x, y),
+        3 => |x, y, width, height| (width - 1 - x, height - 1 - y),
+        4 => |x, y, _, height| (x, height - 1 - y),
+        5 => |x, y, _, _| (y, x),
+        6 => |x, y, _, height| (y, height - 1 - x),
+        7 => |x, y, width, height| (width - 1 - y, height - 1 - x),
+        8 => |x, y, width, _| (width - 1 - y, x),
+        _ => return image,
+    };
+    let size = if orientation >= 5 {
+        [height, width]
+    } else {
+        [width, height]
+    };
+
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..size[1] {
+        for x in 0..size[0] {
+            let (x, y) = source(x, y, width, height);
+            pixels.push(image.pixels[y * width + x]);
+        }
+    }
+    egui::ColorImage { size, pixels }
+}
+
+/// adds `exif` to an image encoded in `format`, only pngs and jpegs can hold it. the image is
+/// upright by now, so its orientation is reset
+pub fn embed_exif(
+    bytes: &mut Vec<u8>,
+    format: image::ImageFormat,
+    exif: &[u8],
+) -> Result<(), String> {
+    let mut exif = exif.to_vec();
+    if let Some((offset, big_endian)) = orientation_value(&exif) {
+        if let Some(value) = exif.get_mut(offset..offset + 2) {
+            let upright = if big_endian {
+                1u16.to_be_bytes()
+            } else {
+                1u16.to_le_bytes()
+            };
+            value.copy_from_slice(&upright);
+        }
+    }
+
+    match format {
+        image::ImageFormat::Png => insert_png_chunk(bytes, b"eXIf", &exif),
+        image::ImageFormat::Jpeg => {
+            let segment = jpeg_segment(0xe1, &[&b"Exif\0\0"[..], &exif].concat())
+                .map_err(|_| "the EXIF data is too big for a jpeg")?;
+            insert_jpeg_segments(bytes, segment)
+        }
+        format => Err(format!("{:?} files cannot hold EXIF data", format)),
+    }
+}
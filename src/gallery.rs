@@ -0,0 +1,84 @@
+//! settings picked at random in the gui, sorted on a small copy of the image side by side so
+//! one can be picked to go on from
+use crate::preview::{downscale, scale_pass};
+use psorter::intervals::{ScanOrder, Traversal};
+use psorter::keys::{threshold_upper_boundary, SortBy};
+use psorter::sort::Pass;
+use rand::Rng;
+use rayon::prelude::*;
+
+/// how many settings are rolled at once
+pub const CANDIDATES: usize = 9;
+/// the thumbnails fit in this many pixels both ways
+pub const THUMBNAIL_SIZE: usize = 160;
+
+/// `pass` with another key, thresholds, traversal, direction and seed. what it selects in
+/// and the rest is kept
+pub fn random_pass(pass: &Pass, rng: &mut impl Rng) -> Pass {
+    let keys = [
+        SortBy::Luminance,
+        SortBy::Hue,
+        SortBy::Saturation,
+        SortBy::Red,
+        SortBy::Green,
+        SortBy::Blue,
+        SortBy::Value,
+        SortBy::Lightness,
+        SortBy::Luma,
+    ];
+    let traversals = [
+        Traversal::Rows,
+        Traversal::Lines {
+            angle: rng.gen_range(0..360) as f32,
+        },
+        Traversal::Radial { center: None },
+        Traversal::Spiral { center: None },
+        Traversal::Hilbert,
+        Traversal::Whole(ScanOrder::RowMajor),
+        Traversal::Whole(ScanOrder::ColumnMajor),
+    ];
+
+    // per channel sorting only tells levels apart
+    let keys: Vec<&SortBy> = keys
+        .iter()
+        .filter(|key| !pass.per_channel || key.is_gray_level())
+        .collect();
+
+    let mut random = pass.clone();
+    random.sort_by = keys[rng.gen_range(0..keys.len())].clone();
+    let key = random.thresholds.key.as_ref().unwrap_or(&random.sort_by);
+    let upper_boundary = threshold_upper_boundary(key);
+    let lower = rng.gen_range(0..=upper_boundary / 2);
+    random.thresholds.lower = lower;
+    random.thresholds.higher = rng.gen_range(lower..=upper_boundary);
+    random.traversal = traversals[rng.gen_range(0..traversals.len())].clone();
+    random.order.descending = rng.gen();
+    random.interval_options.seed = rng.gen::<u32>() as u64;
+    random
+}
+
+/// `image` sorted by `passes` and then by every one of `candidates`, scaled down to
+/// thumbnails
+pub fn thumbnails(
+    image: &egui::ColorImage,
+    passes: &[Pass],
+    candidates: &[Pass],
+) -> Vec<egui::ColorImage> {
+    let (mut small, scale) =
+        downscale(image, THUMBNAIL_SIZE).unwrap_or_else(|| (image.clone(), 1.0));
+    for pass in passes {
+        let mut pass = pass.clone();
+        scale_pass(&mut pass, scale);
+        pass.apply(&mut small);
+    }
+    candidates
+        .par_iter()
+        .map(|candidate| {
+            let mut candidate = candidate.clone();
+            scale_pass(&mut candidate, scale);
+            let mut thumbnail = small.clone();
+            candidate.apply(&mut thumbnail);
+            thumbnail
+        })
+        .collect()
+}
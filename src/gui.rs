@@ -0,0 +1,3240 @@
+use crate::gallery;
+use crate::history::Snapshot;
+use crate::preview::{Job, Sorter};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::session::{self, Session};
+use crate::tabs::Tab;
+use crate::widgets::{AngleDial, RangeSlider};
+use crate::{cli, config, preset};
+use psorter::intervals::{
+    IntervalOperation, IntervalOptions, IntervalSource, ScanOrder, SmearPixel, Thresholds,
+    Traversal,
+};
+use psorter::io::{
+    basename, decode_image, encode_image, EncodeOptions, PngCompression, IMAGE_EXTENSIONS,
+};
+use psorter::jpeg::ChromaSubsampling;
+use psorter::keys::{
+    key_histogram, parse_sort_by, parse_threshold, threshold_from_percent,
+    threshold_upper_boundary, AutoThreshold, KeyExpression, SortBy,
+};
+use psorter::sort::{Pass, SortOrder};
+use psorter::sweep::{Sweep, SweepParameter};
+use psorter::timeline::{Timeline, Track};
+use psorter::video::{Recorder, Webcam, VIDEO_EXTENSIONS};
+use psorter::{convolution, mask, noise, script, svg};
+use rand::Rng;
+use std::collections::{BTreeMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use web_time::Instant;
+
+#[cfg(target_arch = "wasm32")]
+use crate::web::{pick_file, save_animation, save_image};
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_image(
+    image: &egui::ColorImage,
+    name: &str,
+    directory: Option<&Path>,
+    options: &EncodeOptions,
+) {
+    let mut dialog = rfd::FileDialog::new();
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+    let picked_path = if let Some(path) = dialog
+        .set_file_name(name)
+        .add_filter("Image Files", &IMAGE_EXTENSIONS)
+        // written but not read, so it isn't one of the image extensions
+        .add_filter("AVIF", &["avif"])
+        .save_file()
+    {
+        path.display().to_string()
+    } else {
+        return;
+    };
+
+    encode_image(image, Path::new(&picked_path), None, options)
+        .and_then(|bytes| std::fs::write(&picked_path, bytes).map_err(|e| e.to_string()))
+        .unwrap_or_else(|_| panic!("ERROR: failed to save file {}", &picked_path));
+}
+
+/// renders `timeline` and saves it where the user picks, as a gif, an APNG or a video
+#[cfg(not(target_arch = "wasm32"))]
+fn save_animation(
+    timeline: &Timeline,
+    image: &egui::ColorImage,
+    passes: &[Pass],
+    name: &str,
+    directory: Option<&Path>,
+    options: &EncodeOptions,
+) -> Result<(), String> {
+    let mut dialog = rfd::FileDialog::new();
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+    let extensions: Vec<&str> = ["gif", "png"].into_iter().chain(VIDEO_EXTENSIONS).collect();
+    let Some(path) = dialog
+        .set_file_name(&format!("{}.gif", name))
+        .add_filter("Animations", &extensions)
+        .save_file()
+    else {
+        return Ok(());
+    };
+
+    timeline
+        .render(image, passes)?
+        .save(&path, options.png_compression.png())
+        .map_err(|e| format!("failed to save file {}: {}", path.display(), e))
+}
+
+/// the name of an image without its extension, to name what's saved from it
+fn file_stem(name: &str) -> String {
+    Path::new(name)
+        .file_stem()
+        .map_or(name.to_string(), |stem| stem.to_string_lossy().to_string())
+}
+
+/// the image on the clipboard, none when it holds something else
+#[cfg(not(target_arch = "wasm32"))]
+fn paste_image(clipboard: &mut arboard::Clipboard) -> Result<Option<egui::ColorImage>, String> {
+    match clipboard.get_image() {
+        Ok(image) => Ok(Some(egui::ColorImage::from_rgba_unmultiplied(
+            [image.width, image.height],
+            &image.bytes,
+        ))),
+        Err(arboard::Error::ContentNotAvailable) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// puts `image` on the clipboard
+#[cfg(not(target_arch = "wasm32"))]
+fn copy_image(clipboard: &mut arboard::Clipboard, image: &egui::ColorImage) -> Result<(), String> {
+    clipboard
+        .set_image(arboard::ImageData {
+            width: image.width(),
+            height: image.height(),
+            bytes: psorter::io::straight_rgba(&image.pixels).into(),
+        })
+        .map_err(|e| e.to_string())
+}
+
+/// zoomed in past 100% pixels are shown as squares, so single pixels can be told apart
+const PREVIEW_TEXTURE: egui::TextureOptions = egui::TextureOptions {
+    magnification: egui::TextureFilter::Nearest,
+    minification: egui::TextureFilter::Linear,
+};
+
+/// thresholds `band` apart around the key of `pixel` of an image of `size`, read from the
+/// same spot of `image` when it's a control image of another size
+fn band_around(
+    image: &egui::ColorImage,
+    (x, y): (usize, usize),
+    size: [usize; 2],
+    key: &SortBy,
+    band: u16,
+) -> (u16, u16) {
+    let pixel = image[(x * image.width() / size[0], y * image.height() / size[1])];
+    let mut keys = Vec::new();
+    key.keys(&[pixel], &mut keys);
+    let value = keys[0];
+    (
+        value.saturating_sub(band / 2),
+        value
+            .saturating_add(band - band / 2)
+            .min(threshold_upper_boundary(key)),
+    )
+}
+
+/// what picks the pixels to sort, the settings of every one are kept while another is used
+#[derive(Clone, Copy, PartialEq)]
+enum SelectionSource {
+    Thresholds,
+    Noise,
+    Edges,
+    Control,
+}
+
+impl SelectionSource {
+    const ALL: [SelectionSource; 4] = [
+        SelectionSource::Thresholds,
+        SelectionSource::Noise,
+        SelectionSource::Edges,
+        SelectionSource::Control,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            SelectionSource::Thresholds => "Thresholds",
+            SelectionSource::Noise => "Noise",
+            SelectionSource::Edges => "Edges",
+            SelectionSource::Control => "Control image",
+        }
+    }
+}
+
+/// what dragging on the preview does instead of moving it
+#[derive(Clone, Copy, PartialEq)]
+enum RegionTool {
+    /// drags out the rectangle to sort in
+    Rectangle,
+    /// paints the mask to sort in
+    Brush,
+    /// draws around the shape to sort in
+    Lasso,
+}
+
+struct Brush {
+    /// in pixels of the image
+    radius: f32,
+    /// how much of the radius fades out, from 0 to 1
+    softness: f32,
+    erase: bool,
+}
+
+impl Default for Brush {
+    fn default() -> Self {
+        Brush {
+            radius: 30.0,
+            softness: 0.5,
+            erase: false,
+        }
+    }
+}
+
+/// `region` with a stroke of `brush` from `from` to `to` painted on it, in pixels of an image
+/// of `size`. erasing starts from the whole image when there's no region yet
+fn paint_region(
+    region: Option<&mask::Region>,
+    size: [usize; 2],
+    from: egui::Pos2,
+    to: egui::Pos2,
+    brush: &Brush,
+) -> mask::Region {
+    let mut painted = match region {
+        Some(region) => region.to_mask(size),
+        None if brush.erase => mask::RegionMask {
+            size,
+            values: vec![255; size[0] * size[1]],
+        },
+        None => mask::RegionMask::new(size),
+    };
+    // a mask of another size is painted where the pointer is on it
+    let to_mask = |position: egui::Pos2| {
+        (
+            position.x * painted.size[0] as f32 / size[0] as f32,
+            position.y * painted.size[1] as f32 / size[1] as f32,
+        )
+    };
+    let radius = brush.radius * painted.size[0] as f32 / size[0] as f32;
+    let (from, to) = (to_mask(from), to_mask(to));
+    painted.stroke(from, to, radius, brush.softness, brush.erase);
+    mask::Region::Mask(Arc::new(painted))
+}
+
+/// the polygon through `points` filled on a mask of an image of `size`, added to `region`
+/// when `add` is set and replacing it otherwise
+fn lasso_region(
+    region: Option<&mask::Region>,
+    size: [usize; 2],
+    points: &[egui::Pos2],
+    add: bool,
+) -> mask::Region {
+    let mut filled = match region.filter(|_| add) {
+        Some(region) => region.to_mask(size),
+        None => mask::RegionMask::new(size),
+    };
+    let points: Vec<_> = points
+        .iter()
+        .map(|point| {
+            (
+                point.x * filled.size[0] as f32 / size[0] as f32,
+                point.y * filled.size[1] as f32 / size[1] as f32,
+            )
+        })
+        .collect();
+    filled.fill_polygon(&points);
+    mask::Region::Mask(Arc::new(filled))
+}
+
+/// the parts of the image `mask` leaves out darkened
+fn mask_overlay(mask: &mask::RegionMask) -> egui::ColorImage {
+    egui::ColorImage {
+        size: mask.size,
+        pixels: mask
+            .values
+            .iter()
+            .map(|&value| egui::Color32::from_black_alpha((255 - value) / 2))
+            .collect(),
+    }
+}
+
+/// while the settings are dragged, images bigger than this both ways are sorted scaled down
+const PROXY_SIZE: usize = 1024;
+/// how long a drag has to hold still before the whole image is sorted
+const PROXY_PAUSE: Duration = Duration::from_millis(300);
+
+/// how often the session is saved at most, while it changes
+#[cfg(not(target_arch = "wasm32"))]
+const AUTOSAVE_INTERVAL: Duration = Duration::from_secs(10);
+
+/// webcam frames are scaled down to this width, so they can be sorted as fast as they come
+const WEBCAM_WIDTH: usize = 640;
+
+/// where to record the webcam, a video picked by the user
+#[cfg(not(target_arch = "wasm32"))]
+fn pick_recording(directory: Option<&Path>) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new();
+    if let Some(directory) = directory {
+        dialog = dialog.set_directory(directory);
+    }
+    dialog
+        .set_file_name("webcam.mp4")
+        .add_filter("Videos", &VIDEO_EXTENSIONS)
+        .save_file()
+}
+
+/// what a file is picked for in the gui
+#[derive(Clone, Copy)]
+pub enum PickFor {
+    Image,
+    /// an image opened next to the one shown
+    Tab,
+    Control,
+    Svg,
+}
+
+impl PickFor {
+    pub fn filter(self) -> (&'static str, &'static [&'static str]) {
+        match self {
+            PickFor::Image | PickFor::Tab | PickFor::Control => ("Image Files", &IMAGE_EXTENSIONS),
+            PickFor::Svg => ("SVG Files", &["svg"]),
+        }
+    }
+}
+
+/// a picked file, read whole since the browser only hands out the contents
+pub struct PickedFile {
+    pub purpose: PickFor,
+    pub name: String,
+    pub bytes: Result<Vec<u8>, String>,
+}
+
+/// the browser answers file dialogs later, so picked files are queued for the next frame
+pub type PickedFiles = Arc<Mutex<Vec<PickedFile>>>;
+
+#[cfg(not(target_arch = "wasm32"))]
+fn pick_file(purpose: PickFor, picked: &PickedFiles, _ctx: &egui::Context) {
+    let (filter, extensions) = purpose.filter();
+    if let Some(path) = rfd::FileDialog::new()
+        .add_filter(filter, extensions)
+        .pick_file()
+    {
+        picked.lock().unwrap().push(PickedFile {
+            purpose,
+            name: path.display().to_string(),
+            bytes: std::fs::read(&path)
+                .map_err(|e| format!("cannot read {}: {}", path.display(), e)),
+        });
+    }
+}
+
+/// an image dropped on the window, its path natively and its contents in the browser
+fn dropped_image(file: egui::DroppedFile) -> PickedFile {
+    let (name, bytes) = match (file.path, file.bytes) {
+        (_, Some(bytes)) => (file.name, Ok(bytes.to_vec())),
+        (Some(path), None) => (
+            path.display().to_string(),
+            std::fs::read(&path).map_err(|e| format!("cannot read {}: {}", path.display(), e)),
+        ),
+        (None, None) => (file.name.clone(), Err(format!("cannot read {}", file.name))),
+    };
+    PickedFile {
+        purpose: PickFor::Image,
+        name,
+        bytes,
+    }
+}
+
+/// whatever a preset leaves out keeps its current value
+fn preset_defaults(
+    config: &config::Config,
+    sort_by: &SortBy,
+    lower_threshold: u16,
+    higher_threshold: u16,
+) -> config::Config {
+    config::Config {
+        key: Some(sort_by.name()),
+        thresholds: Some([
+            config::Threshold::Value(lower_threshold),
+            config::Threshold::Value(higher_threshold),
+        ]),
+        ..config.clone()
+    }
+}
+
+/// the settings of `preset`, saved under `name`, read the way `--preset` reads them. presets
+/// without a script sort by `script_source` when they sort by a script, and automatic
+/// thresholds are worked out on `image`
+fn preset_pass(
+    preset: &preset::Preset,
+    name: &str,
+    defaults: &config::Config,
+    script_source: &str,
+    image: &egui::ColorImage,
+) -> Result<Pass, String> {
+    let mut args = vec!["--preset".to_string(), name.to_string()];
+    if !preset.contains_key("key-script") {
+        args.extend(["--key-script".to_string(), script_source.to_string()]);
+    }
+    let mut pass = cli::PassOnly::parse_pass(&args, defaults)?;
+    if let Some(automatic) = pass.auto_threshold.take() {
+        let key = pass.thresholds.key.as_ref().unwrap_or(&pass.sort_by);
+        let threshold_image = pass.thresholds.source.threshold_image(image);
+        (pass.thresholds.lower, pass.thresholds.higher) =
+            automatic.thresholds(&key_histogram(threshold_image, key));
+    }
+    Ok(pass)
+}
+
+/// listed in the shortcuts window
+const SHORTCUTS: [(&str, &str); 11] = [
+    ("Ctrl+O", "open an image"),
+    ("Ctrl+S", "save the sorted image"),
+    ("Ctrl+V", "paste an image"),
+    ("Ctrl+C", "copy the sorted image"),
+    ("Ctrl+Z", "undo"),
+    ("Ctrl+Shift+Z, Ctrl+Y", "redo"),
+    ("1, 2, 3", "sort by luminance, hue or saturation"),
+    ("Left, Right", "move the lower threshold, by 10 with shift"),
+    ("Down, Up", "move the higher threshold, by 10 with shift"),
+    ("Space", "hold to compare with the original image"),
+    ("F1", "show the shortcuts"),
+];
+
+/// how far the presses of the arrow `key` move a threshold this frame, by 1 or by 10 with
+/// shift
+fn arrow_steps(input: &mut egui::InputState, key: egui::Key) -> i32 {
+    input.count_and_consume_key(egui::Modifiers::NONE, key) as i32
+        + 10 * input.count_and_consume_key(egui::Modifiers::SHIFT, key) as i32
+}
+
+/// keys without parameters, with the names shown in the gui
+fn simple_keys() -> Vec<(&'static str, SortBy)> {
+    vec![
+        ("Luminance", SortBy::Luminance),
+        ("Hue", SortBy::Hue),
+        ("Saturation", SortBy::Saturation),
+        ("Red", SortBy::Red),
+        ("Green", SortBy::Green),
+        ("Blue", SortBy::Blue),
+        ("Alpha", SortBy::Alpha),
+        ("Value", SortBy::Value),
+        ("Lightness", SortBy::Lightness),
+        ("Luma", SortBy::Luma),
+    ]
+}
+
+fn weighted_key(weights: &[(&str, f32, SortBy)]) -> KeyExpression {
+    KeyExpression {
+        terms: weights
+            .iter()
+            .filter(|(_, weight, _)| *weight > 0.0)
+            .map(|(_, weight, key)| (*weight, key.clone()))
+            .collect(),
+    }
+}
+
+/// shows a sorted image in a window, scaled to fit
+#[cfg(not(target_arch = "wasm32"))]
+pub fn preview_main(image: egui::ColorImage, name: &str) -> Result<(), eframe::Error> {
+    let options = eframe::NativeOptions {
+        initial_window_size: Some(egui::vec2(1024.0, 1024.0)),
+        default_theme: eframe::Theme::Light,
+        follow_system_theme: true,
+        ..Default::default()
+    };
+
+    let name = name.to_string();
+    let mut texture: Option<egui::TextureHandle> = None;
+    eframe::run_simple_native(
+        &format!("PSORTER - {}", name),
+        options,
+        move |ctx, _frame| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                let texture = texture.get_or_insert_with(|| {
+                    ctx.load_texture(&name, image.clone(), Default::default())
+                });
+
+                let available_space = ui.available_size();
+                let scale = (available_space.x / image.width() as f32)
+                    .min(available_space.y / image.height() as f32);
+                ui.centered_and_justified(|ui| {
+                    let size = texture.size_vec2() * scale;
+                    ui.image(&*texture, size);
+                });
+            });
+        },
+    )
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+pub fn gui_main(config: config::Config) -> Result<(), eframe::Error> {
+    let options = eframe::NativeOptions {
+        initial_window_size: Some(egui::vec2(1024.0, 1024.0)),
+        default_theme: match config.theme {
+            config::Theme::Dark => eframe::Theme::Dark,
+            _ => eframe::Theme::Light,
+        },
+        follow_system_theme: config.theme == config::Theme::System,
+        ..Default::default()
+    };
+    eframe::run_simple_native("PSORTER", options, gui_update(config))
+}
+
+/// the state of the gui and what draws it every frame, shared by the window and the web page
+pub fn gui_update(mut config: config::Config) -> impl FnMut(&egui::Context, &mut eframe::Frame) {
+    let mut sort_by: SortBy = config
+        .key
+        .as_deref()
+        .and_then(parse_sort_by)
+        .unwrap_or(SortBy::Luminance);
+    let upper_boundary = threshold_upper_boundary(&sort_by);
+    let (mut lower_threshold, mut higher_threshold) = config
+        .thresholds
+        .as_ref()
+        .and_then(|[lower, higher]| {
+            Some((
+                parse_threshold(&lower.to_string(), upper_boundary)?,
+                parse_threshold(&higher.to_string(), upper_boundary)?,
+            ))
+        })
+        .unwrap_or((0, 255));
+    let mut normalized_thresholds = false;
+    let mut lower_percent: f32 = 0.0;
+    let mut higher_percent: f32 = 100.0;
+    let mut reference_color = egui::Color32::BLACK;
+    let mut weights: Vec<(&str, f32, SortBy)> = simple_keys()
+        .into_iter()
+        .map(|(name, key)| (name, if name == "Luminance" { 1.0 } else { 0.0 }, key))
+        .collect();
+    let mut script_source = "r*0.5 + b - g".to_string();
+    let mut key_script =
+        script::KeyScript::compile(&script_source).expect("the default key script is valid");
+    let mut script_error: Option<String> = None;
+    let mut tie_breaker: Option<usize> = None;
+    let mut threshold_by: Option<usize> = None;
+    let mut invert_selection = false;
+    let mut selection_source = SelectionSource::Thresholds;
+    let mut noise_mask = noise::NoiseMask::default();
+    let mut edge_mask = convolution::EdgeMask::default();
+    let mut control_image: Option<Arc<egui::ColorImage>> = None;
+    let mut control_name = String::new();
+    let mut mask_operations: Vec<mask::MaskOperation> = Vec::new();
+    let mut soft_mask = false;
+    let mut region: Option<mask::Region> = None;
+    let mut region_tool: Option<RegionTool> = None;
+    // where the drag of a region tool started, in pixels
+    let mut region_start: Option<egui::Pos2> = None;
+    let mut brush = Brush::default();
+    // the outline of the lasso being drawn, in pixels
+    let mut lasso: Vec<egui::Pos2> = Vec::new();
+    // the painted mask over the preview, while the brush is picked
+    let mut mask_texture: Option<egui::TextureHandle> = None;
+    let mut strength: f32 = 100.0;
+    let mut per_channel = false;
+    // earlier passes, the current settings are applied on top of them
+    let mut passes: Vec<Pass> = Vec::new();
+    let mut add_pass = false;
+    let mut interval_options = IntervalOptions::default();
+    let mut traversal = Traversal::Rows;
+    let mut use_blocks = false;
+    let mut block_size: usize = 64;
+    let mut order = SortOrder::default();
+    let mut texture: Option<egui::TextureHandle> = None;
+    let mut changed = true;
+    let mut error_message: Option<String> = None;
+    let mut settings: Option<config::Form> = None;
+    // kept from one export to the next
+    let mut encode_options = EncodeOptions::default();
+    let mut exporting = false;
+    let mut sweep = Sweep {
+        parameter: SweepParameter::LowerThreshold,
+        from: 0.0,
+        to: 255.0,
+        frames: 30,
+        delay: Duration::from_millis(100),
+    };
+    let mut sweeping = false;
+    // the frame shown while the timeline is open, sorted with the values it has on that frame
+    let mut timeline = Timeline::new(30, Duration::from_millis(100));
+    let mut timeline_open = false;
+    let mut timeline_frame = 0;
+    // the image before sorting, shown instead of the preview to compare them
+    let mut show_original = false;
+    let mut shortcuts_open = false;
+    // random settings and their thumbnails, while the gallery is open
+    let mut gallery: Option<Vec<(Pass, egui::TextureHandle)>> = None;
+    let mut roll_gallery = false;
+    let mut original_texture: Option<egui::TextureHandle> = None;
+    // the pixels the current settings select, tinted on the image they sort
+    let mut show_selection = false;
+    let mut selection_texture: Option<egui::TextureHandle> = None;
+    let mut sorter = Sorter::start();
+    // when the preview shown was sorted scaled down, the whole image is sorted once the drag
+    // is over or held still
+    let mut proxy_sorted: Option<Instant> = None;
+    // of the image the current settings sort, by the key of their thresholds
+    let mut histogram: Vec<u64> = Vec::new();
+    // while it's on, clicking the preview picks thresholds instead of the center
+    let mut eyedropper = false;
+    let mut eyedropper_band: u16 = 20;
+    // how much of the width shows the original beside the preview, none for no divider
+    let mut split: Option<f32> = None;
+    let mut dragging_divider = false;
+    // while it's on, every new frame replaces the image
+    let mut webcam: Option<Webcam> = None;
+    let mut recorder: Option<Recorder> = None;
+    // kept open, on linux what's copied is gone once the clipboard is closed
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut clipboard = arboard::Clipboard::new()
+        .map_err(|e| log::warn!("cannot open the clipboard: {}", e))
+        .ok();
+    let mut presets = preset::load_all().unwrap_or_else(|e| {
+        log::warn!("ignoring the presets: {}", e);
+        Default::default()
+    });
+    let mut preset_name = String::new();
+    let mut presets_open = false;
+    // a thumbnail of every preset on the image, made once the presets panel is open
+    let mut preset_thumbnails: Option<BTreeMap<String, egui::TextureHandle>> = None;
+    // the preset being renamed in the presets panel, and its new name
+    let mut renaming: Option<(String, String)> = None;
+    // picked in the presets panel, loaded on the next frame like one picked from the list
+    let mut apply_preset: Option<String> = None;
+    // built from the current settings, kept around to be saved as a preset
+    let mut current_pass: Option<Pass> = None;
+    let picked: PickedFiles = Default::default();
+    // dropped along with the open image, each one opened with "Next image"
+    let mut queued_images: VecDeque<egui::DroppedFile> = VecDeque::new();
+    // set once the settings differ from `recorded`, a step is made when the mouse is let go
+    let mut settings_changed = false;
+    // re-sorting only to show something else, which isn't a step
+    let mut view_changed = false;
+    // the images open, the one shown is sorted with the settings in the ui
+    let mut tabs = vec![Tab::new(
+        egui::ColorImage::new([512, 512], egui::Color32::TRANSPARENT),
+        "placeholder".to_string(),
+        None,
+    )];
+    let mut active = 0;
+    // offered to be picked up again until it's restored or discarded, autosaving waits for it
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut saved_session = session::load().unwrap_or_else(|e| {
+        log::warn!("ignoring the last session: {}", e);
+        None
+    });
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut autosave: Option<std::thread::JoinHandle<Result<(), String>>> = None;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut autosaved = Instant::now();
+    // the settings changed since the last autosave, or the zoom it saved
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut session_due = false;
+    #[cfg(not(target_arch = "wasm32"))]
+    let mut autosaved_view = (tabs[active].zoom, tabs[active].pan);
+
+    move |ctx, _frame| {
+        let mut dropped = ctx.input(|input| input.raw.dropped_files.clone());
+        if !dropped.is_empty() {
+            queued_images = dropped.drain(1..).collect();
+            picked
+                .lock()
+                .unwrap()
+                .push(dropped_image(dropped.remove(0)));
+        }
+        if ctx.input(|input| !input.raw.hovered_files.is_empty()) {
+            let painter = ctx.layer_painter(egui::LayerId::new(
+                egui::Order::Foreground,
+                egui::Id::new("dropping"),
+            ));
+            let screen = ctx.screen_rect();
+            painter.rect_filled(screen, 0.0, egui::Color32::from_black_alpha(160));
+            painter.text(
+                screen.center(),
+                egui::Align2::CENTER_CENTER,
+                "Drop images to open them",
+                egui::FontId::proportional(24.0),
+                egui::Color32::WHITE,
+            );
+        }
+
+        // the image before another one is opened and where it came from, kept to be undone
+        let mut replaced_image = None;
+        // the tab to show instead, picked or just opened
+        let mut open_tab: Option<usize> = None;
+        let picked_files = std::mem::take(&mut *picked.lock().unwrap());
+        for file in picked_files {
+            let loaded = file.bytes.and_then(|bytes| match file.purpose {
+                PickFor::Image => {
+                    let decoded = decode_image(&file.name, &bytes).map_err(|e| e.to_string())?;
+                    let tab = &mut tabs[active];
+                    replaced_image = Some((
+                        std::mem::replace(&mut tab.image, decoded),
+                        tab.path.replace(PathBuf::from(&file.name)),
+                    ));
+                    tab.name = basename(&file.name).unwrap_or_else(|| file.name.clone());
+                    if let Some(center) = traversal.center_mut() {
+                        *center = None;
+                    }
+                    Ok(())
+                }
+                PickFor::Tab => {
+                    let decoded = decode_image(&file.name, &bytes).map_err(|e| e.to_string())?;
+                    tabs.push(Tab::new(
+                        decoded,
+                        basename(&file.name).unwrap_or_else(|| file.name.clone()),
+                        Some(PathBuf::from(&file.name)),
+                    ));
+                    open_tab = Some(tabs.len() - 1);
+                    Ok(())
+                }
+                PickFor::Control => {
+                    let control = decode_image(&file.name, &bytes).map_err(|e| e.to_string())?;
+                    control_image = Some(Arc::new(control));
+                    control_name = basename(&file.name).unwrap_or_else(|| file.name.clone());
+                    Ok(())
+                }
+                PickFor::Svg => {
+                    let source = String::from_utf8(bytes).map_err(|e| e.to_string())?;
+                    traversal = Traversal::Svg(svg::SvgPaths::parse(&source)?);
+                    Ok(())
+                }
+            });
+            match loaded {
+                Ok(()) => changed = true,
+                Err(e) => error_message = Some(e),
+            }
+        }
+
+        // text fields paste text, everything else takes images. the browser keeps its
+        // clipboard to itself
+        #[cfg(not(target_arch = "wasm32"))]
+        if !ctx.wants_keyboard_input()
+            && ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::V))
+        {
+            match clipboard
+                .as_mut()
+                .ok_or("there's no clipboard".to_string())
+                .and_then(paste_image)
+            {
+                Ok(Some(pasted)) => {
+                    let tab = &mut tabs[active];
+                    replaced_image =
+                        Some((std::mem::replace(&mut tab.image, pasted), tab.path.take()));
+                    tab.name = "pasted.png".to_string();
+                    if let Some(center) = traversal.center_mut() {
+                        *center = None;
+                    }
+                    changed = true;
+                }
+                Ok(None) => {}
+                Err(e) => error_message = Some(format!("cannot paste: {}", e)),
+            }
+        }
+
+        let mut image_replaced = replaced_image.is_some();
+        if let (Some((replaced, replaced_path)), Some(pass)) = (replaced_image, &current_pass) {
+            let mut current = Snapshot::new(&passes, pass, &script_source);
+            tabs[active].history.record(Snapshot {
+                image: Some(replaced),
+                image_path: replaced_path,
+                ..current.clone()
+            });
+            // the center was on the image replaced
+            if let Some(center) = current.pass.traversal.center_mut() {
+                *center = None;
+            }
+            tabs[active].recorded = Some(current);
+            settings_changed = false;
+        }
+
+        // text fields undo their own typing
+        let typing = ctx.wants_keyboard_input();
+        let mut undo = !typing
+            && ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::Z));
+        let mut redo = !typing
+            && ctx.input_mut(|input| {
+                input.consume_key(
+                    egui::Modifiers::COMMAND | egui::Modifiers::SHIFT,
+                    egui::Key::Z,
+                ) || input.consume_key(egui::Modifiers::COMMAND, egui::Key::Y)
+            });
+        // a focused widget takes space and the arrows for itself
+        let focused = ctx.memory(|memory| memory.focus().is_some());
+        if !typing {
+            if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::O)) {
+                pick_file(PickFor::Image, &picked, ctx);
+            }
+            if ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::S)) {
+                exporting = true;
+            }
+            if ctx.input_mut(|input| input.consume_key(egui::Modifiers::NONE, egui::Key::F1)) {
+                shortcuts_open = !shortcuts_open;
+            }
+            let numbered = ctx.input_mut(|input| {
+                [egui::Key::Num1, egui::Key::Num2, egui::Key::Num3]
+                    .into_iter()
+                    .position(|key| input.consume_key(egui::Modifiers::NONE, key))
+            });
+            if let Some(i) = numbered {
+                sort_by = simple_keys()[i].1.clone();
+                changed = true;
+            }
+        }
+        if !typing && !focused {
+            let (lower_steps, higher_steps) = ctx.input_mut(|input| {
+                (
+                    arrow_steps(input, egui::Key::ArrowRight)
+                        - arrow_steps(input, egui::Key::ArrowLeft),
+                    arrow_steps(input, egui::Key::ArrowUp)
+                        - arrow_steps(input, egui::Key::ArrowDown),
+                )
+            });
+            if (lower_steps, higher_steps) != (0, 0) {
+                let upper_boundary = match threshold_by {
+                    Some(i) => threshold_upper_boundary(&simple_keys()[i].1),
+                    None => threshold_upper_boundary(&sort_by),
+                };
+                let higher =
+                    (higher_threshold as i32 + higher_steps).clamp(0, upper_boundary as i32);
+                let lower = (lower_threshold as i32 + lower_steps).clamp(0, higher);
+                (lower_threshold, higher_threshold) = (lower as u16, higher as u16);
+                lower_percent = lower_threshold as f32 * 100.0 / upper_boundary as f32;
+                higher_percent = higher_threshold as f32 * 100.0 / upper_boundary as f32;
+                changed = true;
+            }
+        }
+        // settings to show in the ui, from a preset, the history or another tab
+        let mut restore: Option<Pass> = None;
+
+        let mut closed_tab = None;
+        egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                for (i, tab) in tabs.iter().enumerate() {
+                    let response = ui
+                        .selectable_label(i == active, &tab.name)
+                        .on_hover_text("right click to save it");
+                    if response.clicked() {
+                        open_tab = Some(i);
+                    }
+                    response.context_menu(|ui| {
+                        if ui.button("Save…").clicked() {
+                            save_image(
+                                &tab.sorted,
+                                &tab.name,
+                                config.out_dir.as_deref(),
+                                &encode_options,
+                            );
+                            ui.close_menu();
+                        }
+                    });
+                    if tabs.len() > 1 && ui.small_button("✖").clicked() {
+                        closed_tab = Some(i);
+                    }
+                    ui.separator();
+                }
+                if ui
+                    .button("+")
+                    .on_hover_text("open an image in a new tab")
+                    .clicked()
+                {
+                    pick_file(PickFor::Tab, &picked, ctx);
+                }
+            });
+        });
+        // the tab next to the one closed is shown instead
+        if closed_tab == Some(active) {
+            open_tab = Some(if active == 0 { 1 } else { active - 1 });
+        }
+
+        // another tab isn't a step, it has a history of its own
+        let mut switched = false;
+        if let Some(target) = open_tab.filter(|&target| target != active) {
+            let current = current_pass
+                .as_ref()
+                .map(|pass| Snapshot::new(&passes, pass, &script_source));
+            tabs[active].settings = current.clone();
+            active = target;
+            let tab = &mut tabs[active];
+            match tab.settings.take() {
+                Some(settings) => {
+                    passes = settings.passes;
+                    script_source = settings.script_source;
+                    restore = Some(settings.pass);
+                }
+                None => {
+                    // the center was on the other image
+                    if let Some(center) = traversal.center_mut() {
+                        *center = None;
+                    }
+                    tab.recorded = current;
+                }
+            }
+            // the webcam would take over the tab
+            webcam = None;
+            texture = Some(ctx.load_texture(&tab.name, tab.sorted.clone(), PREVIEW_TEXTURE));
+            settings_changed = false;
+            switched = true;
+            changed = true;
+        }
+        if let Some(closed) = closed_tab {
+            tabs.remove(closed);
+            if active > closed {
+                active -= 1;
+            }
+        }
+        let tab = &mut tabs[active];
+
+        if let Some(pass) = current_pass.as_ref().filter(|_| roll_gallery) {
+            let mut rng = rand::thread_rng();
+            let candidates: Vec<Pass> = (0..gallery::CANDIDATES)
+                .map(|_| gallery::random_pass(pass, &mut rng))
+                .collect();
+            let thumbnails = gallery::thumbnails(&tab.image, &passes, &candidates);
+            gallery = Some(
+                candidates
+                    .into_iter()
+                    .zip(thumbnails)
+                    .map(|(candidate, thumbnail)| {
+                        let texture = ctx.load_texture("random", thumbnail, PREVIEW_TEXTURE);
+                        (candidate, texture)
+                    })
+                    .collect(),
+            );
+            roll_gallery = false;
+        }
+        if let Some(candidates) = &gallery {
+            let mut open = true;
+            let mut chosen = None;
+            egui::Window::new("Randomize")
+                .open(&mut open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("random settings").show(ui, |ui| {
+                        for (i, (candidate, thumbnail)) in candidates.iter().enumerate() {
+                            if ui
+                                .add(egui::ImageButton::new(
+                                    thumbnail.id(),
+                                    thumbnail.size_vec2(),
+                                ))
+                                .on_hover_text(format!(
+                                    "{}, thresholds {} - {}",
+                                    candidate.sort_by.name(),
+                                    candidate.thresholds.lower,
+                                    candidate.thresholds.higher
+                                ))
+                                .clicked()
+                            {
+                                chosen = Some(candidate.clone());
+                            }
+                            if i % 3 == 2 {
+                                ui.end_row();
+                            }
+                        }
+                    });
+                    if ui
+                        .button("Again")
+                        .on_hover_text("roll other settings")
+                        .clicked()
+                    {
+                        roll_gallery = true;
+                        ctx.request_repaint();
+                    }
+                });
+            if chosen.is_some() {
+                restore = chosen;
+                gallery = None;
+            } else if !open {
+                gallery = None;
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(saved) = &saved_session {
+            let mut answer = None;
+            egui::Window::new("Last session")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(format!(
+                        "Pick up where you left off with {}?",
+                        saved.image_name()
+                    ));
+                    ui.horizontal(|ui| {
+                        if ui.button("Restore").clicked() {
+                            answer = Some(true);
+                        }
+                        if ui.button("Discard").clicked() {
+                            answer = Some(false);
+                        }
+                    });
+                });
+            if answer == Some(true) {
+                match saved.restore(&config) {
+                    Ok(restored) => {
+                        tab.image = restored.image;
+                        let path = restored.image_path.display().to_string();
+                        tab.name = basename(&path).unwrap_or(path);
+                        tab.path = Some(restored.image_path);
+                        passes = restored.passes;
+                        if let Some(source) = restored.script_source {
+                            script_source = source;
+                        }
+                        restore = Some(restored.pass);
+                        (tab.zoom, tab.pan) = (restored.zoom, restored.pan);
+                        texture = None;
+                    }
+                    Err(e) => error_message = Some(format!("cannot restore the session: {}", e)),
+                }
+            }
+            if answer.is_some() {
+                saved_session = None;
+            }
+        }
+        // the preview isn't the whole image sorted with the current settings yet
+        let sorting = sorter.busy() || proxy_sorted.is_some();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut copy_result = !ctx.wants_keyboard_input()
+            && ctx.input_mut(|input| input.consume_key(egui::Modifiers::COMMAND, egui::Key::C));
+        #[cfg(not(target_arch = "wasm32"))]
+        let mut copy_command = false;
+
+        let mut webcam_frame = false;
+        if let Some(camera) = &webcam {
+            // frames coming while the last one is sorted are skipped
+            if sorter.busy() {
+            } else if let Some(frame) = camera.frame() {
+                if frame.size != tab.image.size {
+                    if let Some(center) = traversal.center_mut() {
+                        *center = None;
+                    }
+                }
+                tab.image = frame;
+                tab.name = "webcam.png".to_string();
+                tab.path = None;
+                webcam_frame = true;
+                changed = true;
+            } else if let Some(e) = camera.error() {
+                error_message = Some(e);
+                webcam = None;
+            }
+            ctx.request_repaint();
+        }
+
+        egui::TopBottomPanel::top("my_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.with_layout(
+                    egui::Layout::default().with_cross_align(egui::Align::LEFT),
+                    |ui| {
+                        ui.horizontal(|ui| {
+                            let upper_boundary = match threshold_by {
+                                Some(i) => threshold_upper_boundary(&simple_keys()[i].1),
+                                None => threshold_upper_boundary(&sort_by),
+                            };
+
+                            ui.label("Thresholds: ");
+                            if normalized_thresholds {
+                                ui.add(
+                                    RangeSlider::new(
+                                        &mut lower_percent,
+                                        &mut higher_percent,
+                                        0.0..=100.0,
+                                    )
+                                    .suffix("%")
+                                    .histogram(&histogram),
+                                );
+
+                                // also picks up changes of the key's native range
+                                let lower = threshold_from_percent(lower_percent, upper_boundary);
+                                let higher = threshold_from_percent(higher_percent, upper_boundary);
+                                if (lower, higher) != (lower_threshold, higher_threshold) {
+                                    lower_threshold = lower;
+                                    higher_threshold = higher;
+                                    changed = true;
+                                }
+                            } else {
+                                changed = ui
+                                    .add(
+                                        RangeSlider::new(
+                                            &mut lower_threshold,
+                                            &mut higher_threshold,
+                                            0..=upper_boundary,
+                                        )
+                                        .histogram(&histogram),
+                                    )
+                                    .changed()
+                                    || changed;
+                            }
+
+                            ui.menu_button("Auto", |ui| {
+                                let automatic = if ui.button("Otsu").clicked() {
+                                    Some(AutoThreshold::Otsu)
+                                } else if ui.button("Percentile (10% - 90%)").clicked() {
+                                    Some(AutoThreshold::Percentile(10.0))
+                                } else {
+                                    None
+                                };
+
+                                if let Some(automatic) = automatic {
+                                    let key = match threshold_by {
+                                        Some(i) => simple_keys()[i].1.clone(),
+                                        None => sort_by.clone(),
+                                    };
+                                    let threshold_image = match (&control_image, selection_source) {
+                                        (Some(control), SelectionSource::Control) => control,
+                                        _ => &tab.image,
+                                    };
+                                    (lower_threshold, higher_threshold) =
+                                        automatic.thresholds(&key_histogram(threshold_image, &key));
+                                    lower_percent =
+                                        lower_threshold as f32 * 100.0 / upper_boundary as f32;
+                                    higher_percent =
+                                        higher_threshold as f32 * 100.0 / upper_boundary as f32;
+                                    changed = true;
+                                    ui.close_menu();
+                                }
+                            });
+
+                            ui.toggle_value(&mut eyedropper, "Eyedropper").on_hover_text(
+                                "click the preview to select pixels with keys close to the one \
+                                 clicked",
+                            );
+                            if eyedropper {
+                                ui.add(
+                                    egui::DragValue::new(&mut eyedropper_band)
+                                        .clamp_range(0..=upper_boundary),
+                                )
+                                .on_hover_text("how far apart the thresholds are put");
+                            }
+
+                            if ui
+                                .checkbox(&mut normalized_thresholds, "%")
+                                .on_hover_text("thresholds as a percentage of the key's range")
+                                .changed()
+                            {
+                                lower_percent =
+                                    lower_threshold as f32 * 100.0 / upper_boundary as f32;
+                                higher_percent =
+                                    higher_threshold as f32 * 100.0 / upper_boundary as f32;
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Direction: ");
+                            let angle = match traversal {
+                                Traversal::Rows => Some(0.0),
+                                Traversal::Lines { angle } => Some(angle),
+                                _ => None,
+                            };
+                            let mut picked = None;
+                            if ui
+                                .selectable_label(angle == Some(0.0), "Horizontal")
+                                .clicked()
+                            {
+                                picked = Some(0.0);
+                            }
+                            if ui.selectable_label(angle == Some(90.0), "Vertical").clicked() {
+                                picked = Some(90.0);
+                            }
+                            let mut dialed = angle.unwrap_or(0.0);
+                            if ui
+                                .add(AngleDial::new(&mut dialed))
+                                .on_hover_text(
+                                    "sort along straight lines at this angle, drag with shift \
+                                     to turn by 15°",
+                                )
+                                .changed()
+                            {
+                                picked = Some(dialed);
+                            }
+                            if let Some(angle) = picked {
+                                traversal = if angle == 0.0 {
+                                    Traversal::Rows
+                                } else {
+                                    Traversal::Lines { angle }
+                                };
+                                changed = true;
+                            }
+                        });
+
+                        ui.horizontal(|ui| {
+                            ui.label("Region: ");
+                            let rectangle = region_tool == Some(RegionTool::Rectangle);
+                            if ui
+                                .selectable_label(rectangle, "Rectangle")
+                                .on_hover_text("drag on the preview to only sort inside a rectangle")
+                                .clicked()
+                            {
+                                region_tool = (!rectangle).then_some(RegionTool::Rectangle);
+                            }
+                            let painting = region_tool == Some(RegionTool::Brush);
+                            if ui
+                                .selectable_label(painting, "Brush")
+                                .on_hover_text("paint on the preview where to sort")
+                                .clicked()
+                            {
+                                region_tool = (!painting).then_some(RegionTool::Brush);
+                            }
+                            let lassoing = region_tool == Some(RegionTool::Lasso);
+                            if ui
+                                .selectable_label(lassoing, "Lasso")
+                                .on_hover_text(
+                                    "draw around the shape to sort in on the preview, \
+                                    with shift to add it to the region",
+                                )
+                                .clicked()
+                            {
+                                region_tool = (!lassoing).then_some(RegionTool::Lasso);
+                            }
+                            if ui
+                                .add_enabled(region.is_some(), egui::Button::new("Clear"))
+                                .on_hover_text("sort the whole image again")
+                                .clicked()
+                            {
+                                region = None;
+                                changed = true;
+                            }
+                            if let Some(mask::Region::Mask(painted)) = &region {
+                                if ui
+                                    .button("Save mask…")
+                                    .on_hover_text("for --region-mask on the command line")
+                                    .clicked()
+                                {
+                                    save_image(
+                                        &painted.to_image(),
+                                        &format!("{}-mask.png", file_stem(&tab.name)),
+                                        config.out_dir.as_deref(),
+                                        &encode_options,
+                                    );
+                                }
+                            }
+                        });
+                        if region_tool == Some(RegionTool::Brush) {
+                            ui.horizontal(|ui| {
+                                ui.label("Size: ");
+                                ui.add(
+                                    egui::DragValue::new(&mut brush.radius)
+                                        .clamp_range(1.0..=1000.0)
+                                        .speed(0.5)
+                                        .max_decimals(0)
+                                        .suffix(" px"),
+                                );
+                                ui.label("Softness: ");
+                                ui.add(egui::Slider::new(&mut brush.softness, 0.0..=1.0));
+                                ui.checkbox(&mut brush.erase, "Erase")
+                                    .on_hover_text("take the painted areas out of the region");
+                            });
+                        }
+                    },
+                );
+
+                ui.with_layout(
+                    egui::Layout::default().with_cross_align(egui::Align::RIGHT),
+                    |ui| {
+                        ui.horizontal(|ui| {
+                            if ui.button("Open file…").on_hover_text("Ctrl+O").clicked() {
+                                pick_file(PickFor::Image, &picked, ctx);
+                            }
+
+                            if let Some(next) = queued_images.front() {
+                                let name = match &next.path {
+                                    Some(path) => {
+                                        let path = path.display().to_string();
+                                        basename(&path).unwrap_or(path)
+                                    }
+                                    None => next.name.clone(),
+                                };
+                                if ui
+                                    .button(format!("Next image ({} left)", queued_images.len()))
+                                    .on_hover_text(name)
+                                    .clicked()
+                                {
+                                    let next = queued_images.pop_front().unwrap();
+                                    picked.lock().unwrap().push(dropped_image(next));
+                                }
+                            }
+
+                            if ui.button("Save file…").on_hover_text("Ctrl+S").clicked() {
+                                exporting = true;
+                            }
+
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if ui
+                                .button("Copy result")
+                                .on_hover_text("put the sorted image on the clipboard (Ctrl+C)")
+                                .clicked()
+                            {
+                                copy_result = true;
+                            }
+
+                            #[cfg(not(target_arch = "wasm32"))]
+                            if ui
+                                .button("Copy command")
+                                .on_hover_text(
+                                    "put the psorter command line sorting the image the same way \
+                                     on the clipboard",
+                                )
+                                .clicked()
+                            {
+                                copy_command = true;
+                            }
+
+                            if ui
+                                .button("Randomize…")
+                                .on_hover_text("pick from random settings, sorted side by side")
+                                .clicked()
+                            {
+                                roll_gallery = true;
+                                ctx.request_repaint();
+                            }
+
+                            if ui
+                                .button("Sweep…")
+                                .on_hover_text(
+                                    "save an animation of the current settings with one of them \
+                                     moving from frame to frame",
+                                )
+                                .clicked()
+                            {
+                                sweeping = true;
+                            }
+
+                            if ui
+                                .selectable_label(timeline_open, "Timeline")
+                                .on_hover_text(
+                                    "place keyframes for the current settings and save the \
+                                     animation moving between them",
+                                )
+                                .clicked()
+                            {
+                                timeline_open = !timeline_open;
+                                changed = true;
+                                view_changed = true;
+                            }
+
+                            // the browser can't run ffmpeg
+                            #[cfg(not(target_arch = "wasm32"))]
+                            {
+                                if ui
+                                    .selectable_label(webcam.is_some(), "Webcam")
+                                    .on_hover_text("sort what the webcam sees as it comes, needs ffmpeg")
+                                    .clicked()
+                                {
+                                    if webcam.is_some() {
+                                        webcam = None;
+                                    } else {
+                                        match Webcam::open(config.webcam.as_deref(), WEBCAM_WIDTH) {
+                                            Ok(opened) => webcam = Some(opened),
+                                            Err(e) => error_message = Some(e),
+                                        }
+                                    }
+                                }
+                                if (webcam.is_some() || recorder.is_some())
+                                    && ui
+                                        .selectable_label(recorder.is_some(), "Record")
+                                        .on_hover_text("save the sorted frames into a video")
+                                        .clicked()
+                                {
+                                    if let Some(recording) = recorder.take() {
+                                        if let Err(e) = recording.finish() {
+                                            error_message = Some(e);
+                                        }
+                                    } else if let Some(path) =
+                                        pick_recording(config.out_dir.as_deref())
+                                    {
+                                        recorder = Some(Recorder::new(path));
+                                    }
+                                }
+                            }
+
+                            // the browser has no config file to edit
+                            if cfg!(not(target_arch = "wasm32"))
+                                && ui.button("Settings…").clicked()
+                            {
+                                settings = Some(config::Form::new(&config));
+                            }
+                            if ui
+                                .selectable_label(shortcuts_open, "Shortcuts")
+                                .on_hover_text("F1")
+                                .clicked()
+                            {
+                                shortcuts_open = !shortcuts_open;
+                            }
+
+                            ui.separator();
+
+                            let luminance_button = ui
+                                .add(egui::Button::new("Luminance"))
+                                .on_hover_text("average of the channels");
+                            let hue_button = ui.add(egui::Button::new("Hue"));
+                            let saturation_button = ui.add(egui::Button::new("Saturation"));
+                            let red_button = ui.add(egui::Button::new("Red"));
+                            let green_button = ui.add(egui::Button::new("Green"));
+                            let blue_button = ui.add(egui::Button::new("Blue"));
+                            let alpha_button = ui.add(egui::Button::new("Alpha"));
+                            let value_button = ui
+                                .add(egui::Button::new("Value"))
+                                .on_hover_text("brightest channel");
+                            let lightness_button =
+                                ui.add(egui::Button::new("Lightness")).on_hover_text(
+                                    "middle between the brightest and the darkest channel",
+                                );
+                            let luma_button = ui
+                                .add(egui::Button::new("Luma"))
+                                .on_hover_text("perceptually weighted brightness (Rec.709)");
+                            let script_button = ui
+                                .add(egui::Button::new("Script"))
+                                .on_hover_text("custom key written as an expression");
+                            let weighted_button = ui
+                                .add(egui::Button::new("Weighted"))
+                                .on_hover_text("weighted sum of several keys");
+                            let distance_button = ui
+                                .add(egui::Button::new("Distance"))
+                                .on_hover_text("distance to the reference color");
+                            if egui::color_picker::color_edit_button_srgba(
+                                ui,
+                                &mut reference_color,
+                                egui::color_picker::Alpha::Opaque,
+                            )
+                            .changed()
+                            {
+                                if let SortBy::Distance(reference) = &mut sort_by {
+                                    *reference = reference_color;
+                                    changed = true;
+                                }
+                            }
+
+                            if luminance_button.clicked() {
+                                sort_by = SortBy::Luminance;
+                                changed = true;
+                            } else if hue_button.clicked() {
+                                sort_by = SortBy::Hue;
+                                changed = true;
+                            } else if saturation_button.clicked() {
+                                sort_by = SortBy::Saturation;
+                                changed = true;
+                            } else if red_button.clicked() {
+                                sort_by = SortBy::Red;
+                                changed = true;
+                            } else if green_button.clicked() {
+                                sort_by = SortBy::Green;
+                                changed = true;
+                            } else if blue_button.clicked() {
+                                sort_by = SortBy::Blue;
+                                changed = true;
+                            } else if alpha_button.clicked() {
+                                sort_by = SortBy::Alpha;
+                                changed = true;
+                            } else if value_button.clicked() {
+                                sort_by = SortBy::Value;
+                                changed = true;
+                            } else if lightness_button.clicked() {
+                                sort_by = SortBy::Lightness;
+                                changed = true;
+                            } else if luma_button.clicked() {
+                                sort_by = SortBy::Luma;
+                                changed = true;
+                            } else if distance_button.clicked() {
+                                sort_by = SortBy::Distance(reference_color);
+                                changed = true;
+                            } else if script_button.clicked() {
+                                sort_by = SortBy::Script(key_script.clone());
+                                changed = true;
+                            } else if weighted_button.clicked() {
+                                sort_by = SortBy::Weighted(weighted_key(&weights));
+                                changed = true;
+                            }
+
+                            match sort_by {
+                                SortBy::Luminance => luminance_button,
+                                SortBy::Hue => hue_button,
+                                SortBy::Saturation => saturation_button,
+                                SortBy::Red => red_button,
+                                SortBy::Green => green_button,
+                                SortBy::Blue => blue_button,
+                                SortBy::Alpha => alpha_button,
+                                SortBy::Value => value_button,
+                                SortBy::Lightness => lightness_button,
+                                SortBy::Luma => luma_button,
+                                SortBy::Distance(_) => distance_button,
+                                SortBy::Weighted(_) => weighted_button,
+                                SortBy::Script(_) => script_button,
+                                SortBy::Custom(_) => unreachable!("keys of other crates never reach the gui"),
+                            }
+                            .highlight();
+                        });
+                    },
+                );
+            });
+
+            ui.horizontal(|ui| {
+                ui.label("Traversal: ");
+                let rows_button = ui.add(egui::Button::new("Rows"));
+                let radial_button = ui.add(egui::Button::new("Radial"));
+                let spiral_button = ui.add(egui::Button::new("Spiral"));
+                let hilbert_button = ui.add(egui::Button::new("Hilbert"));
+                let whole_button = ui.add(egui::Button::new("Whole image"));
+                let svg_button = ui.add(egui::Button::new("SVG path…"));
+
+                let center = traversal.center_mut().and_then(|center| *center);
+                if rows_button.clicked() {
+                    traversal = Traversal::Rows;
+                    changed = true;
+                } else if radial_button.clicked() {
+                    traversal = Traversal::Radial { center };
+                    changed = true;
+                } else if spiral_button.clicked() {
+                    traversal = Traversal::Spiral { center };
+                    changed = true;
+                } else if hilbert_button.clicked() {
+                    traversal = Traversal::Hilbert;
+                    changed = true;
+                } else if whole_button.clicked() {
+                    traversal = Traversal::Whole(ScanOrder::RowMajor);
+                    changed = true;
+                } else if svg_button.clicked() {
+                    pick_file(PickFor::Svg, &picked, ctx);
+                }
+
+                match traversal {
+                    Traversal::Rows | Traversal::Lines { .. } => rows_button,
+                    Traversal::Radial { .. } => radial_button,
+                    Traversal::Spiral { .. } => spiral_button,
+                    Traversal::Hilbert => hilbert_button,
+                    Traversal::Whole(_) => whole_button,
+                    Traversal::Svg(_) => svg_button,
+                }
+                .highlight();
+
+                if let Traversal::Whole(order) = &mut traversal {
+                    let mut column_major = *order == ScanOrder::ColumnMajor;
+                    if ui.checkbox(&mut column_major, "Column-major").changed() {
+                        *order = if column_major {
+                            ScanOrder::ColumnMajor
+                        } else {
+                            ScanOrder::RowMajor
+                        };
+                        changed = true;
+                    }
+                }
+
+                ui.separator();
+                changed = ui.checkbox(&mut use_blocks, "Blocks").changed() || changed;
+                if use_blocks {
+                    changed = ui
+                        .add(egui::DragValue::new(&mut block_size).clamp_range(2..=1024))
+                        .changed()
+                        || changed;
+                }
+
+                if traversal.center_mut().is_some() && !use_blocks {
+                    ui.label("(click on the image to move the center)");
+                }
+            });
+
+            if let SortBy::Weighted(expression) = &mut sort_by {
+                ui.horizontal_wrapped(|ui| {
+                    ui.label("Weights: ");
+                    let mut weights_changed = false;
+                    for (name, weight, _) in &mut weights {
+                        ui.label(*name);
+                        weights_changed = ui
+                            .add(
+                                egui::DragValue::new(weight)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=1.0),
+                            )
+                            .changed()
+                            || weights_changed;
+                    }
+
+                    if weights_changed {
+                        *expression = weighted_key(&weights);
+                        changed = true;
+                    }
+                });
+            }
+
+            if let SortBy::Script(current) = &mut sort_by {
+                ui.horizontal(|ui| {
+                    ui.label("Key script: ");
+                    if ui.text_edit_singleline(&mut script_source).changed() {
+                        match script::KeyScript::compile(&script_source) {
+                            Ok(script) => {
+                                key_script = script.clone();
+                                *current = script;
+                                script_error = None;
+                                changed = true;
+                            }
+                            Err(e) => script_error = Some(e),
+                        }
+                    }
+
+                    match &script_error {
+                        Some(e) => ui.colored_label(ui.visuals().error_fg_color, e),
+                        None => ui.label("variables: r g b a h s l v"),
+                    };
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Selection: ");
+                let keys = simple_keys();
+                let selected = threshold_by.map_or("Sort key", |i| keys[i].0);
+                egui::ComboBox::from_label("compared against the thresholds")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        changed = ui
+                            .selectable_value(&mut threshold_by, None, "Sort key")
+                            .changed()
+                            || changed;
+                        for (i, (name, _)) in keys.iter().enumerate() {
+                            changed = ui
+                                .selectable_value(&mut threshold_by, Some(i), *name)
+                                .changed()
+                                || changed;
+                        }
+                    });
+
+                ui.separator();
+                egui::ComboBox::from_id_source("selection source")
+                    .selected_text(selection_source.name())
+                    .show_ui(ui, |ui| {
+                        for source in SelectionSource::ALL {
+                            if ui
+                                .selectable_value(&mut selection_source, source, source.name())
+                                .changed()
+                            {
+                                // control images are usually grayscale, so compare their luminance
+                                if source == SelectionSource::Control && threshold_by.is_none() {
+                                    threshold_by = Some(0);
+                                }
+                                changed = true;
+                            }
+                        }
+                    });
+
+                ui.separator();
+                changed = ui
+                    .checkbox(&mut invert_selection, "Invert")
+                    .on_hover_text("sort the pixels outside of the thresholds instead")
+                    .changed()
+                    || changed;
+
+                ui.separator();
+                ui.label("Min length: ");
+                changed = ui
+                    .add(egui::DragValue::new(&mut interval_options.min_length))
+                    .on_hover_text("intervals shorter than this are left unsorted")
+                    .changed()
+                    || changed;
+
+                changed = ui
+                    .checkbox(&mut interval_options.wrap_around, "Wrap around")
+                    .on_hover_text("intervals reaching the end of a row continue from its start")
+                    .changed()
+                    || changed;
+
+                ui.label("Gap: ");
+                changed = ui
+                    .add(egui::DragValue::new(&mut interval_options.gap_tolerance))
+                    .on_hover_text("intervals separated by fewer than this many pixels are merged")
+                    .changed()
+                    || changed;
+
+                ui.separator();
+                let mut limit_length = interval_options.max_length.is_some();
+                changed = ui.checkbox(&mut limit_length, "Max length").changed() || changed;
+                if limit_length {
+                    let max_length = interval_options.max_length.get_or_insert(64);
+                    changed = ui
+                        .add(egui::DragValue::new(max_length).clamp_range(1..=usize::MAX))
+                        .on_hover_text("longer intervals are split into chunks")
+                        .changed()
+                        || changed;
+                    changed = ui
+                        .checkbox(&mut interval_options.random_chunks, "Random chunks")
+                        .changed()
+                        || changed;
+                } else {
+                    interval_options.max_length = None;
+                }
+
+                ui.separator();
+                ui.label("Split chance: ");
+                changed = ui
+                    .add(
+                        egui::DragValue::new(&mut interval_options.split_probability)
+                            .speed(0.001)
+                            .clamp_range(0.0..=1.0),
+                    )
+                    .on_hover_text("chance of cutting an interval at every pixel")
+                    .changed()
+                    || changed;
+            });
+
+            if selection_source == SelectionSource::Edges {
+                ui.horizontal(|ui| {
+                    ui.label("Edges: ");
+                    for (detector, name) in [
+                        (convolution::EdgeDetector::Sobel, "Sobel"),
+                        (convolution::EdgeDetector::Canny, "Canny"),
+                    ] {
+                        changed = ui
+                            .selectable_value(&mut edge_mask.detector, detector, name)
+                            .changed()
+                            || changed;
+                    }
+                    changed = ui
+                        .add(
+                            egui::Slider::new(&mut edge_mask.threshold, 0.0..=255.0)
+                                .text("Strength"),
+                        )
+                        .on_hover_text("gradient needed for a pixel to count as an edge")
+                        .changed()
+                        || changed;
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Mask: ");
+                let mut removed = None;
+                for (i, operation) in mask_operations.iter_mut().enumerate() {
+                    ui.label(operation.name());
+                    changed = match operation {
+                        mask::MaskOperation::Invert => false,
+                        mask::MaskOperation::Blur(passes) => ui
+                            .add(egui::DragValue::new(passes).clamp_range(1..=32))
+                            .on_hover_text("passes")
+                            .changed(),
+                        mask::MaskOperation::Threshold(level) => ui
+                            .add(
+                                egui::DragValue::new(level)
+                                    .speed(0.01)
+                                    .clamp_range(0.0..=1.0),
+                            )
+                            .changed(),
+                        mask::MaskOperation::Dilate(radius)
+                        | mask::MaskOperation::Erode(radius) => ui
+                            .add(egui::DragValue::new(radius).clamp_range(1..=64))
+                            .on_hover_text("radius in pixels")
+                            .changed(),
+                    } || changed;
+                    if ui.small_button("✖").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.separator();
+                }
+                if let Some(i) = removed {
+                    mask_operations.remove(i);
+                    changed = true;
+                }
+
+                ui.menu_button("Add", |ui| {
+                    for operation in [
+                        mask::MaskOperation::Invert,
+                        mask::MaskOperation::Blur(2),
+                        mask::MaskOperation::Threshold(0.5),
+                        mask::MaskOperation::Dilate(1),
+                        mask::MaskOperation::Erode(1),
+                    ] {
+                        if ui.button(operation.name()).clicked() {
+                            mask_operations.push(operation);
+                            changed = true;
+                            ui.close_menu();
+                        }
+                    }
+                });
+
+                ui.separator();
+                changed = ui
+                    .checkbox(&mut soft_mask, "Soft")
+                    .on_hover_text("blend the sorted pixels in by the mask value, blur the mask for feathered edges")
+                    .changed()
+                    || changed;
+            });
+
+            if selection_source == SelectionSource::Control {
+                ui.horizontal(|ui| {
+                    ui.label("Control image: ");
+                    if ui.button("Open control image…").clicked() {
+                        pick_file(PickFor::Control, &picked, ctx);
+                    }
+                    match &control_image {
+                        Some(_) => ui.label(&control_name),
+                        None => ui.label("none, the thresholds are used as usual"),
+                    };
+                });
+            }
+
+            if selection_source == SelectionSource::Noise {
+                ui.horizontal(|ui| {
+                    ui.label("Noise: ");
+                    ui.label("Scale: ");
+                    changed = ui
+                        .add(egui::DragValue::new(&mut noise_mask.scale).clamp_range(1.0..=4096.0))
+                        .changed()
+                        || changed;
+                    ui.label("Octaves: ");
+                    changed = ui
+                        .add(egui::DragValue::new(&mut noise_mask.octaves).clamp_range(1..=8))
+                        .changed()
+                        || changed;
+                    changed = ui
+                        .add(egui::Slider::new(&mut noise_mask.level, 0.0..=1.0).text("Level"))
+                        .changed()
+                        || changed;
+                });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Operation: ");
+                for (operation, name) in [
+                    (IntervalOperation::Sort, "Sort"),
+                    (IntervalOperation::Shuffle, "Shuffle"),
+                    (IntervalOperation::Smear(SmearPixel::First), "Smear"),
+                    (
+                        IntervalOperation::Smear(SmearPixel::Brightest),
+                        "Smear brightest",
+                    ),
+                ] {
+                    changed = ui
+                        .selectable_value(&mut interval_options.operation, operation, name)
+                        .changed()
+                        || changed;
+                }
+
+                ui.label("Shift: ");
+                changed = ui
+                    .add(egui::DragValue::new(&mut interval_options.shift))
+                    .on_hover_text("move every interval along its row after sorting")
+                    .changed()
+                    || changed;
+                changed = ui
+                    .checkbox(&mut interval_options.random_shift, "Random")
+                    .on_hover_text("shift every interval by a random amount up to the shift")
+                    .changed()
+                    || changed;
+
+                ui.separator();
+                ui.label("Seed: ");
+                changed = ui
+                    .add(egui::DragValue::new(&mut interval_options.seed))
+                    .on_hover_text(
+                        "shuffling, splitting, random shifts and noise always turn out the same \
+                         with the same seed",
+                    )
+                    .changed()
+                    || changed;
+                if ui.button("Random").clicked() {
+                    // kept within what a drag value shows exactly
+                    interval_options.seed = rand::thread_rng().gen::<u32>() as u64;
+                    changed = true;
+                }
+                if ui
+                    .button("Copy")
+                    .on_hover_text("copy the seed, to pass it to --seed")
+                    .clicked()
+                {
+                    ui.output_mut(|output| output.copied_text = interval_options.seed.to_string());
+                }
+
+                ui.separator();
+                ui.label("Order: ");
+                changed = ui.checkbox(&mut order.descending, "Descending").changed() || changed;
+                changed = ui
+                    .checkbox(&mut order.zigzag, "Reverse every other interval")
+                    .changed()
+                    || changed;
+                changed = ui
+                    .checkbox(&mut order.alternate_paths, "Alternate direction per row")
+                    .changed()
+                    || changed;
+
+                ui.separator();
+                let keys = simple_keys();
+                let selected = tie_breaker.map_or("None", |i| keys[i].0);
+                egui::ComboBox::from_label("Ties broken by")
+                    .selected_text(selected)
+                    .show_ui(ui, |ui| {
+                        changed = ui
+                            .selectable_value(&mut tie_breaker, None, "None")
+                            .changed()
+                            || changed;
+                        for (i, (name, _)) in keys.iter().enumerate() {
+                            changed = ui
+                                .selectable_value(&mut tie_breaker, Some(i), *name)
+                                .changed()
+                                || changed;
+                        }
+                    });
+                order.tie_breaker = tie_breaker.map(|i| keys[i].1.clone());
+
+                ui.separator();
+                changed = ui
+                    .add(
+                        egui::Slider::new(&mut strength, 0.0..=100.0)
+                            .suffix("%")
+                            .text("Strength"),
+                    )
+                    .on_hover_text("blend the sorted image with the original")
+                    .changed()
+                    || changed;
+
+                changed = ui
+                    .add_enabled(
+                        sort_by.is_gray_level(),
+                        egui::Checkbox::new(&mut per_channel, "Per channel"),
+                    )
+                    .on_hover_text("sort the red, green and blue planes separately")
+                    .on_disabled_hover_text(
+                        "the planes are sorted by the level of their channel, which this key \
+                         doesn't measure",
+                    )
+                    .changed()
+                    || changed;
+            });
+
+            // presets are kept in the config directory, which the browser doesn't have
+            if cfg!(not(target_arch = "wasm32")) {
+            ui.horizontal(|ui| {
+                ui.label("Preset: ");
+                let mut load = apply_preset.take();
+                egui::ComboBox::from_id_source("preset")
+                    .selected_text(if presets.contains_key(&preset_name) {
+                        preset_name.as_str()
+                    } else {
+                        "None"
+                    })
+                    .show_ui(ui, |ui| {
+                        for name in presets.keys() {
+                            if ui.selectable_label(*name == preset_name, name).clicked() {
+                                load = Some(name.clone());
+                            }
+                        }
+                    });
+                ui.add(egui::TextEdit::singleline(&mut preset_name).desired_width(120.0))
+                    .on_hover_text("name to save the current settings under");
+
+                if ui
+                    .add_enabled(!preset_name.trim().is_empty(), egui::Button::new("Save"))
+                    .on_hover_text("also used by psorter --preset NAME")
+                    .clicked()
+                {
+                    let saved = current_pass
+                        .as_ref()
+                        .ok_or_else(|| "nothing to save yet".to_string())
+                        .and_then(|pass| preset::from_pass(pass, &script_source))
+                        .and_then(|mut preset| {
+                            if normalized_thresholds && preset.contains_key("thresholds") {
+                                preset.insert(
+                                    "thresholds".to_string(),
+                                    serde_json::json!([
+                                        format!("{}%", lower_percent),
+                                        format!("{}%", higher_percent)
+                                    ]),
+                                );
+                            }
+                            preset_name = preset_name.trim().to_string();
+                            presets.insert(preset_name.clone(), preset);
+                            preset::save_all(&presets)
+                        });
+                    if let Err(e) = saved {
+                        error_message = Some(format!("cannot save the preset: {}", e));
+                    }
+                    preset_thumbnails = None;
+                }
+                if ui
+                    .add_enabled(presets.contains_key(&preset_name), egui::Button::new("Delete"))
+                    .clicked()
+                {
+                    presets.remove(&preset_name);
+                    if let Err(e) = preset::save_all(&presets) {
+                        error_message = Some(format!("cannot delete the preset: {}", e));
+                    }
+                    preset_thumbnails = None;
+                }
+                ui.toggle_value(&mut presets_open, "Manage")
+                    .on_hover_text("list the presets with how they look on the image");
+
+                if let Some(name) = load.filter(|name| presets.contains_key(name)) {
+                    let defaults = preset_defaults(&config, &sort_by, lower_threshold, higher_threshold);
+                    match preset_pass(&presets[&name], &name, &defaults, &script_source, &tab.image) {
+                        Ok(pass) => {
+                            if let IntervalSource::Control(_) = &pass.thresholds.source {
+                                control_name = presets[&name]
+                                    .get("control")
+                                    .and_then(|path| path.as_str())
+                                    .and_then(basename)
+                                    .unwrap_or_default();
+                            }
+                            if let Some(source) =
+                                presets[&name].get("key-script").and_then(|s| s.as_str())
+                            {
+                                script_source = source.to_string();
+                            }
+                            preset_name = name;
+                            restore = Some(pass);
+                        }
+                        Err(e) => error_message = Some(format!("cannot load preset {}: {}", name, e)),
+                    }
+                }
+            });
+            }
+
+            ui.horizontal(|ui| {
+                ui.label("Passes: ");
+                let mut removed = None;
+                for (i, pass) in passes.iter().enumerate() {
+                    ui.label(format!("#{}", i + 1)).on_hover_text(format!(
+                        "thresholds {} - {}",
+                        pass.thresholds.lower, pass.thresholds.higher
+                    ));
+                    if ui.small_button("✖").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.separator();
+                }
+                if let Some(i) = removed {
+                    passes.remove(i);
+                    changed = true;
+                }
+                ui.label(format!("#{} (current settings)", passes.len() + 1));
+
+                if ui
+                    .button("Add pass")
+                    .on_hover_text("keep the current settings as a pass and sort again on top of it")
+                    .clicked()
+                {
+                    add_pass = true;
+                }
+                let apply = ui
+                    .add_enabled(
+                        current_pass.is_some() && !sorting,
+                        egui::Button::new("Apply"),
+                    )
+                    .on_hover_text(
+                        "replace the image with the sorted one, to sort it again with other \
+                         settings",
+                    )
+                    .clicked();
+                if let Some(pass) = current_pass.as_ref().filter(|_| apply) {
+                    // the passes are in the image now, undoing puts both back
+                    let before = Snapshot::new(&passes, pass, &script_source);
+                    tab.history.record(Snapshot {
+                        image: Some(std::mem::replace(&mut tab.image, tab.sorted.clone())),
+                        image_path: tab.path.take(),
+                        ..before
+                    });
+                    passes.clear();
+                    tab.recorded = Some(Snapshot::new(&passes, pass, &script_source));
+                    settings_changed = false;
+                    image_replaced = true;
+                    changed = true;
+                }
+                ui.separator();
+                undo = ui
+                    .add_enabled(tab.history.can_undo(), egui::Button::new("Undo"))
+                    .on_hover_text("Ctrl+Z")
+                    .clicked()
+                    || undo;
+                redo = ui
+                    .add_enabled(tab.history.can_redo(), egui::Button::new("Redo"))
+                    .on_hover_text("Ctrl+Shift+Z or Ctrl+Y")
+                    .clicked()
+                    || redo;
+            });
+        });
+
+        // stepping through the history isn't a step itself
+        let mut stepped = false;
+        if let Some(pass) = current_pass.as_ref().filter(|_| undo || redo) {
+            let current = Snapshot::new(&passes, pass, &script_source);
+            let step = if undo {
+                tab.history.undo(current, &mut tab.image, &mut tab.path)
+            } else {
+                tab.history.redo(current, &mut tab.image, &mut tab.path)
+            };
+            if let Some(step) = step {
+                passes = step.passes.clone();
+                script_source = step.script_source.clone();
+                restore = Some(step.pass.clone());
+                tab.recorded = Some(step);
+                settings_changed = false;
+                stepped = true;
+            }
+        }
+
+        // the settings of a preset or of a step of the history, put in the ui
+        if let Some(pass) = restore {
+            match &pass.sort_by {
+                SortBy::Script(script) => key_script = script.clone(),
+                SortBy::Weighted(expression) => {
+                    for (_, weight, key) in &mut weights {
+                        *weight = expression
+                            .terms
+                            .iter()
+                            .find(|(_, term)| term.name() == key.name())
+                            .map_or(0.0, |(weight, _)| *weight);
+                    }
+                }
+                _ => {}
+            }
+            if let Some(color) = pass.sort_by.reference_color() {
+                reference_color = color;
+            }
+            let key_index = |key: &SortBy| {
+                simple_keys()
+                    .iter()
+                    .position(|(_, simple)| simple.name() == key.name())
+            };
+            threshold_by = pass.thresholds.key.as_ref().and_then(key_index);
+            tie_breaker = pass.order.tie_breaker.as_ref().and_then(key_index);
+
+            (lower_threshold, higher_threshold) = (pass.thresholds.lower, pass.thresholds.higher);
+            selection_source = match &pass.thresholds.source {
+                IntervalSource::Threshold => SelectionSource::Thresholds,
+                IntervalSource::Noise(noise) => {
+                    noise_mask = noise.clone();
+                    SelectionSource::Noise
+                }
+                IntervalSource::Edges(edges) => {
+                    edge_mask = edges.clone();
+                    SelectionSource::Edges
+                }
+                IntervalSource::Control(control) => {
+                    control_image = Some(control.clone());
+                    SelectionSource::Control
+                }
+            };
+            let upper_boundary =
+                threshold_upper_boundary(pass.thresholds.key.as_ref().unwrap_or(&pass.sort_by));
+            lower_percent = lower_threshold as f32 * 100.0 / upper_boundary as f32;
+            higher_percent = higher_threshold as f32 * 100.0 / upper_boundary as f32;
+
+            invert_selection = pass.thresholds.invert;
+            mask_operations = pass.thresholds.mask_operations;
+            soft_mask = pass.thresholds.soft;
+            region = pass.thresholds.region;
+            sort_by = pass.sort_by;
+            traversal = pass.traversal;
+            use_blocks = pass.block_size.is_some();
+            block_size = pass.block_size.unwrap_or(block_size);
+            order = pass.order;
+            interval_options = pass.interval_options;
+            per_channel = pass.per_channel;
+            strength = pass.strength * 100.0;
+            changed = true;
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if copy_result {
+            let copied = match clipboard.as_mut() {
+                _ if sorting => Err("the image is still being sorted".to_string()),
+                Some(clipboard) => copy_image(clipboard, &tab.sorted),
+                None => Err("there's no clipboard".to_string()),
+            };
+            if let Err(e) = copied {
+                error_message = Some(format!("cannot copy: {}", e));
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if copy_command {
+            if let Some(pass) = &current_pass {
+                let all_passes: Vec<&Pass> = passes.iter().chain([pass]).collect();
+                // painted masks and control images only exist in memory, the command needs them
+                // as files
+                let files_needed = all_passes.iter().any(|pass| {
+                    matches!(pass.thresholds.region, Some(mask::Region::Mask(_)))
+                        || matches!(pass.thresholds.source, IntervalSource::Control(_))
+                });
+                let directory = if files_needed {
+                    rfd::FileDialog::new()
+                        .set_title("Where to save the masks and control images")
+                        .pick_folder()
+                } else {
+                    None
+                };
+                if !files_needed || directory.is_some() {
+                    let stem = file_stem(&tab.name);
+                    let command = all_passes
+                        .iter()
+                        .enumerate()
+                        .map(|(i, pass)| match &directory {
+                            Some(directory) => preset::from_pass_with_files(
+                                pass,
+                                &script_source,
+                                directory,
+                                &format!("{}-pass-{}", stem, i + 1),
+                            ),
+                            None => preset::from_pass(pass, &script_source),
+                        })
+                        .collect::<Result<Vec<_>, _>>()
+                        .and_then(|presets| {
+                            let image = match &tab.path {
+                                Some(path) => path.display().to_string(),
+                                None => tab.name.clone(),
+                            };
+                            crate::pipeline::command(&presets, &image)
+                        });
+                    match command {
+                        Ok(command) => ctx.output_mut(|output| output.copied_text = command),
+                        Err(e) => error_message = Some(format!("cannot copy the command: {}", e)),
+                    }
+                }
+            }
+        }
+
+        // keyframes and the frame shown aren't settings
+        let changed_before_timeline = changed;
+        if timeline_open {
+            egui::TopBottomPanel::bottom("timeline").show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Frame");
+                    changed = ui
+                        .add(egui::Slider::new(
+                            &mut timeline_frame,
+                            0..=timeline.frames - 1,
+                        ))
+                        .changed()
+                        || changed;
+
+                    ui.label("Frames");
+                    if ui
+                        .add(egui::DragValue::new(&mut timeline.frames).clamp_range(2..=1000))
+                        .changed()
+                    {
+                        timeline_frame = timeline_frame.min(timeline.frames - 1);
+                        changed = true;
+                    }
+
+                    ui.label("Frame delay");
+                    let mut delay = timeline.delay.as_millis() as u64;
+                    if ui
+                        .add(
+                            egui::DragValue::new(&mut delay)
+                                .clamp_range(1..=10_000)
+                                .suffix(" ms"),
+                        )
+                        .changed()
+                    {
+                        timeline.delay = Duration::from_millis(delay);
+                    }
+
+                    if ui.button("Clear").clicked() {
+                        timeline.tracks.clear();
+                        changed = true;
+                    }
+                    if ui.button("Render…").clicked() {
+                        let passes: Vec<Pass> =
+                            passes.iter().chain(&current_pass).cloned().collect();
+                        if let Err(e) = save_animation(
+                            &timeline,
+                            &tab.image,
+                            &passes,
+                            &file_stem(&tab.name),
+                            config.out_dir.as_deref(),
+                            &encode_options,
+                        ) {
+                            error_message = Some(e);
+                        }
+                    }
+                });
+
+                egui::Grid::new("timeline tracks")
+                    .num_columns(4)
+                    .show(ui, |ui| {
+                        for (parameter, name) in SweepParameter::ALL {
+                            let track = timeline.track(parameter);
+                            let keyed =
+                                track.is_some_and(|track| track.has_keyframe(timeline_frame));
+                            // without keyframes the parameter keeps the value of the settings,
+                            // intervals without a max length start out as long as a row
+                            let value = track
+                                .and_then(|track| track.value(timeline_frame))
+                                .or_else(|| {
+                                    current_pass.as_ref().and_then(|pass| parameter.get(pass))
+                                })
+                                .unwrap_or(tab.image.width() as f32);
+
+                            ui.label(name);
+                            if ui
+                                .selectable_label(keyed, "◆")
+                                .on_hover_text("add or remove a keyframe on this frame")
+                                .clicked()
+                            {
+                                if keyed {
+                                    timeline.track_mut(parameter).remove(timeline_frame);
+                                } else {
+                                    timeline.track_mut(parameter).set(timeline_frame, value);
+                                }
+                                changed = true;
+                            }
+
+                            let mut edited = value;
+                            let speed = match parameter {
+                                SweepParameter::SplitChance => 0.01,
+                                _ => 1.0,
+                            };
+                            if ui
+                                .add(egui::DragValue::new(&mut edited).speed(speed))
+                                .on_hover_text("changing it adds a keyframe on this frame")
+                                .changed()
+                            {
+                                timeline.track_mut(parameter).set(timeline_frame, edited);
+                                changed = true;
+                            }
+
+                            let (rect, response) = ui.allocate_exact_size(
+                                egui::vec2(ui.available_width().max(100.0), 16.0),
+                                egui::Sense::click_and_drag(),
+                            );
+                            let last_frame = (timeline.frames - 1) as f32;
+                            let frame_x = |frame: usize| {
+                                rect.left()
+                                    + rect.width() * frame.min(timeline.frames - 1) as f32
+                                        / last_frame
+                            };
+                            let painter = ui.painter_at(rect.expand(8.0));
+                            painter.hline(
+                                rect.x_range(),
+                                rect.center().y,
+                                ui.visuals().widgets.noninteractive.bg_stroke,
+                            );
+                            painter.vline(
+                                frame_x(timeline_frame),
+                                rect.y_range(),
+                                ui.visuals().selection.stroke,
+                            );
+                            for keyframe in
+                                timeline.track(parameter).map_or(&[][..], Track::keyframes)
+                            {
+                                let center = egui::pos2(frame_x(keyframe.frame), rect.center().y);
+                                painter.add(egui::Shape::convex_polygon(
+                                    [
+                                        egui::vec2(0.0, -5.0),
+                                        egui::vec2(5.0, 0.0),
+                                        egui::vec2(0.0, 5.0),
+                                        egui::vec2(-5.0, 0.0),
+                                    ]
+                                    .map(|offset| center + offset)
+                                    .to_vec(),
+                                    ui.visuals().text_color(),
+                                    egui::Stroke::NONE,
+                                ));
+                            }
+                            if let Some(position) = response.interact_pointer_pos() {
+                                let frame = ((position.x - rect.left()) / rect.width() * last_frame)
+                                    .round()
+                                    .clamp(0.0, last_frame)
+                                    as usize;
+                                if frame != timeline_frame {
+                                    timeline_frame = frame;
+                                    changed = true;
+                                }
+                            }
+                            ui.end_row();
+                        }
+                    });
+            });
+        }
+
+        view_changed |= changed && !changed_before_timeline;
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if presets_open {
+            if image_replaced || switched || stepped {
+                preset_thumbnails = None;
+            }
+            let thumbnails = preset_thumbnails.get_or_insert_with(|| {
+                let defaults =
+                    preset_defaults(&config, &sort_by, lower_threshold, higher_threshold);
+                // the ones that can't be read are left without
+                let (names, passes): (Vec<_>, Vec<_>) = presets
+                    .iter()
+                    .filter_map(|(name, preset)| {
+                        preset_pass(preset, name, &defaults, &script_source, &tab.image)
+                            .ok()
+                            .map(|pass| (name.clone(), pass))
+                    })
+                    .unzip();
+                names
+                    .into_iter()
+                    .zip(gallery::thumbnails(&tab.image, &[], &passes))
+                    .map(|(name, thumbnail)| {
+                        let texture = ctx.load_texture("preset", thumbnail, PREVIEW_TEXTURE);
+                        (name, texture)
+                    })
+                    .collect()
+            });
+
+            let mut deleted = None;
+            let mut renamed = None;
+            let mut edited = false;
+            egui::SidePanel::right("presets").show(ctx, |ui| {
+                ui.heading("Presets");
+                ui.horizontal(|ui| {
+                    if ui
+                        .button("Import…")
+                        .on_hover_text("add the presets of a file, replacing the ones named alike")
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .add_filter("Presets", &["yaml", "yml", "json"])
+                            .pick_file()
+                        {
+                            let imported = preset::load_file(&path).and_then(|imported| {
+                                presets.extend(imported);
+                                preset::save_all(&presets)
+                            });
+                            if let Err(e) = imported {
+                                error_message = Some(format!("cannot import presets: {}", e));
+                            }
+                            edited = true;
+                        }
+                    }
+                    if ui
+                        .add_enabled(!presets.is_empty(), egui::Button::new("Export…"))
+                        .on_hover_text("save every preset into a file of its own")
+                        .clicked()
+                    {
+                        if let Some(path) = rfd::FileDialog::new()
+                            .set_file_name("presets.yaml")
+                            .add_filter("Presets", &["yaml", "yml", "json"])
+                            .save_file()
+                        {
+                            if let Err(e) = preset::save_file(&presets, &path) {
+                                error_message = Some(format!("cannot export presets: {}", e));
+                            }
+                        }
+                    }
+                });
+                ui.separator();
+
+                egui::ScrollArea::vertical().show(ui, |ui| {
+                    for name in presets.keys() {
+                        ui.horizontal(|ui| {
+                            match thumbnails.get(name) {
+                                Some(thumbnail) => {
+                                    ui.image(thumbnail.id(), thumbnail.size_vec2() / 2.0);
+                                }
+                                None => {
+                                    ui.label("?").on_hover_text("cannot be read");
+                                }
+                            }
+                            ui.vertical(|ui| {
+                                match &mut renaming {
+                                    Some((old, new)) if old == name => {
+                                        ui.text_edit_singleline(new);
+                                        ui.horizontal(|ui| {
+                                            if ui.button("Ok").clicked() {
+                                                renamed =
+                                                    Some((old.clone(), new.trim().to_string()));
+                                            }
+                                            if ui.button("Cancel").clicked() {
+                                                renamed = Some((old.clone(), old.clone()));
+                                            }
+                                        });
+                                        return;
+                                    }
+                                    _ => {
+                                        ui.strong(name);
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    if ui.button("Apply").clicked() {
+                                        apply_preset = Some(name.clone());
+                                        ctx.request_repaint();
+                                    }
+                                    if ui.button("Rename").clicked() {
+                                        renaming = Some((name.clone(), name.clone()));
+                                    }
+                                    if ui.button("Delete").clicked() {
+                                        deleted = Some(name.clone());
+                                    }
+                                });
+                            });
+                        });
+                    }
+                });
+            });
+
+            if let Some(name) = deleted {
+                presets.remove(&name);
+                if let Err(e) = preset::save_all(&presets) {
+                    error_message = Some(format!("cannot delete the preset: {}", e));
+                }
+                edited = true;
+            }
+            if let Some((old, new)) = renamed {
+                renaming = None;
+                let saved = if new == old {
+                    Ok(())
+                } else if new.is_empty() {
+                    Err("a preset needs a name".to_string())
+                } else if presets.contains_key(&new) {
+                    Err(format!("there's a preset named {} already", new))
+                } else {
+                    let preset = presets.remove(&old).expect("the preset renamed is listed");
+                    presets.insert(new.clone(), preset);
+                    if preset_name == old {
+                        preset_name = new;
+                    }
+                    edited = true;
+                    preset::save_all(&presets)
+                };
+                if let Err(e) = saved {
+                    error_message = Some(format!("cannot rename the preset: {}", e));
+                }
+            }
+            if edited {
+                preset_thumbnails = None;
+            }
+        }
+
+        let dragging = ctx.input(|input| input.pointer.any_down());
+        let full_size_due =
+            proxy_sorted.is_some_and(|sorted| !dragging || sorted.elapsed() >= PROXY_PAUSE);
+        if full_size_due {
+            changed = true;
+            view_changed = true;
+        }
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            if texture.is_none() {
+                texture = Some(ctx.load_texture(&tab.name, tab.image.clone(), PREVIEW_TEXTURE));
+            }
+
+            if changed || add_pass {
+                changed = false;
+                settings_changed |=
+                    !(webcam_frame || image_replaced || stepped || switched || view_changed);
+                view_changed = false;
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    session_due = true;
+                }
+                let pass = Pass {
+                    thresholds: Thresholds {
+                        lower: lower_threshold,
+                        higher: higher_threshold,
+                        key: threshold_by.map(|i| simple_keys()[i].1.clone()),
+                        invert: invert_selection,
+                        source: match selection_source {
+                            // the command line has a single seed too
+                            SelectionSource::Noise => IntervalSource::Noise(noise::NoiseMask {
+                                seed: interval_options.seed,
+                                ..noise_mask.clone()
+                            }),
+                            SelectionSource::Edges => IntervalSource::Edges(edge_mask.clone()),
+                            SelectionSource::Control => match &control_image {
+                                Some(control) => IntervalSource::Control(control.clone()),
+                                None => IntervalSource::Threshold,
+                            },
+                            SelectionSource::Thresholds => IntervalSource::Threshold,
+                        },
+                        mask_operations: mask_operations.clone(),
+                        soft: soft_mask,
+                        region: region.clone(),
+                    },
+                    auto_threshold: None,
+                    sort_by: sort_by.clone(),
+                    traversal: traversal.clone(),
+                    block_size: use_blocks.then_some(block_size),
+                    order: order.clone(),
+                    interval_options: interval_options.clone(),
+                    per_channel: per_channel && sort_by.is_gray_level(),
+                    strength: strength / 100.0,
+                };
+                if add_pass {
+                    passes.push(pass.clone());
+                    add_pass = false;
+                }
+
+                // the settings without the values of the timeline, its keyframes move them
+                let mut shown_pass = pass.clone();
+                if timeline_open {
+                    timeline.apply(&mut shown_pass, timeline_frame);
+                }
+                let job = Job {
+                    image: tab.image.clone(),
+                    passes: passes.clone(),
+                    pass: shown_pass,
+                    selection: show_selection,
+                };
+                let proxy = dragging
+                    && !full_size_due
+                    && tab.image.width().max(tab.image.height()) > PROXY_SIZE;
+                if proxy {
+                    sorter.sort(job.downscaled(PROXY_SIZE));
+                    ctx.request_repaint_after(PROXY_PAUSE);
+                } else {
+                    sorter.sort(job);
+                }
+                proxy_sorted = proxy.then(Instant::now);
+                current_pass = Some(pass);
+                // loaded again once it's shown
+                original_texture = None;
+                mask_texture = match &region {
+                    Some(mask::Region::Mask(painted)) => Some(ctx.load_texture(
+                        "region mask",
+                        mask_overlay(painted),
+                        PREVIEW_TEXTURE,
+                    )),
+                    _ => None,
+                };
+            }
+
+            if let Some(preview) = sorter.finished() {
+                tab.sorted = preview.sorted;
+                histogram = preview.histogram;
+                selection_texture = preview
+                    .selection
+                    .map(|tinted| ctx.load_texture("selection", tinted, PREVIEW_TEXTURE));
+                if let Some(recording) = recorder.as_mut().filter(|_| webcam.is_some()) {
+                    if let Err(e) = recording.write(&tab.sorted) {
+                        error_message = Some(e);
+                        let _ = recorder.take().map(Recorder::finish);
+                    }
+                }
+                texture = Some(ctx.load_texture(&tab.name, tab.sorted.clone(), PREVIEW_TEXTURE));
+            } else if sorter.busy() {
+                ctx.request_repaint();
+            }
+
+            if let Some(texture) = texture.as_ref() {
+                ui.horizontal(|ui| {
+                    if ui.selectable_label(tab.zoom.is_none(), "Fit").clicked() {
+                        tab.zoom = None;
+                        tab.pan = egui::Vec2::ZERO;
+                    }
+                    if ui.selectable_label(tab.zoom == Some(1.0), "100%").clicked() {
+                        tab.zoom = Some(1.0);
+                        tab.pan = egui::Vec2::ZERO;
+                    }
+                    if let Some(zoom) = tab.zoom {
+                        ui.label(format!("{:.0}%", zoom * 100.0));
+                    }
+                    ui.separator();
+                    ui.toggle_value(&mut show_original, "Original")
+                        .on_hover_text("show the image before sorting, or hold Space");
+                    if ui
+                        .toggle_value(&mut show_selection, "Selection")
+                        .on_hover_text(
+                            "tint the pixels the current settings sort, on the image before them",
+                        )
+                        .changed()
+                    {
+                        changed = true;
+                        view_changed = true;
+                    }
+                    let mut split_view = split.is_some();
+                    if ui
+                        .toggle_value(&mut split_view, "Split")
+                        .on_hover_text(
+                            "show the original left of a divider and the preview right of it",
+                        )
+                        .changed()
+                    {
+                        split = split_view.then_some(0.5);
+                    }
+                    if sorter.busy() {
+                        ui.separator();
+                        ui.add(
+                            egui::ProgressBar::new(sorter.progress())
+                                .desired_width(120.0)
+                                .text("sorting"),
+                        )
+                        .on_hover_text("changing the settings starts over with the new ones");
+                    }
+                });
+                let comparing = show_original
+                    || (!typing && !focused && ctx.input(|input| input.key_down(egui::Key::Space)));
+                if (comparing || split.is_some()) && original_texture.is_none() {
+                    original_texture =
+                        Some(ctx.load_texture("original", tab.image.clone(), PREVIEW_TEXTURE));
+                }
+                let original = original_texture.as_ref().unwrap_or(texture);
+                let texture = match &selection_texture {
+                    _ if comparing => original,
+                    Some(selection) if show_selection => selection,
+                    _ => texture,
+                };
+
+                let (rect, response) =
+                    ui.allocate_exact_size(ui.available_size(), egui::Sense::click_and_drag());
+                let image_size = egui::vec2(tab.image.width() as f32, tab.image.height() as f32);
+                let fit = (rect.width() / image_size.x).min(rect.height() / image_size.y);
+                let mut scale = tab.zoom.unwrap_or(fit);
+
+                if let Some(pointer) = response.hover_pos() {
+                    // the wheel and pinching zoom around the pointer
+                    let factor =
+                        ui.input(|input| input.zoom_delta() * (input.scroll_delta.y / 200.0).exp());
+                    if factor != 1.0 {
+                        let zoomed = (scale * factor).clamp(0.05, 64.0);
+                        tab.pan += (pointer - rect.center() - tab.pan) * (1.0 - zoomed / scale);
+                        scale = zoomed;
+                        tab.zoom = Some(zoomed);
+                    }
+                }
+                let mut image_rect =
+                    egui::Rect::from_center_size(rect.center() + tab.pan, image_size * scale);
+                let divider = split.map(|split| image_rect.left() + image_rect.width() * split);
+                // near the divider the pointer moves it instead of the image
+                let near_divider = |position: egui::Pos2| {
+                    divider.is_some_and(|divider| (position.x - divider).abs() < 6.0)
+                };
+                if response.hover_pos().is_some_and(near_divider) || dragging_divider {
+                    ctx.set_cursor_icon(egui::CursorIcon::ResizeHorizontal);
+                }
+                if response.drag_started() {
+                    let origin = ctx.input(|input| input.pointer.press_origin());
+                    dragging_divider = origin.is_some_and(near_divider);
+                    region_start = origin
+                        .filter(|_| region_tool.is_some() && !dragging_divider)
+                        .map(|origin| ((origin - image_rect.min) / scale).to_pos2());
+                    lasso = region_start
+                        .filter(|_| region_tool == Some(RegionTool::Lasso))
+                        .into_iter()
+                        .collect();
+                }
+                if response.dragged() {
+                    match response.interact_pointer_pos() {
+                        Some(position) if dragging_divider => {
+                            split = Some(
+                                ((position.x - image_rect.left()) / image_rect.width())
+                                    .clamp(0.0, 1.0),
+                            );
+                        }
+                        Some(position) if region_tool == Some(RegionTool::Brush) => {
+                            let end = ((position - image_rect.min) / scale).to_pos2();
+                            if let Some(start) = region_start.replace(end) {
+                                region = Some(paint_region(
+                                    region.as_ref(),
+                                    tab.image.size,
+                                    start,
+                                    end,
+                                    &brush,
+                                ));
+                                changed = true;
+                            }
+                        }
+                        Some(position) if !lasso.is_empty() => {
+                            let point = ((position - image_rect.min) / scale).to_pos2();
+                            // a point for every few points of the screen is smooth enough
+                            if lasso
+                                .last()
+                                .is_some_and(|last| last.distance(point) * scale >= 3.0)
+                            {
+                                lasso.push(point);
+                            }
+                        }
+                        Some(position) if region_start.is_some() => {
+                            let end = ((position - image_rect.min) / scale).to_pos2();
+                            let dragged = egui::Rect::from_two_pos(region_start.unwrap(), end)
+                                .intersect(egui::Rect::from_min_size(egui::Pos2::ZERO, image_size));
+                            let (min, max) = (dragged.min.round(), dragged.max.round());
+                            let dragged = mask::Region::Rect([
+                                min.x as usize,
+                                min.y as usize,
+                                (max.x - min.x).max(0.0) as usize,
+                                (max.y - min.y).max(0.0) as usize,
+                            ]);
+                            if region.as_ref() != Some(&dragged) {
+                                region = Some(dragged);
+                                changed = true;
+                            }
+                        }
+                        _ => {
+                            tab.pan += response.drag_delta();
+                            image_rect = image_rect.translate(response.drag_delta());
+                        }
+                    }
+                } else {
+                    dragging_divider = false;
+                }
+                if response.drag_released() && !lasso.is_empty() {
+                    if lasso.len() >= 3 {
+                        let add = ctx.input(|input| input.modifiers.shift);
+                        region = Some(lasso_region(region.as_ref(), tab.image.size, &lasso, add));
+                        changed = true;
+                    }
+                    lasso.clear();
+                }
+
+                let painter = ui.painter_at(rect);
+                let uv = egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0));
+                match split {
+                    Some(split) => {
+                        let divider = image_rect.left() + image_rect.width() * split;
+                        let (left, right) =
+                            rect.split_left_right_at_x(divider.clamp(rect.left(), rect.right()));
+                        painter.with_clip_rect(left).image(
+                            original.id(),
+                            image_rect,
+                            uv,
+                            egui::Color32::WHITE,
+                        );
+                        painter.with_clip_rect(right).image(
+                            texture.id(),
+                            image_rect,
+                            uv,
+                            egui::Color32::WHITE,
+                        );
+                        painter.vline(
+                            divider,
+                            image_rect.y_range(),
+                            egui::Stroke::new(2.0, ui.visuals().selection.bg_fill),
+                        );
+                    }
+                    None => painter.image(texture.id(), image_rect, uv, egui::Color32::WHITE),
+                }
+                if let Some(overlay) = mask_texture
+                    .as_ref()
+                    .filter(|_| matches!(region_tool, Some(RegionTool::Brush | RegionTool::Lasso)))
+                {
+                    painter.image(overlay.id(), image_rect, uv, egui::Color32::WHITE);
+                }
+                if !lasso.is_empty() {
+                    painter.add(egui::Shape::line(
+                        lasso
+                            .iter()
+                            .map(|point| image_rect.min + point.to_vec2() * scale)
+                            .collect(),
+                        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+                    ));
+                }
+                if let Some(mask::Region::Rect([x, y, width, height])) = region {
+                    let outline = egui::Rect::from_min_size(
+                        image_rect.min + egui::vec2(x as f32, y as f32) * scale,
+                        egui::vec2(width as f32, height as f32) * scale,
+                    );
+                    painter.rect_stroke(
+                        outline,
+                        0.0,
+                        egui::Stroke::new(1.5, ui.visuals().selection.bg_fill),
+                    );
+                }
+
+                if (eyedropper || region_tool.is_some())
+                    && response
+                        .hover_pos()
+                        .is_some_and(|pointer| image_rect.contains(pointer))
+                {
+                    ctx.set_cursor_icon(egui::CursorIcon::Crosshair);
+                }
+                if let Some(pointer) = response
+                    .hover_pos()
+                    .filter(|_| region_tool == Some(RegionTool::Brush) && !eyedropper)
+                {
+                    painter.circle_stroke(
+                        pointer,
+                        brush.radius * scale,
+                        egui::Stroke::new(1.0, ui.visuals().selection.bg_fill),
+                    );
+                }
+                if let Some(position) = response
+                    .interact_pointer_pos()
+                    .filter(|_| response.clicked())
+                {
+                    let position = (position - image_rect.min) / scale;
+                    let pixel = (
+                        (position.x.max(0.0) as usize).min(tab.image.width() - 1),
+                        (position.y.max(0.0) as usize).min(tab.image.height() - 1),
+                    );
+                    if eyedropper {
+                        let key = match threshold_by {
+                            Some(i) => simple_keys()[i].1.clone(),
+                            None => sort_by.clone(),
+                        };
+                        let threshold_image = match (&control_image, selection_source) {
+                            (Some(control), SelectionSource::Control) => control,
+                            _ => &tab.image,
+                        };
+                        (lower_threshold, higher_threshold) = band_around(
+                            threshold_image,
+                            pixel,
+                            tab.image.size,
+                            &key,
+                            eyedropper_band,
+                        );
+                        let upper_boundary = threshold_upper_boundary(&key);
+                        lower_percent = lower_threshold as f32 * 100.0 / upper_boundary as f32;
+                        higher_percent = higher_threshold as f32 * 100.0 / upper_boundary as f32;
+                        changed = true;
+                    } else if region_tool == Some(RegionTool::Brush) {
+                        let position = position.to_pos2();
+                        region = Some(paint_region(
+                            region.as_ref(),
+                            tab.image.size,
+                            position,
+                            position,
+                            &brush,
+                        ));
+                        changed = true;
+                    } else if let Some(center) =
+                        traversal.center_mut().filter(|_| region_tool.is_none())
+                    {
+                        *center = Some(pixel);
+                        changed = true;
+                    }
+                }
+            } else {
+                ui.spinner();
+            }
+        });
+
+        // a drag is a single step, made once it's over
+        if settings_changed && !ctx.input(|input| input.pointer.any_down()) {
+            if let Some(pass) = &current_pass {
+                let current = Snapshot::new(&passes, pass, &script_source);
+                if let Some(previous) = tab.recorded.replace(current) {
+                    tab.history.record(previous);
+                }
+                settings_changed = false;
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if autosave.as_ref().is_some_and(|saving| saving.is_finished()) {
+                if let Ok(Err(e)) = autosave.take().unwrap().join() {
+                    error_message = Some(format!("cannot save the session: {}", e));
+                }
+            }
+            // the webcam would save every frame, and the session offered isn't overwritten
+            let due = (session_due || (tab.zoom, tab.pan) != autosaved_view)
+                && webcam.is_none()
+                && saved_session.is_none();
+            if due && autosave.is_none() && autosaved.elapsed() >= AUTOSAVE_INTERVAL {
+                if let Some(pass) = &current_pass {
+                    let session = Session {
+                        image_path: tab.path.clone(),
+                        image: tab.path.is_none().then(|| tab.image.clone()),
+                        passes: passes.clone(),
+                        pass: pass.clone(),
+                        script_source: script_source.clone(),
+                        zoom: tab.zoom,
+                        pan: tab.pan,
+                    };
+                    autosave = Some(std::thread::spawn(move || session.save()));
+                    autosaved = Instant::now();
+                    session_due = false;
+                    autosaved_view = (tab.zoom, tab.pan);
+                }
+            } else if due {
+                ctx.request_repaint_after(AUTOSAVE_INTERVAL);
+            }
+        }
+
+        if shortcuts_open {
+            egui::Window::new("Shortcuts")
+                .open(&mut shortcuts_open)
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("shortcuts").num_columns(2).show(ui, |ui| {
+                        for (keys, action) in SHORTCUTS {
+                            ui.strong(keys);
+                            ui.label(action);
+                            ui.end_row();
+                        }
+                    });
+                });
+        }
+
+        let mut close_settings = false;
+        if let Some(form) = &mut settings {
+            egui::Window::new("Settings")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+                        ui.label("Sort key");
+                        ui.text_edit_singleline(&mut form.key)
+                            .on_hover_text("like l, h or 0.7*l+0.3*s");
+                        ui.end_row();
+
+                        ui.label("Thresholds");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::TextEdit::singleline(&mut form.lower).desired_width(48.0));
+                            ui.add(
+                                egui::TextEdit::singleline(&mut form.higher).desired_width(48.0),
+                            );
+                        })
+                        .response
+                        .on_hover_text("integers or percentages");
+                        ui.end_row();
+
+                        ui.label("Output directory");
+                        ui.text_edit_singleline(&mut form.out_dir);
+                        ui.end_row();
+
+                        ui.label("Webcam");
+                        ui.text_edit_singleline(&mut form.webcam).on_hover_text(
+                            "as ffmpeg names it, like /dev/video1 on linux, 1 on macos or \
+                             video=NAME on windows. the first one when empty",
+                        );
+                        ui.end_row();
+
+                        ui.label("Theme");
+                        egui::ComboBox::from_id_source("settings theme")
+                            .selected_text(match form.theme {
+                                config::Theme::System => "System",
+                                config::Theme::Light => "Light",
+                                config::Theme::Dark => "Dark",
+                            })
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(
+                                    &mut form.theme,
+                                    config::Theme::System,
+                                    "System",
+                                );
+                                ui.selectable_value(&mut form.theme, config::Theme::Light, "Light");
+                                ui.selectable_value(&mut form.theme, config::Theme::Dark, "Dark");
+                            });
+                        ui.end_row();
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Save").clicked() {
+                            match form.config().and_then(|new| new.save().map(|_| new)) {
+                                Ok(new) => {
+                                    // following the system again only happens on restart
+                                    match new.theme {
+                                        config::Theme::Light => {
+                                            ctx.set_visuals(egui::Visuals::light())
+                                        }
+                                        config::Theme::Dark => {
+                                            ctx.set_visuals(egui::Visuals::dark())
+                                        }
+                                        config::Theme::System => {}
+                                    }
+                                    config = new;
+                                    close_settings = true;
+                                }
+                                Err(e) => error_message = Some(e),
+                            }
+                        }
+                        if ui.button("Cancel").clicked() {
+                            close_settings = true;
+                        }
+                    });
+                });
+        }
+        if close_settings {
+            settings = None;
+        }
+
+        if exporting {
+            egui::Window::new("Export")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("export").num_columns(2).show(ui, |ui| {
+                        ui.label("JPEG quality");
+                        ui.add(egui::Slider::new(&mut encode_options.jpeg_quality, 1..=100));
+                        ui.end_row();
+
+                        ui.label("Chroma subsampling");
+                        let subsamplings = [
+                            (ChromaSubsampling::Full, "4:4:4"),
+                            (ChromaSubsampling::Half, "4:2:2"),
+                            (ChromaSubsampling::Quarter, "4:2:0"),
+                        ];
+                        egui::ComboBox::from_id_source("export subsampling")
+                            .selected_text(
+                                subsamplings
+                                    .iter()
+                                    .find(|(subsampling, _)| {
+                                        *subsampling == encode_options.chroma_subsampling
+                                    })
+                                    .map_or("", |(_, name)| name),
+                            )
+                            .show_ui(ui, |ui| {
+                                for (subsampling, name) in subsamplings {
+                                    ui.selectable_value(
+                                        &mut encode_options.chroma_subsampling,
+                                        subsampling,
+                                        name,
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("how many pixels of a jpeg share a color");
+                        ui.end_row();
+
+                        ui.label("PNG compression");
+                        let compressions = [
+                            (PngCompression::Fast, "Fast"),
+                            (PngCompression::Balanced, "Balanced"),
+                            (PngCompression::Best, "Best"),
+                        ];
+                        egui::ComboBox::from_id_source("export compression")
+                            .selected_text(
+                                compressions
+                                    .iter()
+                                    .find(|(compression, _)| {
+                                        *compression == encode_options.png_compression
+                                    })
+                                    .map_or("", |(_, name)| name),
+                            )
+                            .show_ui(ui, |ui| {
+                                for (compression, name) in compressions {
+                                    ui.selectable_value(
+                                        &mut encode_options.png_compression,
+                                        compression,
+                                        name,
+                                    );
+                                }
+                            })
+                            .response
+                            .on_hover_text("smaller files take longer to write");
+                        ui.end_row();
+
+                        ui.label("WebP");
+                        ui.horizontal(|ui| {
+                            let mut lossless = encode_options.webp_quality.is_none();
+                            if ui
+                                .checkbox(&mut lossless, "Lossless")
+                                .on_hover_text("keeps every pixel as it is, in bigger files")
+                                .changed()
+                            {
+                                encode_options.webp_quality = (!lossless).then_some(75);
+                            }
+                            if let Some(quality) = &mut encode_options.webp_quality {
+                                ui.add(egui::Slider::new(quality, 1..=100));
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("AVIF quality");
+                        ui.add(egui::Slider::new(&mut encode_options.avif_quality, 1..=100));
+                        ui.end_row();
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui
+                            .add_enabled(!sorting, egui::Button::new("Save…"))
+                            .on_disabled_hover_text("still sorting")
+                            .clicked()
+                        {
+                            save_image(
+                                &tab.sorted,
+                                &tab.name,
+                                config.out_dir.as_deref(),
+                                &encode_options,
+                            );
+                            exporting = false;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            exporting = false;
+                        }
+                    });
+                });
+        }
+
+        if sweeping {
+            egui::Window::new("Sweep")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    egui::Grid::new("sweep").num_columns(2).show(ui, |ui| {
+                        ui.label("Parameter");
+                        egui::ComboBox::from_id_source("sweep parameter")
+                            .selected_text(
+                                SweepParameter::ALL
+                                    .iter()
+                                    .find(|(parameter, _)| *parameter == sweep.parameter)
+                                    .map_or("", |(_, name)| name),
+                            )
+                            .show_ui(ui, |ui| {
+                                for (parameter, name) in SweepParameter::ALL {
+                                    ui.selectable_value(&mut sweep.parameter, parameter, name);
+                                }
+                            })
+                            .response
+                            .on_hover_text("strength in percent, split chance from 0 to 1");
+                        ui.end_row();
+
+                        ui.label("From");
+                        ui.add(egui::DragValue::new(&mut sweep.from));
+                        ui.end_row();
+
+                        ui.label("To");
+                        ui.add(egui::DragValue::new(&mut sweep.to));
+                        ui.end_row();
+
+                        ui.label("Frames");
+                        ui.add(egui::DragValue::new(&mut sweep.frames).clamp_range(2..=1000));
+                        ui.end_row();
+
+                        ui.label("Frame delay");
+                        let mut delay = sweep.delay.as_millis() as u64;
+                        if ui
+                            .add(
+                                egui::DragValue::new(&mut delay)
+                                    .clamp_range(1..=10_000)
+                                    .suffix(" ms"),
+                            )
+                            .changed()
+                        {
+                            sweep.delay = Duration::from_millis(delay);
+                        }
+                        ui.end_row();
+                    });
+
+                    ui.horizontal(|ui| {
+                        if ui.button("Render…").clicked() {
+                            let passes: Vec<Pass> =
+                                passes.iter().chain(&current_pass).cloned().collect();
+                            if let Err(e) = save_animation(
+                                &sweep.timeline(),
+                                &tab.image,
+                                &passes,
+                                &file_stem(&tab.name),
+                                config.out_dir.as_deref(),
+                                &encode_options,
+                            ) {
+                                error_message = Some(e);
+                            }
+                            sweeping = false;
+                        }
+                        if ui.button("Cancel").clicked() {
+                            sweeping = false;
+                        }
+                    });
+                });
+        }
+
+        if error_message.is_some() {
+            egui::Window::new("Error")
+                .collapsible(false)
+                .resizable(false)
+                .show(ctx, |ui| {
+                    ui.label(error_message.as_ref().unwrap());
+                    if ui.button("Ok").clicked() {
+                        error_message = None;
+                    }
+                });
+        }
+    }
+}
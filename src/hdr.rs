@@ -0,0 +1,124 @@
+//! high dynamic range images, OpenEXR and Radiance HDR, sorted without tone mapping them
+//! first, see `Pass::apply_hdr`
+use std::path::Path;
+
+/// whether `format` holds linear light as floats
+pub fn is_hdr_format(format: image::ImageFormat) -> bool {
+    matches!(
+        format,
+        image::ImageFormat::OpenExr | image::ImageFormat::Hdr
+    )
+}
+
+/// an image in linear light, channels can go past 1
+#[derive(Clone)]
+pub struct HdrImage {
+    pub size: [usize; 2],
+    /// red, green, blue and alpha, row by row
+    pub pixels: Vec<[f32; 4]>,
+}
+
+impl HdrImage {
+    pub fn load(path: &Path) -> Result<HdrImage, String> {
+        let image = image::open(path).map_err(|e| e.to_string())?;
+        let size = [image.width() as usize, image.height() as usize];
+        let pixels = image.into_rgba32f().pixels().map(|pixel| pixel.0).collect();
+        Ok(HdrImage { size, pixels })
+    }
+
+    /// 8 bit colors as they are, from 0 to 1
+    pub fn from_color_image(image: &egui::ColorImage) -> HdrImage {
+        HdrImage {
+            size: image.size,
+            pixels: image
+                .pixels
+                .iter()
+                .map(|pixel| pixel.to_array().map(|channel| channel as f32 / 255.0))
+                .collect(),
+        }
+    }
+
+    /// the inverse of `from_color_image`, channels past 1 are clipped
+    pub fn to_color_image(&self) -> egui::ColorImage {
+        let channel = |value: f32| (value * 255.0).round().clamp(0.0, 255.0) as u8;
+        egui::ColorImage {
+            size: self.size,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|&[red, green, blue, alpha]| {
+                    egui::Color32::from_rgba_premultiplied(
+                        channel(red),
+                        channel(green),
+                        channel(blue),
+                        channel(alpha),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    /// Radiance HDR has no alpha, it's dropped
+    pub fn encode(&self, format: image::ImageFormat) -> Result<Vec<u8>, String> {
+        let [width, height] = self.size;
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        match format {
+            image::ImageFormat::OpenExr => {
+                let buffer = image::Rgba32FImage::from_raw(
+                    width as u32,
+                    height as u32,
+                    self.pixels.concat(),
+                )
+                .ok_or("image buffer has the wrong size")?;
+                image::DynamicImage::ImageRgba32F(buffer)
+                    .write_to(&mut bytes, format)
+                    .map_err(|e| e.to_string())?;
+            }
+            image::ImageFormat::Hdr => {
+                let pixels: Vec<image::Rgb<f32>> = self
+                    .pixels
+                    .iter()
+                    .map(|&[red, green, blue, _]| image::Rgb([red, green, blue]))
+                    .collect();
+                image::codecs::hdr::HdrEncoder::new(&mut bytes)
+                    .encode(&pixels, width, height)
+                    .map_err(|e| e.to_string())?;
+            }
+            format => return Err(format!("{:?} is not a high dynamic range format", format)),
+        }
+        Ok(bytes.into_inner())
+    }
+
+    /// what keys and selections are computed on: the linear light scaled so the brightest
+    /// channel of the image is 255
+    pub fn key_image(&self) -> egui::ColorImage {
+        let brightest = self
+            .pixels
+            .iter()
+            .flat_map(|pixel| &pixel[..3])
+            .filter(|channel| channel.is_finite())
+            .fold(0.0, |brightest: f32, &channel| brightest.max(channel));
+        let scale = if brightest > 0.0 {
+            255.0 / brightest
+        } else {
+            0.0
+        };
+        let channel = |value: f32, scale: f32| (value * scale).round().clamp(0.0, 255.0) as u8;
+
+        egui::ColorImage {
+            size: self.size,
+            pixels: self
+                .pixels
+                .iter()
+                .map(|&[red, green, blue, alpha]| {
+                    egui::Color32::from_rgba_unmultiplied(
+                        channel(red, scale),
+                        channel(green, scale),
+                        channel(blue, scale),
+                        channel(alpha, 255.0),
+                    )
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! undo and redo in the gui. every step keeps the settings from before a change, and the
+//! image from before it was replaced when another one was opened
+use psorter::mask;
+use psorter::sort::Pass;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+/// the most steps kept, the oldest ones are forgotten first
+const MAX_STEPS: usize = 100;
+/// how many bytes of replaced images and painted masks are kept at most, a few big photos
+const MAX_IMAGE_BYTES: usize = 512 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct Snapshot {
+    pub passes: Vec<Pass>,
+    /// the current settings
+    pub pass: Pass,
+    pub script_source: String,
+    /// the image sorted, only kept when a step replaces it
+    pub image: Option<egui::ColorImage>,
+    /// where `image` was opened from
+    pub image_path: Option<PathBuf>,
+}
+
+impl Snapshot {
+    pub fn new(passes: &[Pass], pass: &Pass, script_source: &str) -> Snapshot {
+        Snapshot {
+            passes: passes.to_vec(),
+            pass: pass.clone(),
+            script_source: script_source.to_string(),
+            image: None,
+            image_path: None,
+        }
+    }
+
+    /// of the replaced image and the painted region masks
+    fn image_bytes(&self) -> usize {
+        let masks = self.passes.iter().chain([&self.pass]).filter_map(|pass| {
+            match &pass.thresholds.region {
+                Some(mask::Region::Mask(painted)) => Some(painted.values.len()),
+                _ => None,
+            }
+        });
+        self.image
+            .as_ref()
+            .map_or(0, |image| image.pixels.len() * 4)
+            + masks.sum::<usize>()
+    }
+}
+
+#[derive(Default)]
+pub struct History {
+    undo: VecDeque<Snapshot>,
+    redo: Vec<Snapshot>,
+}
+
+impl History {
+    /// keeps `snapshot` to go back to, forgetting what was undone
+    pub fn record(&mut self, snapshot: Snapshot) {
+        self.undo.push_back(snapshot);
+        self.redo.clear();
+        let mut image_bytes: usize = self.undo.iter().map(Snapshot::image_bytes).sum();
+        while self.undo.len() > MAX_STEPS || image_bytes > MAX_IMAGE_BYTES {
+            let forgotten = self.undo.pop_front().unwrap();
+            image_bytes -= forgotten.image_bytes();
+        }
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// the step before `current`, which is kept to be redone. a step that replaced the image
+    /// puts the one before back in `image`, and where it was opened from in `image_path`
+    pub fn undo(
+        &mut self,
+        current: Snapshot,
+        image: &mut egui::ColorImage,
+        image_path: &mut Option<PathBuf>,
+    ) -> Option<Snapshot> {
+        let previous = self.undo.pop_back()?;
+        let (previous, current) = swap_images(previous, current, image, image_path);
+        self.redo.push(current);
+        Some(previous)
+    }
+
+    /// the step `undo` went back from, `current` can be undone again
+    pub fn redo(
+        &mut self,
+        current: Snapshot,
+        image: &mut egui::ColorImage,
+        image_path: &mut Option<PathBuf>,
+    ) -> Option<Snapshot> {
+        let next = self.redo.pop()?;
+        let (next, current) = swap_images(next, current, image, image_path);
+        self.undo.push_back(current);
+        Some(next)
+    }
+}
+
+/// puts the image of `step` in `image`, the one it replaces goes with `current`
+fn swap_images(
+    mut step: Snapshot,
+    mut current: Snapshot,
+    image: &mut egui::ColorImage,
+    image_path: &mut Option<PathBuf>,
+) -> (Snapshot, Snapshot) {
+    if let Some(replaced) = step.image.take() {
+        current.image = Some(std::mem::replace(image, replaced));
+        current.image_path = std::mem::replace(image_path, step.image_path.take());
+    }
+    (step, current)
+}
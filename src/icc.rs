@@ -0,0 +1,242 @@
+//! ICC color profiles: kept from the input image in the sorted file, and optionally used to
+//! compute keys on the colors converted to sRGB, so wide gamut images sort like any other
+use crate::io::{insert_jpeg_segments, insert_png_chunk, jpeg_segment};
+use image::ImageDecoder;
+use std::io::Write;
+
+/// the profile embedded in the png or jpeg at `path`, none for other formats and standard
+/// input
+pub fn read_profile(path: &str) -> Option<Vec<u8>> {
+    if path == "-" {
+        return None;
+    }
+    let reader = image::io::Reader::open(path)
+        .ok()?
+        .with_guessed_format()
+        .ok()?;
+    match reader.format()? {
+        image::ImageFormat::Png => image::codecs::png::PngDecoder::new(reader.into_inner())
+            .ok()?
+            .icc_profile(),
+        image::ImageFormat::Jpeg => image::codecs::jpeg::JpegDecoder::new(reader.into_inner())
+            .ok()?
+            .icc_profile(),
+        _ => None,
+    }
+}
+
+/// adds `profile` to an image encoded in `format`, only pngs and jpegs can hold one
+pub fn embed_profile(
+    bytes: &mut Vec<u8>,
+    format: image::ImageFormat,
+    profile: &[u8],
+) -> Result<(), String> {
+    match format {
+        image::ImageFormat::Png => insert_png_chunk(bytes, b"iCCP", &iccp_data(profile)?),
+        image::ImageFormat::Jpeg => insert_jpeg_segments(bytes, jpeg_segments(profile)?),
+        format => Err(format!("{:?} files cannot hold a color profile", format)),
+    }
+}
+
+/// the data of a png iCCP chunk: a name and the compressed profile
+pub(crate) fn iccp_data(profile: &[u8]) -> Result<Vec<u8>, String> {
+    let mut data = b"ICC profile\0\0".to_vec();
+    let mut encoder = flate2::write::ZlibEncoder::new(&mut data, flate2::Compression::default());
+    encoder.write_all(profile).map_err(|e| e.to_string())?;
+    encoder.finish().map_err(|e| e.to_string())?;
+    Ok(data)
+}
+
+/// APP2 segments holding the profile, split in numbered parts as long as a segment allows
+fn jpeg_segments(profile: &[u8]) -> Result<Vec<u8>, String> {
+    const SIGNATURE: &[u8] = b"ICC_PROFILE\0";
+    const PART_LENGTH: usize = u16::MAX as usize - 2 - SIGNATURE.len() - 2;
+    let parts: Vec<&[u8]> = profile.chunks(PART_LENGTH).collect();
+    if parts.len() > u8::MAX as usize {
+        return Err("the color profile is too big for a jpeg".to_string());
+    }
+
+    let mut segments = Vec::new();
+    for (i, part) in parts.iter().enumerate() {
+        let data = [SIGNATURE, &[i as u8 + 1, parts.len() as u8], part].concat();
+        segments.extend(jpeg_segment(0xe2, &data)?);
+    }
+    Ok(segments)
+}
+
+/// from the XYZ colors of profiles, white being D50, to linear sRGB
+const XYZ_TO_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+/// an rgb profile made of a curve for every channel and a matrix to XYZ, like the profiles of
+/// Display P3, Adobe RGB and most cameras and screens
+pub struct ColorProfile {
+    /// the linear light of every 8 bit value of red, green and blue
+    curves: [[f32; 256]; 3],
+    /// from the linear light of the profile to linear sRGB
+    to_srgb: [[f32; 3]; 3],
+}
+
+impl ColorProfile {
+    pub fn parse(profile: &[u8]) -> Result<ColorProfile, String> {
+        let unsupported = || "only rgb profiles made of curves and a matrix are supported";
+        if profile.get(16..20) != Some(b"RGB ") {
+            return Err(unsupported().to_string());
+        }
+
+        let tag = |name: &[u8; 4]| -> Option<&[u8]> {
+            let count = read_u32(profile, 128)? as usize;
+            (0..count.min(1024)).find_map(|i| {
+                let entry = 132 + i * 12;
+                if profile.get(entry..entry + 4)? != name {
+                    return None;
+                }
+                let offset = read_u32(profile, entry + 4)? as usize;
+                let size = read_u32(profile, entry + 8)? as usize;
+                profile.get(offset..offset.checked_add(size)?)
+            })
+        };
+        let primary = |name| {
+            let data = tag(name).filter(|data| data.starts_with(b"XYZ "))?;
+            Some([8, 12, 16].map(|offset| read_fixed(data, offset).unwrap_or(f32::NAN)))
+        };
+        let curve = |name| tag(name).and_then(curve);
+
+        let (Some(red), Some(green), Some(blue)) =
+            (primary(b"rXYZ"), primary(b"gXYZ"), primary(b"bXYZ"))
+        else {
+            return Err(unsupported().to_string());
+        };
+        let (Some(red_curve), Some(green_curve), Some(blue_curve)) =
+            (curve(b"rTRC"), curve(b"gTRC"), curve(b"bTRC"))
+        else {
+            return Err(unsupported().to_string());
+        };
+        if [red, green, blue]
+            .iter()
+            .flatten()
+            .any(|value| !value.is_finite())
+        {
+            return Err("the profile has a broken matrix".to_string());
+        }
+
+        // the primaries are the columns of the matrix of the profile
+        let to_srgb = XYZ_TO_SRGB
+            .map(|row| [red, green, blue].map(|primary| (0..3).map(|i| row[i] * primary[i]).sum()));
+        Ok(ColorProfile {
+            curves: [red_curve, green_curve, blue_curve],
+            to_srgb,
+        })
+    }
+
+    /// the colors of `image` in sRGB, colors out of its gamut are clipped
+    pub fn to_srgb(&self, image: &egui::ColorImage) -> egui::ColorImage {
+        let encode = |linear: f32| {
+            let linear = linear.clamp(0.0, 1.0);
+            let value = if linear <= 0.003_130_8 {
+                linear * 12.92
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+            (value * 255.0).round() as u8
+        };
+
+        egui::ColorImage {
+            size: image.size,
+            pixels: image
+                .pixels
+                .iter()
+                .map(|pixel| {
+                    let [red, green, blue, alpha] = pixel.to_srgba_unmultiplied();
+                    let linear = [
+                        self.curves[0][red as usize],
+                        self.curves[1][green as usize],
+                        self.curves[2][blue as usize],
+                    ];
+                    let [red, green, blue] = self
+                        .to_srgb
+                        .map(|row| encode((0..3).map(|i| row[i] * linear[i]).sum()));
+                    egui::Color32::from_rgba_unmultiplied(red, green, blue, alpha)
+                })
+                .collect(),
+        }
+    }
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// an s15Fixed16Number
+fn read_fixed(bytes: &[u8], offset: usize) -> Option<f32> {
+    Some(read_u32(bytes, offset)? as i32 as f32 / 65536.0)
+}
+
+/// the linear light of every 8 bit value, from a `curv` or `para` tag
+fn curve(data: &[u8]) -> Option<[f32; 256]> {
+    let function: Box<dyn Fn(f32) -> f32> = match data.get(..4)? {
+        b"curv" => {
+            let count = read_u32(data, 8)? as usize;
+            match count {
+                0 => Box::new(|x| x),
+                1 => {
+                    let gamma = read_u16(data, 12)? as f32 / 256.0;
+                    Box::new(move |x: f32| x.powf(gamma))
+                }
+                _ => {
+                    let table: Vec<f32> = (0..count)
+                        .map(|i| read_u16(data, 12 + i * 2).map(|y| y as f32 / 65535.0))
+                        .collect::<Option<_>>()?;
+                    Box::new(move |x: f32| {
+                        let position = x * (table.len() - 1) as f32;
+                        let below = position.floor() as usize;
+                        let above = (below + 1).min(table.len() - 1);
+                        let fraction = position - below as f32;
+                        table[below] + (table[above] - table[below]) * fraction
+                    })
+                }
+            }
+        }
+        b"para" => {
+            let kind = read_u16(data, 8)?;
+            let length = [1, 3, 4, 5, 7].get(kind as usize)?;
+            let parameters: Vec<f32> = (0..*length)
+                .map(|i| read_fixed(data, 12 + i * 4))
+                .collect::<Option<_>>()?;
+            // every kind is the last one with some parameters left out
+            let [g, a, b, c, d, e, f] = match *parameters.as_slice() {
+                [g] => [g, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                [g, a, b] => [g, a, b, 0.0, -b / a, 0.0, 0.0],
+                [g, a, b, c] => [g, a, b, 0.0, -b / a, c, c],
+                [g, a, b, c, d] => [g, a, b, c, d, 0.0, 0.0],
+                [g, a, b, c, d, e, f] => [g, a, b, c, d, e, f],
+                _ => return None,
+            };
+            Box::new(move |x: f32| {
+                if x >= d {
+                    (a * x + b).max(0.0).powf(g) + e
+                } else {
+                    c * x + f
+                }
+            })
+        }
+        _ => return None,
+    };
+
+    let mut curve = [0.0; 256];
+    for (value, linear) in curve.iter_mut().enumerate() {
+        *linear = function(value as f32 / 255.0);
+    }
+    curve.iter().all(|value| value.is_finite()).then_some(curve)
+}
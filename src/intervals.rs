@@ -0,0 +1,604 @@
+//! the paths pixels are walked along and how they're split into the intervals that get sorted
+use crate::keys::SortBy;
+use crate::{convolution, mask, noise, svg};
+use rand::Rng;
+use std::sync::Arc;
+
+pub(crate) fn into_intervals(bitmap: Vec<bool>) -> Vec<(usize, usize)> {
+    let mut result: Vec<(usize, usize)> = Vec::new();
+    let mut interval_start: Option<usize> = None;
+
+    for (i, &accepted) in bitmap.iter().enumerate() {
+        match interval_start {
+            Some(start) if !accepted => {
+                result.push((start, i));
+                interval_start = None;
+            }
+            None if accepted => interval_start = Some(i),
+            _ => {}
+        }
+    }
+
+    if let Some(start) = interval_start {
+        result.push((start, bitmap.len()));
+    }
+
+    result
+}
+
+/// the paths pixels are walked along, every one is split into intervals of its own
+#[derive(Clone)]
+pub enum Traversal {
+    /// every row from left to right
+    Rows,
+    /// straight lines going `angle` degrees clockwise from left to right, 90 runs down the
+    /// columns
+    Lines {
+        /// in degrees
+        angle: f32,
+    },
+    /// rays going outwards from the center, `None` means the middle of the image
+    Radial {
+        /// in pixels from the top left corner
+        center: Option<(usize, usize)>,
+    },
+    /// a single run winding outwards from the center
+    Spiral {
+        /// in pixels from the top left corner, `None` means the middle of the image
+        center: Option<(usize, usize)>,
+    },
+    /// a single run following a Hilbert curve over the whole image
+    Hilbert,
+    /// the whole image flattened into a single run, so intervals can wrap across rows
+    Whole(ScanOrder),
+    /// rasterized lines from an svg file
+    Svg(svg::SvgPaths),
+}
+
+/// how `Traversal::Whole` flattens the image
+#[derive(Clone, Copy, PartialEq)]
+pub enum ScanOrder {
+    /// a row after the other
+    RowMajor,
+    /// a column after the other
+    ColumnMajor,
+}
+
+impl Traversal {
+    /// the point radial and spiral paths start from, none for the middle of the image
+    pub fn center_mut(&mut self) -> Option<&mut Option<(usize, usize)>> {
+        match self {
+            Traversal::Rows
+            | Traversal::Lines { .. }
+            | Traversal::Hilbert
+            | Traversal::Whole(_)
+            | Traversal::Svg(_) => None,
+            Traversal::Radial { center } | Traversal::Spiral { center } => Some(center),
+        }
+    }
+}
+
+/// a point given as `x,y` in pixels, like the center of a radial traversal
+pub fn parse_point(arg: &str) -> Option<(usize, usize)> {
+    let (x, y) = arg.split_once(',')?;
+    Some((x.trim().parse().ok()?, y.trim().parse().ok()?))
+}
+
+pub(crate) fn row_paths(width: usize, height: usize) -> Vec<Vec<usize>> {
+    (0..height)
+        .map(|yi| (yi * width..(yi + 1) * width).collect())
+        .collect()
+}
+
+/// parallel lines covering every pixel once, ordered along `angle`
+pub(crate) fn line_paths(width: usize, height: usize, angle: f32) -> Vec<Vec<usize>> {
+    let (sin, cos) = angle.to_radians().sin_cos();
+    // which line a pixel is on, counted across the direction
+    let across = |xi: usize, yi: usize| (yi as f32 * cos - xi as f32 * sin).round() as isize;
+    let corners = [
+        (0, 0),
+        (width - 1, 0),
+        (0, height - 1),
+        (width - 1, height - 1),
+    ]
+    .map(|(xi, yi)| across(xi, yi));
+    let first = *corners.iter().min().unwrap();
+    let last = *corners.iter().max().unwrap();
+    let mut lines: Vec<Vec<(f32, usize)>> = vec![Vec::new(); (last - first + 1) as usize];
+
+    for yi in 0..height {
+        for xi in 0..width {
+            let along = xi as f32 * cos + yi as f32 * sin;
+            lines[(across(xi, yi) - first) as usize].push((along, yi * width + xi));
+        }
+    }
+
+    lines
+        .into_iter()
+        .filter(|line| !line.is_empty())
+        .map(|mut line| {
+            line.sort_by(|a, b| a.0.total_cmp(&b.0));
+            line.into_iter().map(|(_, index)| index).collect()
+        })
+        .collect()
+}
+
+pub(crate) fn radial_paths(width: usize, height: usize, center: (usize, usize)) -> Vec<Vec<usize>> {
+    let (cx, cy) = (center.0.min(width - 1), center.1.min(height - 1));
+
+    // one ray per pixel on the border, so the outermost ring is fully covered
+    let ray_count = (2 * (width + height)).saturating_sub(4).max(1);
+    let mut rays: Vec<Vec<(usize, usize)>> = vec![Vec::new(); ray_count];
+
+    for yi in 0..height {
+        for xi in 0..width {
+            let dx = xi as f32 - cx as f32;
+            let dy = yi as f32 - cy as f32;
+            let angle = dy.atan2(dx) + std::f32::consts::PI;
+            let ray = ((angle / std::f32::consts::TAU) * ray_count as f32) as usize % ray_count;
+            let distance = dx.abs().max(dy.abs()) as usize;
+            rays[ray].push((distance, yi * width + xi));
+        }
+    }
+
+    rays.into_iter()
+        .filter(|ray| !ray.is_empty())
+        .map(|mut ray| {
+            ray.sort_by_key(|&(distance, _)| distance);
+            ray.into_iter().map(|(_, index)| index).collect()
+        })
+        .collect()
+}
+
+pub(crate) fn spiral_path(width: usize, height: usize, center: (usize, usize)) -> Vec<usize> {
+    let (mut x, mut y) = (
+        center.0.min(width - 1) as isize,
+        center.1.min(height - 1) as isize,
+    );
+    let mut path = Vec::with_capacity(width * height);
+    path.push(y as usize * width + x as usize);
+
+    // right, down, left, up with the leg length growing every second turn
+    let directions = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut leg = 1;
+    let mut turn = 0;
+    while path.len() < width * height {
+        let (dx, dy) = directions[turn % 4];
+        for _ in 0..leg {
+            x += dx;
+            y += dy;
+            if (0..width as isize).contains(&x) && (0..height as isize).contains(&y) {
+                path.push(y as usize * width + x as usize);
+            }
+        }
+
+        turn += 1;
+        if turn % 2 == 0 {
+            leg += 1;
+        }
+    }
+
+    path
+}
+
+/// converts a distance along a Hilbert curve filling a `side`x`side` square into a point
+pub(crate) fn hilbert_point(side: usize, distance: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = distance;
+    let mut s = 1;
+
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+
+    (x, y)
+}
+
+pub(crate) fn hilbert_path(width: usize, height: usize) -> Vec<usize> {
+    let side = width.max(height).next_power_of_two();
+    (0..side * side)
+        .map(|distance| hilbert_point(side, distance))
+        .filter(|&(x, y)| x < width && y < height)
+        .map(|(x, y)| y * width + x)
+        .collect()
+}
+
+pub(crate) fn whole_path(width: usize, height: usize, order: ScanOrder) -> Vec<usize> {
+    match order {
+        ScanOrder::RowMajor => (0..width * height).collect(),
+        ScanOrder::ColumnMajor => (0..width)
+            .flat_map(|xi| (0..height).map(move |yi| yi * width + xi))
+            .collect(),
+    }
+}
+
+/// sequences of pixel indices along which intervals are extracted and sorted
+pub(crate) fn traversal_paths(
+    traversal: &Traversal,
+    width: usize,
+    height: usize,
+) -> Vec<Vec<usize>> {
+    if width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    match traversal {
+        Traversal::Rows => row_paths(width, height),
+        Traversal::Lines { angle } => line_paths(width, height, *angle),
+        Traversal::Radial { center } => {
+            radial_paths(width, height, center.unwrap_or((width / 2, height / 2)))
+        }
+        Traversal::Spiral { center } => vec![spiral_path(
+            width,
+            height,
+            center.unwrap_or((width / 2, height / 2)),
+        )],
+        Traversal::Hilbert => vec![hilbert_path(width, height)],
+        Traversal::Whole(order) => vec![whole_path(width, height, *order)],
+        Traversal::Svg(paths) => paths.rasterize(width, height),
+    }
+}
+
+/// runs the traversal independently inside every `size`x`size` block of the image
+pub(crate) fn block_paths(
+    traversal: &Traversal,
+    width: usize,
+    height: usize,
+    size: usize,
+) -> Vec<Vec<usize>> {
+    // centers are in image coordinates, inside a block the block's middle is used instead
+    let mut block_traversal = traversal.clone();
+    if let Some(center) = block_traversal.center_mut() {
+        *center = None;
+    }
+
+    let mut paths = Vec::new();
+    for block_y in (0..height).step_by(size) {
+        for block_x in (0..width).step_by(size) {
+            let block_width = size.min(width - block_x);
+            let block_height = size.min(height - block_y);
+
+            for path in traversal_paths(&block_traversal, block_width, block_height) {
+                paths.push(
+                    path.into_iter()
+                        .map(|i| (block_y + i / block_width) * width + block_x + i % block_width)
+                        .collect(),
+                );
+            }
+        }
+    }
+
+    paths
+}
+
+/// decides which pixels form the intervals that get sorted
+#[derive(Clone)]
+pub struct Thresholds {
+    /// the lowest key selected, in the native range of the key
+    pub lower: u16,
+    /// the highest key selected, included
+    pub higher: u16,
+    /// key compared against the thresholds, the sort key is used when `None`
+    pub key: Option<SortBy>,
+    /// select the pixels outside of `lower..=higher` instead
+    pub invert: bool,
+    /// what the selection comes from, the key compared against the thresholds by default
+    pub source: IntervalSource,
+    /// applied in order to the selection before intervals are found
+    pub mask_operations: Vec<mask::MaskOperation>,
+    /// sort wherever the mask isn't zero and blend the result in by the mask value,
+    /// instead of cutting off at half
+    pub soft: bool,
+    /// the selection is cut down to it after the mask operations
+    pub region: Option<mask::Region>,
+}
+
+/// what decides which pixels are sortable
+#[derive(Clone, PartialEq)]
+pub enum IntervalSource {
+    /// pixels whose key is within the thresholds
+    Threshold,
+    /// pixels where a noise field is above its level, whatever their key
+    Noise(noise::NoiseMask),
+    /// everything between detected edges
+    Edges(convolution::EdgeMask),
+    /// the threshold key of another image's pixels is compared against the thresholds instead,
+    /// stretched over the sorted image when the sizes differ
+    Control(Arc<egui::ColorImage>),
+}
+
+impl IntervalSource {
+    /// the image whose pixels are compared against the thresholds
+    pub fn threshold_image<'a>(&'a self, image: &'a egui::ColorImage) -> &'a egui::ColorImage {
+        match self {
+            IntervalSource::Control(control) => control,
+            _ => image,
+        }
+    }
+}
+
+/// marks every pixel of the image that may be part of an interval
+pub(crate) fn selection_mask(
+    thresholds: &Thresholds,
+    image: &egui::ColorImage,
+    sorting_method: &SortBy,
+) -> Vec<bool> {
+    selection_values(thresholds, image, sorting_method)
+        .into_iter()
+        .map(|value| {
+            if thresholds.soft {
+                value > 0.0
+            } else {
+                value >= 0.5
+            }
+        })
+        .collect()
+}
+
+/// how much every pixel is selected, from 0 to 1
+pub(crate) fn selection_values(
+    thresholds: &Thresholds,
+    image: &egui::ColorImage,
+    sorting_method: &SortBy,
+) -> Vec<f32> {
+    let mask = match &thresholds.source {
+        IntervalSource::Threshold => {
+            let key = thresholds.key.as_ref().unwrap_or(sorting_method);
+            let accepted_range = thresholds.lower..=thresholds.higher;
+            let mut values = Vec::new();
+            key.keys(&image.pixels, &mut values);
+            values
+                .into_iter()
+                .map(|value| accepted_range.contains(&value))
+                .collect()
+        }
+        IntervalSource::Noise(noise) => noise.mask(image.width(), image.height()),
+        IntervalSource::Edges(edges) => edges.mask(image),
+        IntervalSource::Control(control) => {
+            let key = thresholds.key.as_ref().unwrap_or(sorting_method);
+            let accepted_range = thresholds.lower..=thresholds.higher;
+            let [width, height] = image.size;
+            let [control_width, control_height] = control.size;
+            (0..width * height)
+                .map(|i| {
+                    let x = i % width * control_width / width;
+                    let y = i / width * control_height / height;
+                    accepted_range.contains(&key.key(&control.pixels[y * control_width + x]))
+                })
+                .collect()
+        }
+    };
+
+    let [width, height] = image.size;
+    let values = mask
+        .into_iter()
+        .map(|selected: bool| {
+            if selected != thresholds.invert {
+                1.0
+            } else {
+                0.0
+            }
+        })
+        .collect();
+    let values = thresholds
+        .mask_operations
+        .iter()
+        .fold(values, |values, operation| {
+            operation.apply(values, width, height)
+        });
+    match &thresholds.region {
+        Some(region) => values
+            .into_iter()
+            .zip(region.values(width, height))
+            .map(|(value, inside)| value * inside)
+            .collect(),
+        None => values,
+    }
+}
+
+/// what is done with the pixels of every interval
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum IntervalOperation {
+    /// orders the pixels by the sort key
+    #[default]
+    Sort,
+    /// puts the pixels in a random order
+    Shuffle,
+    /// stretches a single pixel over the whole interval
+    Smear(SmearPixel),
+}
+
+/// which pixel `IntervalOperation::Smear` stretches
+#[derive(Clone, Copy, PartialEq)]
+pub enum SmearPixel {
+    /// the first pixel along the path
+    First,
+    /// the pixel with the highest luminance
+    Brightest,
+}
+
+/// post-processing of the intervals found by thresholding
+#[derive(Clone, Default)]
+pub struct IntervalOptions {
+    /// what's done with the pixels of every interval
+    pub operation: IntervalOperation,
+    /// intervals shorter than this are left unsorted
+    pub min_length: usize,
+    /// neighbouring intervals separated by fewer than this many rejected pixels are merged
+    pub gap_tolerance: usize,
+    /// longer intervals are split into chunks of at most this many pixels
+    pub max_length: Option<usize>,
+    /// split into chunks of random length up to `max_length` instead
+    pub random_chunks: bool,
+    /// chance of an interval being cut short at every pixel
+    pub split_probability: f64,
+    /// seed for the random choices above, so results are reproducible
+    pub seed: u64,
+    /// paths are circular, so an interval reaching the end continues from the start
+    pub wrap_around: bool,
+    /// moves every interval along its path by this many pixels after sorting
+    pub shift: isize,
+    /// shift by a random amount between `-shift` and `shift` instead
+    pub random_shift: bool,
+}
+
+pub(crate) fn process_intervals(
+    intervals: Vec<(usize, usize)>,
+    options: &IntervalOptions,
+    rng: &mut impl Rng,
+) -> Vec<(usize, usize)> {
+    let mut merged: Vec<(usize, usize)> = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start - last.1 < options.gap_tolerance => last.1 = end,
+            _ => merged.push((start, end)),
+        }
+    }
+
+    let mut split = Vec::with_capacity(merged.len());
+    for (start, end) in merged {
+        let mut chunk_start = start;
+        if options.split_probability > 0.0 {
+            for i in start + 1..end {
+                if rng.gen_bool(options.split_probability.min(1.0)) {
+                    split.push((chunk_start, i));
+                    chunk_start = i;
+                }
+            }
+        }
+        split.push((chunk_start, end));
+    }
+
+    let mut result = Vec::with_capacity(split.len());
+
+    for (start, end) in split {
+        match options.max_length {
+            Some(max_length) if max_length > 0 => {
+                let mut chunk_start = start;
+                while chunk_start < end {
+                    let length = if options.random_chunks {
+                        rng.gen_range(1..=max_length)
+                    } else {
+                        max_length
+                    };
+                    let chunk_end = (chunk_start + length).min(end);
+                    result.push((chunk_start, chunk_end));
+                    chunk_start = chunk_end;
+                }
+            }
+            _ => result.push((start, end)),
+        }
+    }
+
+    result.retain(|(start, end)| end - start >= options.min_length);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    fn process(intervals: Vec<(usize, usize)>, options: &IntervalOptions) -> Vec<(usize, usize)> {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(options.seed);
+        process_intervals(intervals, options, &mut rng)
+    }
+
+    #[test]
+    fn intervals_of_a_bitmap() {
+        assert_eq!(into_intervals(Vec::new()), []);
+        assert_eq!(into_intervals(vec![false; 4]), []);
+        assert_eq!(into_intervals(vec![true; 4]), [(0, 4)]);
+        // intervals touching either end
+        assert_eq!(
+            into_intervals(vec![true, false, false, true, true]),
+            [(0, 1), (3, 5)]
+        );
+        assert_eq!(
+            into_intervals(vec![false, true, false, true, false]),
+            [(1, 2), (3, 4)]
+        );
+    }
+
+    #[test]
+    fn gaps_shorter_than_the_tolerance_are_merged() {
+        let intervals = vec![(0, 2), (4, 6), (9, 10)];
+        let options = |gap_tolerance| IntervalOptions {
+            gap_tolerance,
+            ..Default::default()
+        };
+        assert_eq!(process(intervals.clone(), &options(0)), intervals);
+        assert_eq!(process(intervals.clone(), &options(2)), intervals);
+        assert_eq!(process(intervals.clone(), &options(3)), [(0, 6), (9, 10)]);
+        assert_eq!(process(intervals, &options(4)), [(0, 10)]);
+    }
+
+    #[test]
+    fn long_intervals_are_cut_and_short_ones_dropped() {
+        let options = IntervalOptions {
+            max_length: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            process(vec![(0, 7), (10, 12)], &options),
+            [(0, 3), (3, 6), (6, 7), (10, 12)]
+        );
+        let options = IntervalOptions {
+            min_length: 2,
+            ..options
+        };
+        assert_eq!(
+            process(vec![(0, 7), (10, 12)], &options),
+            [(0, 3), (3, 6), (10, 12)]
+        );
+    }
+
+    #[test]
+    fn random_chunks_cover_the_interval() {
+        let options = IntervalOptions {
+            max_length: Some(5),
+            random_chunks: true,
+            seed: 3,
+            ..Default::default()
+        };
+        let chunks = process(vec![(0, 100)], &options);
+        assert_eq!(chunks.first().map(|chunk| chunk.0), Some(0));
+        assert_eq!(chunks.last().map(|chunk| chunk.1), Some(100));
+        for (chunk, next) in chunks.iter().zip(&chunks[1..]) {
+            assert_eq!(chunk.1, next.0);
+        }
+        assert!(chunks
+            .iter()
+            .all(|(start, end)| (1..=5).contains(&(end - start))));
+    }
+
+    #[test]
+    fn split_probability_cuts_intervals() {
+        let options = |split_probability| IntervalOptions {
+            split_probability,
+            ..Default::default()
+        };
+        assert_eq!(process(vec![(2, 6)], &options(0.0)), [(2, 6)]);
+        assert_eq!(
+            process(vec![(2, 6)], &options(1.0)),
+            [(2, 3), (3, 4), (4, 5), (5, 6)]
+        );
+        // cut somewhere, but still covering the same pixels
+        let chunks = process(vec![(0, 1000)], &options(0.1));
+        assert!(chunks.len() > 1);
+        let covered: usize = chunks.iter().map(|(start, end)| end - start).sum();
+        assert_eq!(covered, 1000);
+    }
+}
@@ -0,0 +1,338 @@
+//! reading and writing images as `egui::ColorImage`, the image type of the whole crate
+use crate::exif::{self, read_exif, read_exif_file};
+use crate::hdr::{is_hdr_format, HdrImage};
+use crate::jpeg::{self, ChromaSubsampling};
+use crate::keys;
+use crate::webp;
+use image::codecs::png::{CompressionType, FilterType, PngEncoder};
+use image::{ColorType, ImageEncoder};
+use std::io::Read;
+use std::path::Path;
+use web_time::Instant;
+
+/// the file name of `path`, none when it has none like `/` or `..`
+pub fn basename(path: &str) -> Option<String> {
+    Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+}
+
+/// `pixels` as rgba bytes with straight alpha, the way image files keep them. colors are
+/// premultiplied by alpha in a `ColorImage`
+pub fn straight_rgba(pixels: &[egui::Color32]) -> Vec<u8> {
+    pixels
+        .iter()
+        .flat_map(|pixel| keys::straight(pixel).0)
+        .collect()
+}
+
+/// files with one of `extensions`, or any image extension when it's empty
+pub fn is_image_file(path: &Path, extensions: &[String]) -> bool {
+    path.is_file()
+        && path.extension().is_some_and(|extension| {
+            let extension = extension.to_string_lossy().to_lowercase();
+            if extensions.is_empty() {
+                IMAGE_EXTENSIONS.contains(&extension.as_str())
+            } else {
+                extensions
+                    .iter()
+                    .any(|e| e.trim_start_matches('.').eq_ignore_ascii_case(&extension))
+            }
+        })
+}
+
+/// `format`, or else the format matching the extension of `path`, standard output (`-`)
+/// defaults to png
+pub fn output_format(
+    path: &Path,
+    format: Option<image::ImageFormat>,
+) -> Result<image::ImageFormat, String> {
+    match format {
+        Some(format) => Ok(format),
+        None if path == Path::new("-") => Ok(image::ImageFormat::Png),
+        None => image::ImageFormat::from_path(path).map_err(|e| e.to_string()),
+    }
+}
+
+/// how hard pngs are compressed, smaller files take longer to write
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PngCompression {
+    /// the fastest to write and the biggest
+    #[default]
+    Fast,
+    /// the default of the png crate
+    Balanced,
+    /// the smallest and the slowest
+    Best,
+}
+
+/// the settings of the encoders, the defaults are the ones of the image crate
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodeOptions {
+    /// from 1 to 100
+    pub jpeg_quality: u8,
+    /// how many pixels of a jpeg share a color
+    pub chroma_subsampling: ChromaSubsampling,
+    /// how hard pngs are compressed
+    pub png_compression: PngCompression,
+    /// from 1 to 100 for lossy webps, none for lossless ones
+    pub webp_quality: Option<u8>,
+    /// from 1 to 100
+    pub avif_quality: u8,
+}
+
+impl Default for EncodeOptions {
+    fn default() -> EncodeOptions {
+        EncodeOptions {
+            jpeg_quality: 75,
+            chroma_subsampling: ChromaSubsampling::Full,
+            png_compression: PngCompression::Fast,
+            webp_quality: None,
+            avif_quality: 80,
+        }
+    }
+}
+
+impl PngCompression {
+    /// the level for the png crate, which writes pngs a band at a time
+    pub fn png(self) -> png::Compression {
+        match self {
+            PngCompression::Fast => png::Compression::Fast,
+            PngCompression::Balanced => png::Compression::Default,
+            PngCompression::Best => png::Compression::Best,
+        }
+    }
+}
+
+/// encodes the image in the `output_format` of `path` and `format`
+pub fn encode_image(
+    image: &egui::ColorImage,
+    path: &Path,
+    format: Option<image::ImageFormat>,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, String> {
+    let format = output_format(path, format)?;
+
+    let start = Instant::now();
+    // float formats get the colors as they are, from 0 to 1
+    if is_hdr_format(format) {
+        return HdrImage::from_color_image(image).encode(format);
+    }
+    let [width, height] = image.size;
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    let straight = || straight_rgba(&image.pixels);
+    match format {
+        // the jpeg encoder of the image crate keeps every color
+        image::ImageFormat::Jpeg if options.chroma_subsampling != ChromaSubsampling::Full => {
+            bytes = std::io::Cursor::new(jpeg::encode(
+                image,
+                options.jpeg_quality,
+                options.chroma_subsampling,
+            )?);
+        }
+        image::ImageFormat::Jpeg => {
+            image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, options.jpeg_quality)
+                .write_image(&straight(), width as u32, height as u32, ColorType::Rgba8)
+                .map_err(|e| e.to_string())?
+        }
+        image::ImageFormat::WebP => {
+            bytes = std::io::Cursor::new(match options.webp_quality {
+                Some(quality) => encode_lossy_webp(image, quality)?,
+                None => webp::encode_lossless(image)?,
+            })
+        }
+        image::ImageFormat::Avif => {
+            bytes = std::io::Cursor::new(encode_avif(image, options.avif_quality)?)
+        }
+        image::ImageFormat::Png => {
+            let compression = match options.png_compression {
+                PngCompression::Fast => CompressionType::Fast,
+                PngCompression::Balanced => CompressionType::Default,
+                PngCompression::Best => CompressionType::Best,
+            };
+            PngEncoder::new_with_quality(&mut bytes, compression, FilterType::Adaptive)
+                .write_image(&straight(), width as u32, height as u32, ColorType::Rgba8)
+                .map_err(|e| e.to_string())?
+        }
+        _ => {
+            let buffer = image::RgbaImage::from_raw(width as u32, height as u32, straight())
+                .ok_or("image buffer has the wrong size")?;
+            image::DynamicImage::ImageRgba8(buffer)
+                .write_to(&mut bytes, format)
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    log::debug!(
+        "encoded {} as {:?} in {:.2?}",
+        path.display(),
+        format,
+        start.elapsed()
+    );
+    Ok(bytes.into_inner())
+}
+
+/// a lossy webp written by libwebp, `quality` goes from 1 to 100
+#[cfg(not(target_arch = "wasm32"))]
+fn encode_lossy_webp(image: &egui::ColorImage, quality: u8) -> Result<Vec<u8>, String> {
+    let [width, height] = image.size;
+    let pixels = straight_rgba(&image.pixels);
+    libwebp::Encoder::from_rgba(&pixels, width as u32, height as u32)
+        .encode_simple(false, quality.clamp(1, 100) as f32)
+        .map(|webp| webp.to_vec())
+        .map_err(|e| format!("cannot write the webp: {:?}", e))
+}
+
+#[cfg(target_arch = "wasm32")]
+fn encode_lossy_webp(_image: &egui::ColorImage, _quality: u8) -> Result<Vec<u8>, String> {
+    Err("lossy webps cannot be written in the browser, only lossless ones".to_string())
+}
+
+/// how long rav1e searches for a smaller file, from 1 to 10. the slower speeds take minutes
+/// for a photo without its assembly
+const AVIF_SPEED: u8 = 7;
+
+/// an avif written by rav1e, `quality` goes from 1 to 100
+fn encode_avif(image: &egui::ColorImage, quality: u8) -> Result<Vec<u8>, String> {
+    let [width, height] = image.size;
+    let pixels: Vec<ravif::RGBA8> = image
+        .pixels
+        .iter()
+        .map(|pixel| {
+            let [red, green, blue, alpha] = keys::straight(pixel).0;
+            ravif::RGBA8::new(red, green, blue, alpha)
+        })
+        .collect();
+    ravif::Encoder::new()
+        .with_quality(quality.clamp(1, 100) as f32)
+        .with_speed(AVIF_SPEED)
+        .encode_rgba(ravif::Img::new(&pixels[..], width, height))
+        .map(|encoded| encoded.avif_file)
+        .map_err(|e| format!("cannot write the avif: {}", e))
+}
+
+/// adds a chunk to an encoded png right after its header, before the pixels
+pub(crate) fn insert_png_chunk(
+    bytes: &mut Vec<u8>,
+    kind: &[u8; 4],
+    data: &[u8],
+) -> Result<(), String> {
+    const HEADER_END: usize = 8 + 8 + 13 + 4;
+    if bytes.get(12..16) != Some(b"IHDR") {
+        return Err("the png has no header".to_string());
+    }
+
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    let mut chunk = Vec::with_capacity(data.len() + 12);
+    chunk.extend((data.len() as u32).to_be_bytes());
+    chunk.extend(kind);
+    chunk.extend(data);
+    chunk.extend(crc.finalize().to_be_bytes());
+    bytes.splice(HEADER_END..HEADER_END, chunk);
+    Ok(())
+}
+
+/// a jpeg segment of `marker`, `FF E1` being `0xe1`
+pub(crate) fn jpeg_segment(marker: u8, data: &[u8]) -> Result<Vec<u8>, String> {
+    let length = u16::try_from(data.len() + 2).map_err(|_| "too big for a jpeg segment")?;
+    Ok([&[0xff, marker], &length.to_be_bytes()[..], data].concat())
+}
+
+/// adds segments to an encoded jpeg after its start and its JFIF segment, which has to come
+/// first
+pub(crate) fn insert_jpeg_segments(bytes: &mut Vec<u8>, segments: Vec<u8>) -> Result<(), String> {
+    if bytes.get(..2) != Some(&[0xff, 0xd8]) {
+        return Err("the jpeg has no start marker".to_string());
+    }
+    let mut position = 2;
+    if bytes.get(2..4) == Some(&[0xff, 0xe0]) {
+        position += 2 + u16::from_be_bytes([bytes[4], bytes[5]]) as usize;
+    }
+    bytes.splice(position..position, segments);
+    Ok(())
+}
+
+/// writes next to the original first and renames over it, so a failed save never leaves a
+/// half written image behind
+pub fn replace_image(
+    image: &egui::ColorImage,
+    path: &Path,
+    backup_path: Option<&Path>,
+    format: Option<image::ImageFormat>,
+    options: &EncodeOptions,
+) -> Result<(), String> {
+    let bytes = encode_image(image, path, format, options)?;
+    replace_file(path, &bytes, backup_path)
+}
+
+/// writes `bytes` over the file at `path` through a temporary file, copying the original to
+/// `backup_path` first when given
+pub fn replace_file(path: &Path, bytes: &[u8], backup_path: Option<&Path>) -> Result<(), String> {
+    let temporary_path = temporary_path(path);
+
+    if let Err(e) = std::fs::write(&temporary_path, bytes) {
+        let _ = std::fs::remove_file(&temporary_path);
+        return Err(e.to_string());
+    }
+    if let Some(backup_path) = backup_path {
+        std::fs::copy(path, backup_path).map_err(|e| e.to_string())?;
+    }
+    std::fs::rename(&temporary_path, path).map_err(|e| e.to_string())
+}
+
+/// a hidden file next to `path`, written first so `path` is only replaced once the new image
+/// is complete
+pub fn temporary_path(path: &Path) -> std::path::PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!(".{}.psorter-tmp", file_name))
+}
+
+/// the format is guessed from the first bytes, the extension of `name` is the fallback for
+/// formats without a signature
+pub fn decode_image(name: &str, bytes: &[u8]) -> Result<egui::ColorImage, image::ImageError> {
+    let mut reader = image::io::Reader::new(std::io::Cursor::new(bytes)).with_guessed_format()?;
+    if reader.format().is_none() {
+        reader.set_format(image::ImageFormat::from_path(name)?);
+    }
+    Ok(upright(color_image(reader.decode()?), read_exif(bytes)))
+}
+
+/// `-` reads standard input, guessing the format from the first bytes. images are turned
+/// upright as their EXIF orientation says
+pub fn load_image_from_path(path: &str) -> Result<egui::ColorImage, image::ImageError> {
+    let start = Instant::now();
+    let image = if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut bytes)?;
+        upright(
+            color_image(image::load_from_memory(&bytes)?),
+            read_exif(&bytes[..]),
+        )
+    } else {
+        let image = image::io::Reader::open(path)?.decode()?;
+        upright(color_image(image), read_exif_file(path))
+    };
+    log::debug!("decoded {} in {:.2?}", path, start.elapsed());
+    Ok(image)
+}
+
+fn upright(image: egui::ColorImage, exif: Option<Vec<u8>>) -> egui::ColorImage {
+    match exif.as_deref().and_then(exif::orientation) {
+        Some(orientation) => exif::upright(image, orientation),
+        None => image,
+    }
+}
+
+/// converts a decoded image to rgba
+pub fn color_image(image: image::DynamicImage) -> egui::ColorImage {
+    let size = [image.width() as _, image.height() as _];
+    let image_buffer = image.to_rgba8();
+    let pixels = image_buffer.as_flat_samples();
+    egui::ColorImage::from_rgba_unmultiplied(size, pixels.as_slice())
+}
+
+/// the extensions of the images found in directories and offered by file dialogs
+pub const IMAGE_EXTENSIONS: [&str; 11] = [
+    "png", "jpg", "jpeg", "gif", "bmp", "ico", "tiff", "webp", "tga", "exr", "hdr",
+];
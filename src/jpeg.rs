@@ -0,0 +1,353 @@
+//! a baseline jpeg encoder for chroma subsampling, which the encoder of the image crate
+//! doesn't do. it takes the same tables, so 4:4:4 images come out alike
+use crate::io::jpeg_segment;
+use crate::keys;
+
+/// how much color jpegs keep, the brightness of every pixel is always kept
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// 4:4:4, a color for every pixel
+    #[default]
+    Full,
+    /// 4:2:2, a color for every two pixels of a row
+    Half,
+    /// 4:2:0, a color for every square of four pixels
+    Quarter,
+}
+
+impl ChromaSubsampling {
+    /// how many pixels share a color across and down
+    fn factors(self) -> (usize, usize) {
+        match self {
+            ChromaSubsampling::Full => (1, 1),
+            ChromaSubsampling::Half => (2, 1),
+            ChromaSubsampling::Quarter => (2, 2),
+        }
+    }
+}
+
+/// table K.1 of the standard, for the brightness
+#[rustfmt::skip]
+const LUMA_QUANTIZATION: [u8; 64] = [
+    16, 11, 10, 16,  24,  40,  51,  61,
+    12, 12, 14, 19,  26,  58,  60,  55,
+    14, 13, 16, 24,  40,  57,  69,  56,
+    14, 17, 22, 29,  51,  87,  80,  62,
+    18, 22, 37, 56,  68, 109, 103,  77,
+    24, 35, 55, 64,  81, 104, 113,  92,
+    49, 64, 78, 87, 103, 121, 120, 101,
+    72, 92, 95, 98, 112, 100, 103,  99,
+];
+
+/// table K.2, for the colors
+#[rustfmt::skip]
+const CHROMA_QUANTIZATION: [u8; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// where the coefficients of a block are, in the zigzag order they're written in
+#[rustfmt::skip]
+const ZIGZAG: [usize; 64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// a huffman table as it's written: how many codes have every length from 1 to 16 and the
+/// values they stand for
+struct HuffmanTable {
+    lengths: [u8; 16],
+    values: &'static [u8],
+}
+
+/// tables K.3 to K.6
+const LUMA_DC: HuffmanTable = HuffmanTable {
+    lengths: [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0],
+    values: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+const CHROMA_DC: HuffmanTable = HuffmanTable {
+    lengths: [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0],
+    values: &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+};
+const LUMA_AC: HuffmanTable = HuffmanTable {
+    lengths: [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d],
+    values: &[
+        0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61,
+        0x07, 0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08, 0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52,
+        0xd1, 0xf0, 0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16, 0x17, 0x18, 0x19, 0x1a, 0x25,
+        0x26, 0x27, 0x28, 0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44, 0x45,
+        0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63, 0x64,
+        0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a, 0x83,
+        0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99,
+        0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6,
+        0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3,
+        0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8,
+        0xe9, 0xea, 0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ],
+};
+const CHROMA_AC: HuffmanTable = HuffmanTable {
+    lengths: [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77],
+    values: &[
+        0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61,
+        0x71, 0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33,
+        0x52, 0xf0, 0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34, 0xe1, 0x25, 0xf1, 0x17, 0x18,
+        0x19, 0x1a, 0x26, 0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3a, 0x43, 0x44,
+        0x45, 0x46, 0x47, 0x48, 0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5a, 0x63,
+        0x64, 0x65, 0x66, 0x67, 0x68, 0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7a,
+        0x82, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97,
+        0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4,
+        0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca,
+        0xd2, 0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7,
+        0xe8, 0xe9, 0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8, 0xf9, 0xfa,
+    ],
+};
+
+impl HuffmanTable {
+    /// the code and its length for every value
+    fn codes(&self) -> [(u16, u8); 256] {
+        let mut codes = [(0, 0); 256];
+        let mut values = self.values.iter();
+        let mut code = 0u16;
+        for (length, &count) in self.lengths.iter().enumerate() {
+            for &value in values.by_ref().take(count as usize) {
+                codes[value as usize] = (code, length as u8 + 1);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        codes
+    }
+}
+
+/// bits packed into bytes, with a zero after every 0xff so it isn't read as a marker
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u8,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u16, length: u8) {
+        self.bits = (self.bits << length) | bits as u32;
+        self.count += length;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.bits >> self.count) as u8;
+            self.bytes.push(byte);
+            if byte == 0xff {
+                self.bytes.push(0);
+            }
+        }
+        self.bits &= (1 << self.count) - 1;
+    }
+
+    /// fills the last byte with ones
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.write((1 << (8 - self.count)) - 1, 8 - self.count);
+        }
+        self.bytes
+    }
+}
+
+/// the quantization table of `quality`, scaled the way libjpeg and the image crate do it
+fn quantization(table: &[u8; 64], quality: u8) -> [u8; 64] {
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    };
+    table.map(|value| ((value as u32 * scale + 50) / 100).clamp(1, 255) as u8)
+}
+
+/// the number of bits of `value` and the bits written for it, negative values are written
+/// minus one
+fn magnitude(value: i32) -> (u8, u16) {
+    let length = 32 - value.unsigned_abs().leading_zeros();
+    let bits = if value < 0 { value - 1 } else { value };
+    (length as u8, (bits & ((1 << length) - 1)) as u16)
+}
+
+/// one component of the image: its quantization and huffman tables and the last DC value
+struct Component {
+    quantization: [u8; 64],
+    dc: [(u16, u8); 256],
+    ac: [(u16, u8); 256],
+    previous_dc: i32,
+}
+
+impl Component {
+    /// transforms, quantizes and writes an 8x8 block of samples centered on 0
+    fn write_block(&mut self, block: &[f32; 64], cosines: &[[f32; 8]; 8], writer: &mut BitWriter) {
+        // the discrete cosine transform of the rows, then of the columns
+        let mut rows = [0.0; 64];
+        for y in 0..8 {
+            for u in 0..8 {
+                rows[y * 8 + u] = (0..8).map(|x| block[y * 8 + x] * cosines[u][x]).sum();
+            }
+        }
+        let mut coefficients = [0i32; 64];
+        for (i, &position) in ZIGZAG.iter().enumerate() {
+            let (v, u) = (position / 8, position % 8);
+            let coefficient: f32 = (0..8).map(|y| rows[y * 8 + u] * cosines[v][y]).sum();
+            let quantized = (coefficient / self.quantization[position] as f32).round() as i32;
+            // the standard tables hold up to 11 bits for DC and 10 for AC values
+            let limit = if i == 0 { 2047 } else { 1023 };
+            coefficients[i] = quantized.clamp(-limit, limit);
+        }
+
+        let (length, bits) = magnitude(coefficients[0] - self.previous_dc);
+        self.previous_dc = coefficients[0];
+        let (code, code_length) = self.dc[length as usize];
+        writer.write(code, code_length);
+        writer.write(bits, length);
+
+        let mut zeros = 0;
+        for &coefficient in &coefficients[1..] {
+            if coefficient == 0 {
+                zeros += 1;
+                continue;
+            }
+            while zeros >= 16 {
+                let (code, code_length) = self.ac[0xf0];
+                writer.write(code, code_length);
+                zeros -= 16;
+            }
+            let (length, bits) = magnitude(coefficient);
+            let (code, code_length) = self.ac[(zeros << 4) | length as usize];
+            writer.write(code, code_length);
+            writer.write(bits, length);
+            zeros = 0;
+        }
+        if zeros > 0 {
+            let (code, code_length) = self.ac[0];
+            writer.write(code, code_length);
+        }
+    }
+}
+
+/// encodes `image` as a jpeg of `quality`, from 1 to 100, dropping its alpha like the image
+/// crate does
+pub fn encode(
+    image: &egui::ColorImage,
+    quality: u8,
+    subsampling: ChromaSubsampling,
+) -> Result<Vec<u8>, String> {
+    let [width, height] = image.size;
+    if width == 0 || height == 0 || width > u16::MAX as usize || height > u16::MAX as usize {
+        return Err(format!(
+            "jpegs are 1 to {} pixels wide and high, not {}x{}",
+            u16::MAX,
+            width,
+            height
+        ));
+    }
+    let (across, down) = subsampling.factors();
+    let luma_quantization = quantization(&LUMA_QUANTIZATION, quality);
+    let chroma_quantization = quantization(&CHROMA_QUANTIZATION, quality);
+
+    let mut bytes = vec![0xff, 0xd8];
+    // JFIF 1.1 without a pixel density or a thumbnail
+    bytes.extend(jpeg_segment(0xe0, b"JFIF\0\x01\x01\0\0\x01\0\x01\0\0")?);
+    for (id, table) in [luma_quantization, chroma_quantization].iter().enumerate() {
+        let data: Vec<u8> = std::iter::once(id as u8)
+            .chain(ZIGZAG.iter().map(|&position| table[position]))
+            .collect();
+        bytes.extend(jpeg_segment(0xdb, &data)?);
+    }
+    let mut frame = vec![8];
+    frame.extend((height as u16).to_be_bytes());
+    frame.extend((width as u16).to_be_bytes());
+    frame.extend([3, 1, (across << 4 | down) as u8, 0, 2, 0x11, 1, 3, 0x11, 1]);
+    bytes.extend(jpeg_segment(0xc0, &frame)?);
+    for (class_and_id, table) in [
+        (0x00, &LUMA_DC),
+        (0x10, &LUMA_AC),
+        (0x01, &CHROMA_DC),
+        (0x11, &CHROMA_AC),
+    ] {
+        let data = [&[class_and_id], &table.lengths[..], table.values].concat();
+        bytes.extend(jpeg_segment(0xc4, &data)?);
+    }
+    bytes.extend(jpeg_segment(
+        0xda,
+        &[3, 1, 0x00, 2, 0x11, 3, 0x11, 0, 63, 0],
+    )?);
+
+    let mut cosines = [[0.0; 8]; 8];
+    for (u, row) in cosines.iter_mut().enumerate() {
+        let scale = if u == 0 { 0.5_f32.sqrt() } else { 1.0 };
+        for (x, cosine) in row.iter_mut().enumerate() {
+            let angle = (2 * x + 1) as f32 * u as f32 * std::f32::consts::PI / 16.0;
+            *cosine = scale / 2.0 * angle.cos();
+        }
+    }
+
+    // brightness, blue and red difference, centered on 0. pixels past the edges repeat the
+    // last row and column
+    let ycbcr = |x: usize, y: usize| {
+        let pixel = keys::straight(&image.pixels[y.min(height - 1) * width + x.min(width - 1)]);
+        let [red, green, blue] = [pixel[0], pixel[1], pixel[2]].map(|channel| channel as f32);
+        [
+            0.299 * red + 0.587 * green + 0.114 * blue - 128.0,
+            -0.168_736 * red - 0.331_264 * green + 0.5 * blue,
+            0.5 * red - 0.418_688 * green - 0.081_312 * blue,
+        ]
+    };
+
+    let mut components = [
+        (luma_quantization, LUMA_DC.codes(), LUMA_AC.codes()),
+        (chroma_quantization, CHROMA_DC.codes(), CHROMA_AC.codes()),
+        (chroma_quantization, CHROMA_DC.codes(), CHROMA_AC.codes()),
+    ]
+    .map(|(quantization, dc, ac)| Component {
+        quantization,
+        dc,
+        ac,
+        previous_dc: 0,
+    });
+    let mut writer = BitWriter::default();
+    let (unit_width, unit_height) = (8 * across, 8 * down);
+    for unit_y in (0..height).step_by(unit_height) {
+        for unit_x in (0..width).step_by(unit_width) {
+            let mut block = [0.0; 64];
+            for block_y in (0..unit_height).step_by(8) {
+                for block_x in (0..unit_width).step_by(8) {
+                    for (i, sample) in block.iter_mut().enumerate() {
+                        *sample = ycbcr(unit_x + block_x + i % 8, unit_y + block_y + i / 8)[0];
+                    }
+                    components[0].write_block(&block, &cosines, &mut writer);
+                }
+            }
+            // the colors of the pixels sharing every sample, averaged
+            for (channel, component) in components.iter_mut().enumerate().skip(1) {
+                for (i, sample) in block.iter_mut().enumerate() {
+                    let (x, y) = (unit_x + i % 8 * across, unit_y + i / 8 * down);
+                    let sum: f32 = (0..down)
+                        .flat_map(|dy| (0..across).map(move |dx| (x + dx, y + dy)))
+                        .map(|(x, y)| ycbcr(x, y)[channel])
+                        .sum();
+                    *sample = sum / (across * down) as f32;
+                }
+                component.write_block(&block, &cosines, &mut writer);
+            }
+        }
+    }
+    bytes.extend(writer.finish());
+    bytes.extend([0xff, 0xd9]);
+    Ok(bytes)
+}
@@ -0,0 +1,486 @@
+//! the values pixels are sorted and selected by
+use crate::{script, simd};
+use image::Rgba;
+use std::sync::Arc;
+
+/// a key pixels can be sorted by. the built-in keys of `SortBy` are ones, and keys of other
+/// crates are turned into one the sorter takes by `SortBy::custom`
+pub trait SortKey: Send + Sync {
+    /// of a pixel with straight alpha, from 0 to `max_value`
+    fn key(&self, pixel: Rgba<u8>) -> u16;
+
+    /// the highest value of the key, thresholds go from 0 to it
+    fn max_value(&self) -> u16;
+}
+
+/// the color of `pixel` with straight alpha, the way keys take it
+pub fn straight(pixel: &egui::Color32) -> Rgba<u8> {
+    match pixel.a() {
+        255 => Rgba([pixel.r(), pixel.g(), pixel.b(), 255]),
+        _ => Rgba(pixel.to_srgba_unmultiplied()),
+    }
+}
+
+/// a key pixels are sorted by, see `parse_sort_by` for the names of the command line
+#[derive(Clone)]
+pub enum SortBy {
+    /// plain average of the channels
+    Luminance,
+    /// the angle of the color wheel in degrees, 0 for grays
+    Hue,
+    /// 0 for grays, otherwise the highest halfway between black and white
+    Saturation,
+    /// a single channel
+    Red,
+    /// a single channel
+    Green,
+    /// a single channel
+    Blue,
+    /// a single channel, 0 is transparent
+    Alpha,
+    /// HSV value, the brightest channel
+    Value,
+    /// HSL lightness, the middle between the brightest and the darkest channel
+    Lightness,
+    /// perceptually weighted Rec.709 luma
+    Luma,
+    /// euclidean distance to a reference color in RGB space
+    Distance(egui::Color32),
+    /// a rhai expression of the channels, see `script`
+    Script(script::KeyScript),
+    /// a weighted sum of the other keys
+    Weighted(KeyExpression),
+    /// a key of your own, see `SortBy::custom`
+    Custom(Arc<dyn SortKey>),
+}
+
+impl SortBy {
+    /// sorts by a key implemented outside of psorter
+    pub fn custom(key: impl SortKey + 'static) -> SortBy {
+        SortBy::Custom(Arc::new(key))
+    }
+
+    /// of a pixel of an image, from 0 to `threshold_upper_boundary`
+    pub fn key(&self, pixel: &egui::Color32) -> u16 {
+        SortKey::key(self, straight(pixel))
+    }
+
+    /// replaces `keys` with the keys of many pixels like those of a row, luminance, hue and
+    /// saturation are vectorized where the target allows it
+    pub fn keys(&self, pixels: &[egui::Color32], keys: &mut Vec<u16>) {
+        keys.clear();
+        match self {
+            SortBy::Luminance => simd::luminance(pixels, keys),
+            SortBy::Hue => simd::hue(pixels, keys),
+            SortBy::Saturation => simd::saturation(pixels, keys),
+            _ => keys.extend(pixels.iter().map(|pixel| SortBy::key(self, pixel))),
+        }
+    }
+
+    /// the name the command line takes, `x` for scripts. keys of other crates are `custom`,
+    /// which it can't read back
+    pub fn name(&self) -> String {
+        match self {
+            SortBy::Luminance => "l".to_string(),
+            SortBy::Hue => "h".to_string(),
+            SortBy::Saturation => "s".to_string(),
+            SortBy::Red => "r".to_string(),
+            SortBy::Green => "g".to_string(),
+            SortBy::Blue => "b".to_string(),
+            SortBy::Alpha => "a".to_string(),
+            SortBy::Value => "v".to_string(),
+            SortBy::Lightness => "lightness".to_string(),
+            SortBy::Luma => "y".to_string(),
+            SortBy::Distance(_) => "d".to_string(),
+            SortBy::Script(_) => "x".to_string(),
+            SortBy::Custom(_) => "custom".to_string(),
+            SortBy::Weighted(expression) => expression
+                .terms
+                .iter()
+                .map(|(weight, key)| format!("{}*{}", weight, key.name()))
+                .collect::<Vec<_>>()
+                .join("+"),
+        }
+    }
+
+    /// whether the key of a gray pixel is its level, which is all the planes of per channel
+    /// sorting have to tell their pixels apart
+    pub fn is_gray_level(&self) -> bool {
+        match self {
+            SortBy::Luminance
+            | SortBy::Red
+            | SortBy::Green
+            | SortBy::Blue
+            | SortBy::Value
+            | SortBy::Lightness
+            | SortBy::Luma => true,
+            SortBy::Weighted(expression) => {
+                expression.terms.iter().all(|(_, key)| key.is_gray_level())
+            }
+            _ => false,
+        }
+    }
+
+    /// the color distances are measured to, none for other keys
+    pub fn reference_color(&self) -> Option<egui::Color32> {
+        match self {
+            SortBy::Distance(reference) => Some(*reference),
+            SortBy::Weighted(expression) => expression
+                .terms
+                .iter()
+                .find_map(|(_, key)| key.reference_color()),
+            _ => None,
+        }
+    }
+
+    /// changes the color distances are measured to, keys without one are left as they are
+    pub fn set_reference_color(&mut self, color: egui::Color32) {
+        match self {
+            SortBy::Distance(reference) => *reference = color,
+            SortBy::Weighted(expression) => {
+                for (_, key) in &mut expression.terms {
+                    key.set_reference_color(color);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// a weighted sum of keys like `0.7*l+0.3*s`, every key is scaled to 0..=255 before weighting
+#[derive(Clone)]
+pub struct KeyExpression {
+    /// weights and their keys, weights are never negative
+    pub terms: Vec<(f32, SortBy)>,
+}
+
+impl KeyExpression {
+    /// parses a sum like `0.7*l+0.3*s`, weights are 1 when left out and sums can't be nested
+    pub fn parse(arg: &str) -> Option<KeyExpression> {
+        let mut terms = Vec::new();
+        for term in arg.split('+') {
+            let (weight, name) = match term.split_once('*') {
+                Some((weight, name)) => (weight.trim().parse::<f32>().ok()?, name.trim()),
+                None => (1.0, term.trim()),
+            };
+            match parse_sort_by(name)? {
+                SortBy::Weighted(_) => return None,
+                key if weight >= 0.0 => terms.push((weight, key)),
+                _ => return None,
+            }
+        }
+
+        Some(KeyExpression { terms })
+    }
+
+    /// the weighted average of the keys of `pixel`, from 0 to 255
+    pub fn key(&self, pixel: Rgba<u8>) -> u16 {
+        let total_weight: f32 = self.terms.iter().map(|(weight, _)| weight).sum();
+        if total_weight <= 0.0 {
+            return 0;
+        }
+
+        let sum: f32 = self
+            .terms
+            .iter()
+            .map(|(weight, key)| {
+                weight * SortKey::key(key, pixel) as f32 * 255.0 / key.max_value() as f32
+            })
+            .sum();
+        (sum / total_weight).round() as u16
+    }
+}
+
+/// `l`, `hue`, `0.7*l+0.3*s`... distances are to black until a reference color is set
+pub fn parse_sort_by(arg: &str) -> Option<SortBy> {
+    match arg {
+        "l" | "luminance" => Some(SortBy::Luminance),
+        "h" | "hue" => Some(SortBy::Hue),
+        "s" | "saturation" => Some(SortBy::Saturation),
+        "r" | "red" => Some(SortBy::Red),
+        "g" | "green" => Some(SortBy::Green),
+        "b" | "blue" => Some(SortBy::Blue),
+        "a" | "alpha" => Some(SortBy::Alpha),
+        "v" | "value" => Some(SortBy::Value),
+        "lightness" => Some(SortBy::Lightness),
+        "y" | "luma" => Some(SortBy::Luma),
+        "d" | "distance" => Some(SortBy::Distance(egui::Color32::BLACK)),
+        _ if arg.contains(['+', '*']) => KeyExpression::parse(arg).map(SortBy::Weighted),
+        _ => None,
+    }
+}
+
+/// the highest value of a key, thresholds go from 0 to it
+pub fn threshold_upper_boundary(method: &SortBy) -> u16 {
+    method.max_value()
+}
+
+impl SortKey for SortBy {
+    fn key(&self, pixel: Rgba<u8>) -> u16 {
+        match self {
+            SortBy::Hue => hue(pixel),
+            SortBy::Saturation => saturation(pixel),
+            SortBy::Luminance => luminance(pixel),
+            SortBy::Red => red(pixel),
+            SortBy::Green => green(pixel),
+            SortBy::Blue => blue(pixel),
+            SortBy::Alpha => alpha(pixel),
+            SortBy::Value => value(pixel),
+            SortBy::Lightness => lightness(pixel),
+            SortBy::Luma => luma(pixel),
+            SortBy::Distance(reference) => distance(pixel, reference),
+            SortBy::Weighted(expression) => expression.key(pixel),
+            SortBy::Script(script) => script.key(pixel),
+            SortBy::Custom(key) => key.key(pixel),
+        }
+    }
+
+    fn max_value(&self) -> u16 {
+        match self {
+            SortBy::Luminance
+            | SortBy::Saturation
+            | SortBy::Red
+            | SortBy::Green
+            | SortBy::Blue
+            | SortBy::Alpha
+            | SortBy::Value
+            | SortBy::Lightness
+            | SortBy::Luma
+            | SortBy::Weighted(_)
+            | SortBy::Script(_) => 255,
+            SortBy::Hue => 360,
+            // the diagonal of the RGB cube
+            SortBy::Distance(_) => 442,
+            SortBy::Custom(key) => key.max_value(),
+        }
+    }
+}
+
+/// `percent` of the way to `upper_boundary`, the native range of a key
+pub fn threshold_from_percent(percent: f32, upper_boundary: u16) -> u16 {
+    (percent.clamp(0.0, 100.0) / 100.0 * upper_boundary as f32).round() as u16
+}
+
+/// parses thresholds either in the key's native range or as a percentage like `40%`
+pub fn parse_threshold(arg: &str, upper_boundary: u16) -> Option<u16> {
+    match arg.strip_suffix('%') {
+        Some(percent) => {
+            let percent = percent.parse::<f32>().ok()?;
+            (0.0..=100.0)
+                .contains(&percent)
+                .then(|| threshold_from_percent(percent, upper_boundary))
+        }
+        None => arg.parse::<u16>().ok(),
+    }
+}
+
+/// how many pixels have each key value, indexed by the value
+pub fn key_histogram(image: &egui::ColorImage, key: &SortBy) -> Vec<u64> {
+    let mut histogram = vec![0; threshold_upper_boundary(key) as usize + 1];
+    let mut values = Vec::new();
+    key.keys(&image.pixels, &mut values);
+    for value in values {
+        let value = (value as usize).min(histogram.len() - 1);
+        histogram[value] += 1;
+    }
+    histogram
+}
+
+/// picks the thresholds for every image from the histogram of its keys
+#[derive(Clone, Copy)]
+pub enum AutoThreshold {
+    /// select the brighter class of Otsu's two-class split
+    Otsu,
+    /// clip the given percentage from both ends of the histogram
+    Percentile(f32),
+}
+
+impl AutoThreshold {
+    /// parses `otsu`, `percentile` or `percentile:N`, N is 10 when left out
+    pub fn parse(arg: &str) -> Option<AutoThreshold> {
+        match arg.split_once(':') {
+            None if arg == "otsu" => Some(AutoThreshold::Otsu),
+            None if arg == "percentile" => Some(AutoThreshold::Percentile(10.0)),
+            Some(("percentile", percent)) => {
+                let percent = percent.trim_end_matches('%').parse::<f32>().ok()?;
+                (0.0..50.0)
+                    .contains(&percent)
+                    .then_some(AutoThreshold::Percentile(percent))
+            }
+            _ => None,
+        }
+    }
+
+    /// the lower and higher thresholds for a histogram of `key_histogram`
+    pub fn thresholds(&self, histogram: &[u64]) -> (u16, u16) {
+        let upper_boundary = (histogram.len() - 1) as u16;
+        let total: u64 = histogram.iter().sum();
+        if total == 0 {
+            return (0, upper_boundary);
+        }
+
+        match self {
+            AutoThreshold::Otsu => {
+                let total_sum: f64 = histogram
+                    .iter()
+                    .enumerate()
+                    .map(|(value, &count)| value as f64 * count as f64)
+                    .sum();
+
+                let mut best = (0.0, 0);
+                let mut background_weight = 0.0;
+                let mut background_sum = 0.0;
+                for (value, &count) in histogram.iter().enumerate() {
+                    background_weight += count as f64;
+                    background_sum += value as f64 * count as f64;
+                    let foreground_weight = total as f64 - background_weight;
+                    if background_weight == 0.0 || foreground_weight == 0.0 {
+                        continue;
+                    }
+
+                    let background_mean = background_sum / background_weight;
+                    let foreground_mean = (total_sum - background_sum) / foreground_weight;
+                    let variance = background_weight
+                        * foreground_weight
+                        * (background_mean - foreground_mean).powi(2);
+                    if variance > best.0 {
+                        best = (variance, value);
+                    }
+                }
+
+                ((best.1 as u16 + 1).min(upper_boundary), upper_boundary)
+            }
+            AutoThreshold::Percentile(percent) => {
+                let clipped = (total as f64 * *percent as f64 / 100.0) as u64;
+                let mut seen = 0;
+                let mut lower = 0;
+                let mut higher = upper_boundary;
+                for (value, &count) in histogram.iter().enumerate() {
+                    if seen <= clipped {
+                        lower = value as u16;
+                    }
+                    seen += count;
+                    if seen >= total - clipped {
+                        higher = value as u16;
+                        break;
+                    }
+                }
+                (lower.min(higher), higher)
+            }
+        }
+    }
+}
+
+/// parses colors in the form `#rrggbb`
+pub fn parse_color(arg: &str) -> Option<egui::Color32> {
+    let hex = arg.strip_prefix('#').unwrap_or(arg);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+    Some(egui::Color32::from_rgb(
+        channel(0)?,
+        channel(2)?,
+        channel(4)?,
+    ))
+}
+
+// the keys of single pixels, with straight alpha
+
+/// `SortBy::Red`
+pub fn red(pixel: Rgba<u8>) -> u16 {
+    pixel[0] as u16
+}
+
+/// `SortBy::Green`
+pub fn green(pixel: Rgba<u8>) -> u16 {
+    pixel[1] as u16
+}
+
+/// `SortBy::Blue`
+pub fn blue(pixel: Rgba<u8>) -> u16 {
+    pixel[2] as u16
+}
+
+/// `SortBy::Alpha`
+pub fn alpha(pixel: Rgba<u8>) -> u16 {
+    pixel[3] as u16
+}
+
+/// `SortBy::Value`
+pub fn value(pixel: Rgba<u8>) -> u16 {
+    pixel[0].max(pixel[1]).max(pixel[2]) as u16
+}
+
+/// `SortBy::Lightness`
+pub fn lightness(pixel: Rgba<u8>) -> u16 {
+    let max = pixel[0].max(pixel[1]).max(pixel[2]) as u16;
+    let min = pixel[0].min(pixel[1]).min(pixel[2]) as u16;
+    (max + min) / 2
+}
+
+/// `SortBy::Distance`, from 0 to 442
+pub fn distance(pixel: Rgba<u8>, reference: &egui::Color32) -> u16 {
+    let difference = |a: u8, b: u8| (a as f32 - b as f32).powi(2);
+    (difference(pixel[0], reference.r())
+        + difference(pixel[1], reference.g())
+        + difference(pixel[2], reference.b()))
+    .sqrt()
+    .round() as u16
+}
+
+/// `SortBy::Luma`
+pub fn luma(pixel: Rgba<u8>) -> u16 {
+    (0.2126 * pixel[0] as f32 + 0.7152 * pixel[1] as f32 + 0.0722 * pixel[2] as f32).round() as u16
+}
+
+/// `SortBy::Luminance`
+pub fn luminance(pixel: Rgba<u8>) -> u16 {
+    ((pixel[0] as u16) + (pixel[1] as u16) + (pixel[2] as u16)) / 3
+}
+
+/// `SortBy::Hue`, from 0 to 359
+pub fn hue(pixel: Rgba<u8>) -> u16 {
+    let red = pixel[0] as f32;
+    let green = pixel[1] as f32;
+    let blue = pixel[2] as f32;
+
+    let min = blue.min(red.min(green));
+    let max = blue.max(red.max(green));
+
+    if max == min {
+        return 0;
+    }
+
+    let hue: f32 = if max == red {
+        (green - blue) / (max - min)
+    } else if max == green {
+        2.0 + (blue - red) / (max - min)
+    } else if max == blue {
+        4.0 + (red - green) / (max - min)
+    } else {
+        panic!("how?");
+    } * 60.0;
+
+    (if hue < 0.0 { hue + 360.0 } else { hue }) as u16
+}
+
+/// `SortBy::Saturation`
+pub fn saturation(pixel: Rgba<u8>) -> u16 {
+    let red = pixel[0] as f32 / 255.0;
+    let green = pixel[1] as f32 / 255.0;
+    let blue = pixel[2] as f32 / 255.0;
+
+    let min = blue.min(red.min(green));
+    let max = blue.max(red.max(green));
+
+    if max == min {
+        return 0;
+    }
+
+    let luminance = (max + min) / 2.0;
+    let saturation = 1.0 - ((2.0 * luminance) - 1.0).abs();
+
+    (saturation * 255.0) as u16
+}
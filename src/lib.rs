@@ -0,0 +1,27 @@
+//! pixel sorting: the pixels of an image are walked along paths, split into intervals by
+//! their key and every interval is sorted. the command line and the gui of `psorter` are
+//! built on top of this
+pub mod animation;
+pub mod bench;
+pub mod convolution;
+pub mod exif;
+pub mod hdr;
+pub mod icc;
+pub mod intervals;
+pub mod io;
+pub mod jpeg;
+pub mod keys;
+pub mod mask;
+pub mod noise;
+pub mod script;
+mod simd;
+pub mod sort;
+pub mod stream;
+pub mod svg;
+pub mod sweep;
+pub mod temporal;
+pub mod timeline;
+pub mod video;
+pub mod webp;
+
+pub use sort::{Direction, PixelSorter};
@@ -1,417 +1,410 @@
+// the web build only runs the gui, the command line is left unused
+#![cfg_attr(target_arch = "wasm32", allow(dead_code, unused_imports))]
+// and the command line alone leaves out what only the gui uses
+#![cfg_attr(not(feature = "gui"), allow(dead_code))]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
-use eframe::egui;
-use image;
+mod batch;
+mod cli;
+mod config;
+#[cfg(feature = "gui")]
+mod gallery;
+#[cfg(feature = "gui")]
+mod gui;
+#[cfg(feature = "gui")]
+mod history;
+mod pipeline;
+mod preset;
+#[cfg(feature = "gui")]
+mod preview;
+mod report;
+#[cfg(not(target_arch = "wasm32"))]
+mod serve;
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+mod session;
+#[cfg(feature = "gui")]
+mod tabs;
+#[cfg(not(target_arch = "wasm32"))]
+mod watch;
+#[cfg(target_arch = "wasm32")]
+mod web;
+#[cfg(feature = "gui")]
+mod widgets;
+
+use batch::{image_files, run_pipeline, sort_images, InputImage};
+use clap::{CommandFactory, Parser};
+#[cfg(all(feature = "gui", not(target_arch = "wasm32")))]
+use gui::{gui_main, preview_main};
+use psorter::animation::is_animated_format;
+use psorter::bench;
+use psorter::io::{basename, load_image_from_path, PngCompression};
+use psorter::sort::Pass;
+use psorter::temporal::TemporalSmoothing;
+use psorter::timeline::Timeline;
+use psorter::video::{is_video_path, sort_video, TimeRange};
 use std::env;
+use std::io::Write;
 use std::path::Path;
+use web_time::Instant;
 
-enum SortBy {
-    Luminance,
-    Hue,
-    Saturation,
-}
-
-fn threshold_upper_boundary(method: &SortBy) -> u16 {
-    match method {
-        SortBy::Luminance | SortBy::Saturation => 255,
-        SortBy::Hue => 360,
-    }
-}
-
-fn luminance(pixel: &egui::Color32) -> u16 {
-    ((pixel.r() as u16) + (pixel.g() as u16) + (pixel.b() as u16)) / 3
+#[cfg(target_arch = "wasm32")]
+fn main() {
+    web::start();
 }
 
-fn hue(pixel: &egui::Color32) -> u16 {
-    let red = pixel.r() as f32;
-    let green = pixel.g() as f32;
-    let blue = pixel.b() as f32;
-
-    let min = blue.min(red.min(green));
-    let max = blue.max(red.max(green));
-
-    if max == min {
-        return 0;
+#[cfg(not(target_arch = "wasm32"))]
+fn main() {
+    init_logging();
+    let config = config::Config::load().unwrap_or_else(|e| {
+        log::warn!("ignoring the config file: {}", e);
+        config::Config::default()
+    });
+    let mut args: Vec<String> = env::args().collect();
+
+    if args.len() == 1 {
+        run_gui(config);
     }
 
-    let hue: f32 = if max == red {
-        (green - blue) / (max - min)
-    } else if max == green {
-        2.0 + (blue - red) / (max - min)
-    } else if max == blue {
-        4.0 + (red - green) / (max - min)
-    } else {
-        panic!("how?");
-    } * 60.0;
-
-    (if hue < 0.0 { hue + 360.0 } else { hue }) as u16
-}
-
-fn saturation(pixel: &egui::Color32) -> u16 {
-    let red = pixel.r() as f32 / 255.0;
-    let green = pixel.g() as f32 / 255.0;
-    let blue = pixel.b() as f32 / 255.0;
-
-    let min = blue.min(red.min(green));
-    let max = blue.max(red.max(green));
-
-    if max == min {
-        return 0;
+    // the subcommand comes after the global flags, if any
+    let command = args
+        .iter()
+        .skip(1)
+        .position(|arg| !is_global_flag(arg))
+        .map_or(args.len(), |i| i + 1);
+
+    // `psorter l 0 69 image.png` is short for `psorter sort l 0 69 image.png`
+    if let Some(first) = args.get(command) {
+        let help_or_version = ["-h", "--help", "-V", "--version"].contains(&first.as_str());
+        if !help_or_version && cli::Cli::command().find_subcommand(first).is_none() {
+            args.insert(command, "sort".to_string());
+        }
     }
 
-    let luminance = (max + min) / 2.0;
-    let saturation = 1.0 - ((2.0 * luminance) - 1.0).abs();
-
-    (saturation * 255.0) as u16
-}
-
-fn basename(path: &str) -> String {
-    let path = Path::new(&path);
-    path.file_name().unwrap().to_str().unwrap().to_string()
-}
-
-fn into_intervals(bitmap: Vec<bool>) -> Vec<(usize, usize)> {
-    let mut result: Vec<(usize, usize)> = Vec::new();
-    let mut interval_start: Option<usize> = None;
-
-    for i in 0..bitmap.len() {
-        if bitmap[i] == false && interval_start.is_some() {
-            result.push((interval_start.unwrap(), i));
-            interval_start = None;
-        } else if bitmap[i] == true && interval_start.is_none() {
-            interval_start = Some(i);
+    // passes are separated by --pass, the images follow the last one
+    let mut passes = Vec::new();
+    let mut config = config;
+    let splits_passes = args.get(command).is_some_and(|subcommand| {
+        ["sort", "batch", "preview", "watch", "sweep", "video"].contains(&subcommand.as_str())
+    });
+    if splits_passes {
+        let mut segments: Vec<Vec<String>> = args[command + 1..]
+            .split(|arg| arg == "--pass")
+            .map(|segment| segment.to_vec())
+            .collect();
+        let last = segments.pop().unwrap_or_default();
+        // the key and the thresholds of a preset only stand in for those of the last pass
+        let (last, last_config) =
+            preset::expand(&last, &config).unwrap_or_else(|e| exit_with_error(e));
+        for segment in segments {
+            passes.push(
+                cli::PassOnly::parse_pass(&segment, &config).unwrap_or_else(|e| exit_with_error(e)),
+            );
         }
+        args.truncate(command + 1);
+        args.extend(last);
+        config = last_config;
     }
 
-    if interval_start.is_some() {
-        result.push((interval_start.unwrap(), bitmap.len()));
+    let cli = cli::Cli::parse_from(args);
+    // RUST_LOG takes precedence over the flags
+    if env::var_os("RUST_LOG").is_none() {
+        log::set_max_level(match (cli.quiet, cli.verbose) {
+            (true, _) => log::LevelFilter::Error,
+            (false, 0) => log::LevelFilter::Warn,
+            (false, 1) => log::LevelFilter::Info,
+            (false, 2) => log::LevelFilter::Debug,
+            (false, _) => log::LevelFilter::Trace,
+        });
     }
 
-    result
-}
-
-fn sort_image(
-    lower_threshold: u16,
-    higher_threshold: u16,
-    image: &mut egui::ColorImage,
-    sorting_method: &SortBy,
-) {
-    let width = image.width();
-    let height = image.height();
-
-    let pixel_property = match sorting_method {
-        SortBy::Hue => hue,
-        SortBy::Saturation => saturation,
-        SortBy::Luminance => luminance,
-    };
-
-    for yi in 0..height {
-        let intervals = {
-            let mut pixel_bitmap: Vec<bool> = Vec::with_capacity(width as usize);
-            for xi in 0..width {
-                let pixel: egui::Color32 = image.pixels[yi * width + xi];
-                let value = pixel_property(&pixel);
-                let accepted_range = lower_threshold..=higher_threshold;
-                pixel_bitmap.push(accepted_range.contains(&value));
+    match cli.command {
+        cli::Command::Sort(sort) => {
+            let images = finish_passes(&mut passes, sort.pass, sort.positional, &config);
+            let output = sort
+                .output
+                .output(images.len(), &config)
+                .unwrap_or_else(|e| exit_with_error(e));
+            let images: Vec<InputImage> = images
+                .into_iter()
+                .map(InputImage::new)
+                .collect::<Result<_, _>>()
+                .unwrap_or_else(|e| exit_with_error(e));
+            sort_images(&passes, &images, &output, &sort.jobs);
+        }
+        cli::Command::Batch(batch) => {
+            let sort = batch.sort;
+            let inputs = finish_passes(&mut passes, sort.pass, sort.positional, &config);
+            let images = image_files(&inputs, batch.recursive, &batch.ext);
+            if images.is_empty() {
+                exit_with_error("no images found");
             }
-
-            into_intervals(pixel_bitmap)
-        };
-
-        for interval in intervals {
-            let (start, end) = interval;
-            let mut pixels: Vec<egui::Color32> = Vec::with_capacity(end - start);
-            for xi in start..end {
-                pixels.push(image.pixels[yi * width + xi]);
+            let output = sort
+                .output
+                .output(images.len(), &config)
+                .unwrap_or_else(|e| exit_with_error(e));
+            sort_images(&passes, &images, &output, &sort.jobs);
+        }
+        cli::Command::Preview(preview) => {
+            let images = finish_passes(&mut passes, preview.pass, preview.positional, &config);
+            if images.len() != 1 {
+                exit_with_error("preview takes a single image");
             }
-            pixels.sort_by(|a, b| pixel_property(&a).cmp(&pixel_property(&b)));
-
-            for i in 0..pixels.len() {
-                let xi = start + i;
-                image.pixels[yi * width + xi] = pixels[i];
+            let mut image = load_image_from_path(&images[0]).unwrap_or_else(|e| {
+                exit_with_error(format!("cannot load image {}: {}", images[0], e))
+            });
+            for pass in &passes {
+                pass.apply(&mut image);
+            }
+            let name = basename(&images[0]).unwrap_or_else(|| images[0].clone());
+            if preview_main(image, &name).is_err() {
+                std::process::exit(1);
             }
         }
-    }
-}
-
-fn main() {
-    let mut args: Vec<String> = env::args().skip(1).collect();
-
-    if args.len() == 0 {
-        if gui_main().is_err() {
-            std::process::exit(1);
-        } else {
-            std::process::exit(0);
+        cli::Command::Watch(watch) => {
+            let directories = finish_passes(&mut passes, watch.pass, watch.positional, &config);
+            // any number of images may turn up
+            let output = watch
+                .output
+                .output(usize::MAX, &config)
+                .unwrap_or_else(|e| exit_with_error(e));
+            if output.in_place {
+                exit_with_error("watch cannot sort in place, the sorted images would change again");
+            }
+            watch::watch(&passes, &directories, watch.recursive, &watch.ext, &output)
+                .unwrap_or_else(|e| exit_with_error(e));
         }
-    } else if args.len() < 4 {
-        eprintln!("USAGE: psorter <l/h/s> <lower threshold> <higher threshold> [images]");
-        std::process::exit(1);
-    }
-
-    let sorting_method = {
-        let arg = args.first().expect("ERROR: please choose one of the methods of sorting (l for luminance, h for hue and s for saturation) as a first argument");
-        match arg.as_str() {
-            "l" => SortBy::Luminance,
-            "h" => SortBy::Hue,
-            "s" => SortBy::Saturation,
-            _ => {
-                eprintln!("ERROR: sorting method must be one of the following: l (luminance), h (hue) or s (saturation)");
-                std::process::exit(1);
+        cli::Command::Serve(serve) => {
+            serve::serve(&serve, &config).unwrap_or_else(|e| exit_with_error(e))
+        }
+        cli::Command::Run {
+            pipeline,
+            images,
+            force,
+            jobs,
+        } => run_pipeline(&pipeline, &images, force, &jobs, &config),
+        cli::Command::Bench(bench) => run_bench(&bench).unwrap_or_else(|e| exit_with_error(e)),
+        cli::Command::Sweep(sweep) => {
+            let timeline = sweep.timeline();
+            let compression = sweep.png_compression();
+            let images = finish_passes(&mut passes, sweep.pass, sweep.positional, &config);
+            if images.len() != 1 {
+                exit_with_error("sweep takes a single image");
+            }
+            if sweep.output.exists() && !sweep.force {
+                exit_with_error(format!(
+                    "{} already exists, pass --force to overwrite it",
+                    sweep.output.display()
+                ));
             }
+            run_sweep(&timeline, &passes, &images[0], &sweep.output, compression)
+                .unwrap_or_else(|e| exit_with_error(e));
         }
-    };
-    args.remove(0);
-
-    let lower_threshold = args
-        .first()
-        .expect("ERROR: please provide lower threshold as a second argument")
-        .parse::<u16>()
-        .expect("ERROR: threshold must be an integer");
-    args.remove(0);
-
-    let higher_threshold = args
-        .first()
-        .expect("ERROR: please provide higher threshold as a third argument")
-        .parse::<u16>()
-        .expect("ERROR: threshold must be an integer");
-    args.remove(0);
+        cli::Command::Video(video) => {
+            let range = TimeRange {
+                start: video.start,
+                end: video.end,
+            };
+            let files = finish_passes(&mut passes, video.pass, video.positional, &config);
+            let [input, output] = files.as_slice() else {
+                exit_with_error("video takes the video to sort and the one to write");
+            };
+            if Path::new(output).exists() && !video.force {
+                exit_with_error(format!(
+                    "{} already exists, pass --force to overwrite it",
+                    output
+                ));
+            }
+            let smoothing = video.smooth.map(TemporalSmoothing::new);
+            run_video(&passes, input, output, &range, smoothing)
+                .unwrap_or_else(|e| exit_with_error(e));
+        }
+        cli::Command::Gui => run_gui(config),
+    }
+}
 
-    if lower_threshold > higher_threshold {
-        eprintln!("ERROR: lower threshold cannot be bigger than a higher threshold.");
-        std::process::exit(1);
+/// prints the throughput of every stage of every pass of `args`
+#[cfg(not(target_arch = "wasm32"))]
+fn run_bench(args: &cli::BenchArgs) -> Result<(), String> {
+    let passes = args.passes()?;
+    let image = load_image_from_path(&args.image)
+        .map_err(|e| format!("cannot load image {}: {}", args.image, e))?;
+    let pixels = image.width() * image.height();
+    let key_width = passes
+        .iter()
+        .map(|(key, _, _)| key.len())
+        .fold("key".len(), usize::max);
+    println!(
+        "{}, {}x{} pixels, {} threads, {} runs of every pass, megapixels per second",
+        args.image,
+        image.width(),
+        image.height(),
+        rayon::current_num_threads(),
+        args.runs
+    );
+    println!(
+        "{:<key_width$} {:<14} {:>10} {:>10} {:>10}",
+        "key", "traversal", "select", "paths", "pass"
+    );
+    for (key, traversal, pass) in passes {
+        let times = bench::time_pass(&pass, &image, args.runs);
+        println!(
+            "{:<key_width$} {:<14} {:>10.1} {:>10.1} {:>10.1}",
+            key,
+            traversal,
+            bench::megapixels_per_second(pixels, times.select),
+            bench::megapixels_per_second(pixels, times.paths),
+            bench::megapixels_per_second(pixels, times.pass)
+        );
     }
+    Ok(())
+}
 
-    for path in args {
-        let mut image = match load_image_from_path(&path) {
-            Ok(new_image) => new_image,
-            Err(e) => {
-                eprintln!("ERROR: cannot load image {}: {}", path, e);
-                std::process::exit(1);
-            }
-        };
+/// renders the frames of `timeline` over the image at `path` and saves them as an animation
+#[cfg(not(target_arch = "wasm32"))]
+fn run_sweep(
+    timeline: &Timeline,
+    passes: &[Pass],
+    path: &str,
+    output: &Path,
+    compression: PngCompression,
+) -> Result<(), String> {
+    // checked before rendering, which takes a while
+    let animated = is_video_path(output)
+        || image::ImageFormat::from_path(output).is_ok_and(is_animated_format);
+    if !animated {
+        return Err(format!(
+            "cannot save {}, sweeps are saved as gifs, pngs or videos like mp4",
+            output.display()
+        ));
+    }
 
-        sort_image(
-            lower_threshold,
-            higher_threshold,
-            &mut image,
-            &sorting_method,
-        );
+    let image =
+        load_image_from_path(path).map_err(|e| format!("cannot load image {}: {}", path, e))?;
+    log::info!(
+        "rendering {} frames of {} ({}x{})",
+        timeline.frames,
+        path,
+        image.width(),
+        image.height()
+    );
+    let start = Instant::now();
+    let animation = timeline.render(&image, passes)?;
+    log::debug!("rendered the frames in {:.2?}", start.elapsed());
+    animation
+        .save(output, compression.png())
+        .map_err(|e| format!("failed to save file {}: {}", output.display(), e))?;
+    log::info!("saved {}", output.display());
+    Ok(())
+}
 
-        let new_file_name = format!("sorted-{}", basename(&path));
-        image::save_buffer(
-            &new_file_name,
-            image.as_raw(),
-            image.width() as u32,
-            image.height() as u32,
-            image::ColorType::Rgba8,
+/// sorts the frames of the video at `input` into `output`, counting them as they're written
+#[cfg(not(target_arch = "wasm32"))]
+fn run_video(
+    passes: &[Pass],
+    input: &str,
+    output: &str,
+    range: &TimeRange,
+    smoothing: Option<TemporalSmoothing>,
+) -> Result<(), String> {
+    if input == output {
+        return Err("the sorted video cannot replace the one it's read from".to_string());
+    }
+    let bar = indicatif::ProgressBar::new_spinner()
+        .with_style(
+            indicatif::ProgressStyle::with_template("{spinner} {prefix} {pos} frames {elapsed}")
+                .unwrap(),
         )
-        .expect(&format!("ERROR: failed to save file {}", &new_file_name));
+        .with_prefix(input.to_string());
+    // like the progress bars of batches, only drawn without -v and -q
+    if log::max_level() != log::LevelFilter::Warn {
+        bar.set_draw_target(indicatif::ProgressDrawTarget::hidden());
     }
+    bar.enable_steady_tick(std::time::Duration::from_millis(100));
+
+    let start = Instant::now();
+    let frames = sort_video(
+        passes,
+        Path::new(input),
+        Path::new(output),
+        range,
+        smoothing,
+        |frames| bar.set_position(frames as u64),
+    );
+    bar.finish_and_clear();
+    let frames = frames.map_err(|e| format!("cannot sort {}: {}", input, e))?;
+    log::info!(
+        "sorted {} frames of {} into {} in {:.2?}",
+        frames,
+        input,
+        output,
+        start.elapsed()
+    );
+    Ok(())
 }
 
-fn load_image_from_path(path: &str) -> Result<egui::ColorImage, image::ImageError> {
-    let image = image::io::Reader::open(path)?.decode()?;
-    let size = [image.width() as _, image.height() as _];
-    let image_buffer = image.to_rgba8();
-    let pixels = image_buffer.as_flat_samples();
-    Ok(egui::ColorImage::from_rgba_unmultiplied(
-        size,
-        pixels.as_slice(),
-    ))
+/// the windows of the gui, when psorter is built without them
+#[cfg(not(feature = "gui"))]
+fn gui_main(_config: config::Config) -> Result<(), String> {
+    exit_with_error("psorter was built without the gui feature, see psorter --help")
 }
 
-const IMAGE_EXTENSIONS: [&'static str; 8] =
-    ["png", "jpg", "jpeg", "bmp", "ico", "tiff", "webp", "tga"];
-
-fn save_image(image: &egui::ColorImage, name: &str) {
-    let picked_path = if let Some(path) = rfd::FileDialog::new()
-        .set_file_name(name)
-        .add_filter("Image Files", &IMAGE_EXTENSIONS)
-        .save_file()
-    {
-        path.display().to_string()
-    } else {
-        return;
-    };
-
-    image::save_buffer(
-        &picked_path,
-        image.as_raw(),
-        image.width() as u32,
-        image.height() as u32,
-        image::ColorType::Rgba8,
-    )
-    .expect(&format!("ERROR: failed to save file {}", &picked_path));
+#[cfg(not(feature = "gui"))]
+fn preview_main(_image: egui::ColorImage, _name: &str) -> Result<(), String> {
+    gui_main(config::Config::default())
 }
 
-fn pick_image() -> Option<String> {
-    if let Some(path) = rfd::FileDialog::new()
-        .add_filter("Image Files", &IMAGE_EXTENSIONS)
-        .pick_file()
-    {
-        Some(path.display().to_string())
+#[cfg(not(target_arch = "wasm32"))]
+fn run_gui(config: config::Config) -> ! {
+    if gui_main(config).is_err() {
+        std::process::exit(1);
     } else {
-        None
+        std::process::exit(0);
     }
 }
 
-fn gui_main() -> Result<(), eframe::Error> {
-    env_logger::init(); // Log to stderr (if you run with `RUST_LOG=debug`).
-
-    let options = eframe::NativeOptions {
-        initial_window_size: Some(egui::vec2(1024.0, 1024.0)),
-        default_theme: eframe::Theme::Light,
-        follow_system_theme: true,
-        ..Default::default()
-    };
-
-    let mut lower_threshold: u16 = 0;
-    let mut higher_threshold: u16 = 255;
-    let mut sort_by: SortBy = SortBy::Luminance;
-    let mut texture: Option<egui::TextureHandle> = None;
-    let mut image = egui::ColorImage::new([512, 512], egui::Color32::TRANSPARENT);
-    let mut sorted_image = image.clone();
-    let mut changed = true;
-    let mut image_name = "placeholder".to_string();
-    let mut error_message: Option<String> = None;
-
-    eframe::run_simple_native("PSORTER", options, move |ctx, _frame| {
-        egui::TopBottomPanel::top("my_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.with_layout(
-                    egui::Layout::default().with_cross_align(egui::Align::LEFT),
-                    |ui| {
-                        ui.horizontal(|ui| {
-                            let upper_boundary = threshold_upper_boundary(&sort_by);
-
-                            let mut new_lower_threshold = lower_threshold;
-                            ui.label("Lower threshold: ");
-                            changed = ui
-                                .add(egui::Slider::new(
-                                    &mut new_lower_threshold,
-                                    0..=upper_boundary,
-                                ))
-                                .changed()
-                                || changed;
-                            lower_threshold = new_lower_threshold.clamp(0, higher_threshold);
-
-                            ui.separator();
-
-                            let mut new_higher_threshold = higher_threshold;
-                            ui.label("Higher threshold: ");
-                            changed = ui
-                                .add(egui::Slider::new(
-                                    &mut new_higher_threshold,
-                                    0..=upper_boundary,
-                                ))
-                                .changed()
-                                || changed;
-                            higher_threshold =
-                                new_higher_threshold.clamp(lower_threshold, upper_boundary);
-                        });
-                    },
-                );
-
-                ui.with_layout(
-                    egui::Layout::default().with_cross_align(egui::Align::RIGHT),
-                    |ui| {
-                        ui.horizontal(|ui| {
-                            if ui.button("Open file…").clicked() {
-                                if let Some(new_image_path) = pick_image() {
-                                    match load_image_from_path(&new_image_path) {
-                                        Ok(x) => {
-                                            image = x;
-                                            changed = true;
-                                            image_name = basename(&new_image_path);
-                                        }
-                                        Err(e) => error_message = Some(e.to_string()),
-                                    };
-                                }
-                            }
-
-                            if ui.button("Save file…").clicked() {
-                                save_image(&sorted_image, &image_name);
-                            }
-
-                            ui.separator();
-
-                            let luminance_button = ui.add(egui::Button::new("Luminance"));
-                            let hue_button = ui.add(egui::Button::new("Hue"));
-                            let saturation_button = ui.add(egui::Button::new("Saturation"));
-
-                            if luminance_button.clicked() {
-                                sort_by = SortBy::Luminance;
-                                changed = true;
-                            } else if hue_button.clicked() {
-                                sort_by = SortBy::Hue;
-                                changed = true;
-                            } else if saturation_button.clicked() {
-                                sort_by = SortBy::Saturation;
-                                changed = true;
-                            }
-
-                            match sort_by {
-                                SortBy::Luminance => luminance_button,
-                                SortBy::Hue => hue_button,
-                                SortBy::Saturation => saturation_button,
-                            }
-                            .highlight();
-                        });
-                    },
-                );
-            });
-        });
-
-        egui::CentralPanel::default().show(ctx, |ui| {
-            if texture.is_none() {
-                texture = Some(ctx.load_texture(&image_name, image.clone(), Default::default()));
-            }
-
-            if changed {
-                changed = false;
-                sorted_image = image.clone();
-                sort_image(
-                    lower_threshold,
-                    higher_threshold,
-                    &mut sorted_image,
-                    &sort_by,
-                );
-
-                texture =
-                    Some(ctx.load_texture(&image_name, sorted_image.clone(), Default::default()));
-            }
+/// logs to stderr as `LEVEL: message`, warnings and errors only until the verbosity flags
+/// are parsed. other crates only get to log warnings
+#[cfg(not(target_arch = "wasm32"))]
+fn init_logging() {
+    env_logger::Builder::new()
+        .filter_level(log::LevelFilter::Warn)
+        .filter_module("psorter", log::LevelFilter::Trace)
+        .format(|f, record| writeln!(f, "{}: {}", record.level(), record.args()))
+        .parse_default_env()
+        .init();
+    if env::var_os("RUST_LOG").is_none() {
+        log::set_max_level(log::LevelFilter::Warn);
+    }
+}
 
-            if let Some(texture) = texture.as_ref() {
-                let available_space = ui.available_size();
-                let vertical_scale = available_space.y / (image.height() as f32);
-                let horizontal_scale = available_space.x / (image.width() as f32);
-                let scale = vertical_scale.min(horizontal_scale);
-                let image_size = egui::Vec2::new(
-                    (image.width() as f32) * scale,
-                    (image.height() as f32) * scale,
-                );
+/// -q, -v and the like, which may come before the subcommand
+fn is_global_flag(arg: &str) -> bool {
+    match arg.strip_prefix('-') {
+        Some("-quiet" | "-verbose") => true,
+        Some(letters) => !letters.is_empty() && letters.chars().all(|c| c == 'q' || c == 'v'),
+        None => false,
+    }
+}
 
-                ui.with_layout(
-                    egui::Layout::centered_and_justified(egui::Direction::LeftToRight),
-                    |ui| {
-                        ui.image(texture, image_size);
-                    },
-                );
-            } else {
-                ui.spinner();
-            }
-        });
+fn exit_with_error(message: impl std::fmt::Display) -> ! {
+    log::error!("{}", message);
+    std::process::exit(1);
+}
 
-        if error_message.is_some() {
-            egui::Window::new("Error")
-                .collapsible(false)
-                .resizable(false)
-                .show(ctx, |ui| {
-                    ui.label(error_message.as_ref().unwrap());
-                    if ui.button("Ok").clicked() {
-                        error_message = None;
-                    }
-                });
-        }
-    })
+/// builds the last pass, returning the files that follow it
+fn finish_passes(
+    passes: &mut Vec<Pass>,
+    pass: cli::PassArgs,
+    positional: Vec<String>,
+    config: &config::Config,
+) -> Vec<String> {
+    let mut files = positional;
+    passes.push(
+        pass.build(&mut files, config)
+            .unwrap_or_else(|e| exit_with_error(e)),
+    );
+    if files.is_empty() {
+        exit_with_error("no images given, they follow the sort key and the thresholds");
+    }
+    files
 }
@@ -0,0 +1,316 @@
+use crate::convolution;
+use crate::keys::{luminance, straight};
+use std::sync::Arc;
+
+/// an operation on a selection mask, with values from 0 (unselected) to 1 (selected)
+#[derive(Clone, Copy, PartialEq)]
+pub enum MaskOperation {
+    Invert,
+    /// gaussian blur, repeated this many times
+    Blur(usize),
+    /// values above the level become 1, the rest 0
+    Threshold(f32),
+    /// grows the selected areas by this many pixels
+    Dilate(usize),
+    /// shrinks the selected areas by this many pixels
+    Erode(usize),
+}
+
+impl MaskOperation {
+    pub fn name(&self) -> &'static str {
+        match self {
+            MaskOperation::Invert => "invert",
+            MaskOperation::Blur(_) => "blur",
+            MaskOperation::Threshold(_) => "threshold",
+            MaskOperation::Dilate(_) => "dilate",
+            MaskOperation::Erode(_) => "erode",
+        }
+    }
+
+    /// parses a comma separated list like `blur:2,threshold:0.3,dilate:1`
+    pub fn parse_list(arg: &str) -> Result<Vec<MaskOperation>, String> {
+        arg.split(',')
+            .filter(|operation| !operation.is_empty())
+            .map(MaskOperation::parse)
+            .collect()
+    }
+
+    fn parse(arg: &str) -> Result<MaskOperation, String> {
+        let (name, parameter) = match arg.split_once(':') {
+            Some((name, parameter)) => (name, Some(parameter)),
+            None => (arg, None),
+        };
+        let invalid = || format!("invalid parameter for mask operation {}", name);
+        let count =
+            |default| parameter.map_or(Ok(default), |p| p.parse::<usize>().map_err(|_| invalid()));
+
+        match name {
+            "invert" => Ok(MaskOperation::Invert),
+            "blur" => Ok(MaskOperation::Blur(count(1)?)),
+            "threshold" => Ok(MaskOperation::Threshold(
+                parameter.map_or(Ok(0.5), |p| p.parse::<f32>().map_err(|_| invalid()))?,
+            )),
+            "dilate" => Ok(MaskOperation::Dilate(count(1)?)),
+            "erode" => Ok(MaskOperation::Erode(count(1)?)),
+            _ => Err(format!(
+                "unknown mask operation {}, expected invert, blur, threshold, dilate or erode",
+                name
+            )),
+        }
+    }
+
+    pub fn apply(&self, mask: Vec<f32>, width: usize, height: usize) -> Vec<f32> {
+        match *self {
+            MaskOperation::Invert => mask.into_iter().map(|value| 1.0 - value).collect(),
+            MaskOperation::Blur(passes) => (0..passes).fold(mask, |mask, _| {
+                convolution::convolve(&mask, width, height, &convolution::GAUSSIAN)
+            }),
+            MaskOperation::Threshold(level) => mask
+                .into_iter()
+                .map(|value| if value > level { 1.0 } else { 0.0 })
+                .collect(),
+            MaskOperation::Dilate(radius) => {
+                extremum_filter(&mask, width, height, radius, f32::max)
+            }
+            MaskOperation::Erode(radius) => extremum_filter(&mask, width, height, radius, f32::min),
+        }
+    }
+}
+
+/// the form `parse_list` reads back, like `blur:2`
+impl std::fmt::Display for MaskOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            MaskOperation::Invert => write!(f, "invert"),
+            MaskOperation::Blur(count)
+            | MaskOperation::Dilate(count)
+            | MaskOperation::Erode(count) => {
+                write!(f, "{}:{}", self.name(), count)
+            }
+            MaskOperation::Threshold(level) => write!(f, "threshold:{}", level),
+        }
+    }
+}
+
+/// where an image may be sorted at all, the selection outside of it is dropped
+#[derive(Clone, PartialEq)]
+pub enum Region {
+    /// x, y, width and height in pixels, clipped to the image
+    Rect([usize; 4]),
+    /// painted, or loaded from a grayscale image
+    Mask(Arc<RegionMask>),
+}
+
+impl Region {
+    /// from 0 where the image is kept as it is to 1 where it may be sorted
+    pub fn values(&self, width: usize, height: usize) -> Vec<f32> {
+        match *self {
+            Region::Rect([x, y, rect_width, rect_height]) => {
+                let (columns, rows) = (x..x + rect_width, y..y + rect_height);
+                (0..width * height)
+                    .map(|i| {
+                        if columns.contains(&(i % width)) && rows.contains(&(i / width)) {
+                            1.0
+                        } else {
+                            0.0
+                        }
+                    })
+                    .collect()
+            }
+            Region::Mask(ref mask) => mask.values(width, height),
+        }
+    }
+
+    /// the same region on the image scaled by `scale`
+    pub fn scaled(&self, scale: f32) -> Region {
+        let scaled = |value: usize| (value as f32 * scale).round() as usize;
+        match self {
+            Region::Rect(rect) => Region::Rect(rect.map(scaled)),
+            // masks are stretched over the image anyway
+            Region::Mask(_) => self.clone(),
+        }
+    }
+
+    /// the region painted on a mask of `size` pixels, to paint on further
+    pub fn to_mask(&self, size: [usize; 2]) -> RegionMask {
+        match self {
+            Region::Mask(mask) => RegionMask::clone(mask),
+            Region::Rect(_) => RegionMask {
+                size,
+                values: self
+                    .values(size[0], size[1])
+                    .into_iter()
+                    .map(|value| (value * 255.0) as u8)
+                    .collect(),
+            },
+        }
+    }
+
+    /// parses `x,y,width,height` in pixels
+    pub fn parse_rect(arg: &str) -> Option<Region> {
+        let values: Vec<usize> = arg
+            .split(',')
+            .map(|value| value.trim().parse().ok())
+            .collect::<Option<_>>()?;
+        Some(Region::Rect(values.try_into().ok()?))
+    }
+}
+
+/// how much every pixel may be sorted, from 0 to 255, stretched over images of another size
+#[derive(Clone, PartialEq)]
+pub struct RegionMask {
+    pub size: [usize; 2],
+    pub values: Vec<u8>,
+}
+
+impl RegionMask {
+    /// where nothing is sorted yet
+    pub fn new(size: [usize; 2]) -> RegionMask {
+        RegionMask {
+            size,
+            values: vec![0; size[0] * size[1]],
+        }
+    }
+
+    /// white is sorted and black isn't, by luminance
+    pub fn from_image(image: &egui::ColorImage) -> RegionMask {
+        RegionMask {
+            size: image.size,
+            values: image
+                .pixels
+                .iter()
+                .map(|pixel| luminance(straight(pixel)) as u8)
+                .collect(),
+        }
+    }
+
+    /// the form `from_image` reads back
+    pub fn to_image(&self) -> egui::ColorImage {
+        egui::ColorImage {
+            size: self.size,
+            pixels: self
+                .values
+                .iter()
+                .map(|&value| egui::Color32::from_gray(value))
+                .collect(),
+        }
+    }
+
+    fn values(&self, width: usize, height: usize) -> Vec<f32> {
+        let [mask_width, mask_height] = self.size;
+        (0..width * height)
+            .map(|i| {
+                let x = i % width * mask_width / width;
+                let y = i / width * mask_height / height;
+                self.values[y * mask_width + x] as f32 / 255.0
+            })
+            .collect()
+    }
+
+    /// a round dab of `radius` pixels. `softness` is how much of the radius fades out, from 0
+    /// for a hard edge to 1. erasing takes the dab out of the mask instead
+    pub fn paint(&mut self, (x, y): (f32, f32), radius: f32, softness: f32, erase: bool) {
+        let [width, height] = self.size;
+        let radius = radius.max(0.5);
+        let solid = radius * (1.0 - softness.clamp(0.0, 1.0));
+        let columns =
+            (x - radius).max(0.0) as usize..((x + radius).ceil().max(0.0) as usize).min(width);
+        let rows =
+            (y - radius).max(0.0) as usize..((y + radius).ceil().max(0.0) as usize).min(height);
+        for yi in rows {
+            for xi in columns.clone() {
+                let distance = (xi as f32 + 0.5 - x).hypot(yi as f32 + 0.5 - y);
+                let strength = if distance <= solid {
+                    1.0
+                } else {
+                    ((radius - distance) / (radius - solid)).clamp(0.0, 1.0)
+                };
+                let value = &mut self.values[yi * width + xi];
+                *value = if erase {
+                    (*value).min(((1.0 - strength) * 255.0).round() as u8)
+                } else {
+                    (*value).max((strength * 255.0).round() as u8)
+                };
+            }
+        }
+    }
+
+    /// fills the polygon through `points`, closed back to the first one. pixels count as inside
+    /// by their centers, crossing edges take turns going in and out
+    pub fn fill_polygon(&mut self, points: &[(f32, f32)]) {
+        let [width, height] = self.size;
+        let edges: Vec<_> = points.iter().zip(points.iter().cycle().skip(1)).collect();
+        for y in 0..height {
+            let center = y as f32 + 0.5;
+            let mut crossings: Vec<f32> = edges
+                .iter()
+                .filter(|(&(_, y0), &(_, y1))| (y0 <= center) != (y1 <= center))
+                .map(|(&(x0, y0), &(x1, y1))| x0 + (center - y0) / (y1 - y0) * (x1 - x0))
+                .collect();
+            crossings.sort_by(f32::total_cmp);
+            for inside in crossings.chunks_exact(2) {
+                let end = ((inside[1] - 0.5).ceil().max(0.0) as usize).min(width);
+                let start = ((inside[0] - 0.5).ceil().max(0.0) as usize).min(end);
+                self.values[y * width + start..y * width + end].fill(255);
+            }
+        }
+    }
+
+    /// dabs close enough together along the line from `from` to `to` to look continuous
+    pub fn stroke(
+        &mut self,
+        from: (f32, f32),
+        to: (f32, f32),
+        radius: f32,
+        softness: f32,
+        erase: bool,
+    ) {
+        let length = (to.0 - from.0).hypot(to.1 - from.1);
+        let dabs = (length / (radius / 4.0).max(0.5)).ceil() as usize;
+        for dab in 0..=dabs {
+            let t = if dabs == 0 {
+                0.0
+            } else {
+                dab as f32 / dabs as f32
+            };
+            let point = (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t);
+            self.paint(point, radius, softness, erase);
+        }
+    }
+}
+
+/// replaces every value with the extremum of the square around it, done in two separable passes
+fn extremum_filter(
+    mask: &[f32],
+    width: usize,
+    height: usize,
+    radius: usize,
+    extremum: fn(f32, f32) -> f32,
+) -> Vec<f32> {
+    let mut horizontal = vec![0.0; mask.len()];
+    for y in 0..height {
+        for x in 0..width {
+            let from = x.saturating_sub(radius);
+            let to = (x + radius).min(width - 1);
+            horizontal[y * width + x] = mask[y * width + from..=y * width + to]
+                .iter()
+                .copied()
+                .reduce(extremum)
+                .unwrap_or_default();
+        }
+    }
+
+    let mut result = vec![0.0; mask.len()];
+    for y in 0..height {
+        let from = y.saturating_sub(radius);
+        let to = (y + radius).min(height - 1);
+        for x in 0..width {
+            result[y * width + x] = (from..=to)
+                .map(|row| horizontal[row * width + x])
+                .reduce(extremum)
+                .unwrap_or_default();
+        }
+    }
+
+    result
+}
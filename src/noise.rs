@@ -0,0 +1,121 @@
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+
+/// a fractal perlin noise field, thresholded into a mask of sortable pixels
+#[derive(Clone, PartialEq)]
+pub struct NoiseMask {
+    /// size of the coarsest features in pixels
+    pub scale: f32,
+    /// every octave adds detail at twice the frequency and half the amplitude
+    pub octaves: u32,
+    /// pixels where the noise (0-1) is above this level are sortable
+    pub level: f32,
+    pub seed: u64,
+}
+
+impl Default for NoiseMask {
+    fn default() -> Self {
+        NoiseMask {
+            scale: 64.0,
+            octaves: 4,
+            level: 0.5,
+            seed: 0,
+        }
+    }
+}
+
+impl NoiseMask {
+    pub fn mask(&self, width: usize, height: usize) -> Vec<bool> {
+        let perlin = Perlin::new(self.seed);
+        let scale = self.scale.max(1.0);
+        let octaves = self.octaves.max(1);
+
+        let mut mask = Vec::with_capacity(width * height);
+        for y in 0..height {
+            for x in 0..width {
+                let mut value = 0.0;
+                let mut amplitude = 1.0;
+                let mut frequency = 1.0 / scale;
+                let mut total_amplitude = 0.0;
+                for _ in 0..octaves {
+                    value += amplitude * perlin.noise(x as f32 * frequency, y as f32 * frequency);
+                    total_amplitude += amplitude;
+                    amplitude *= 0.5;
+                    frequency *= 2.0;
+                }
+                // 2d perlin noise stays within about ±0.7
+                let value = (value / total_amplitude / 1.4 + 0.5).clamp(0.0, 1.0);
+                mask.push(value > self.level);
+            }
+        }
+
+        mask
+    }
+}
+
+struct Perlin {
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    fn new(seed: u64) -> Perlin {
+        let mut values: Vec<u8> = (0..=255).collect();
+        values.shuffle(&mut rand_chacha::ChaCha8Rng::seed_from_u64(seed));
+
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = values[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    fn noise(&self, x: f32, y: f32) -> f32 {
+        let cell_x = x.floor() as i64 & 255;
+        let cell_y = y.floor() as i64 & 255;
+        let x = x - x.floor();
+        let y = y - y.floor();
+
+        let hash = |dx: i64, dy: i64| {
+            let p = &self.permutation;
+            p[p[((cell_x + dx) & 255) as usize] as usize + ((cell_y + dy) & 255) as usize]
+        };
+
+        let u = fade(x);
+        let v = fade(y);
+        lerp(
+            v,
+            lerp(
+                u,
+                gradient(hash(0, 0), x, y),
+                gradient(hash(1, 0), x - 1.0, y),
+            ),
+            lerp(
+                u,
+                gradient(hash(0, 1), x, y - 1.0),
+                gradient(hash(1, 1), x - 1.0, y - 1.0),
+            ),
+        )
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+/// dot product with one of eight gradient directions picked by the hash
+fn gradient(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => x - y,
+        2 => -x + y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
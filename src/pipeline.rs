@@ -0,0 +1,208 @@
+use psorter::io::EncodeOptions;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// a recipe of sorting passes read from a yaml or json file, e.g.
+///
+/// ```yaml
+/// passes:
+///   - key: l
+///     thresholds: [0, 69]
+///     mask: [blur:3, threshold:0.4]
+///   - key: h
+///     thresholds: ["10%", "60%"]
+///     traversal: whole-columns
+///     descending: true
+/// output:
+///   prefix: glitched-
+/// ```
+///
+/// every pass takes the same options as the command line, without the leading dashes:
+/// `true` turns a flag on and lists are joined with commas
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pipeline {
+    pub passes: Vec<BTreeMap<String, serde_json::Value>>,
+    /// sorted along with the images given on the command line
+    #[serde(default)]
+    pub images: Vec<String>,
+    #[serde(default)]
+    pub output: Output,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields, default, rename_all = "kebab-case")]
+pub struct Output {
+    /// put in front of the name of every sorted image
+    pub prefix: String,
+    /// the working directory when not set
+    pub directory: Option<PathBuf>,
+    /// overwrite existing files
+    pub force: bool,
+    /// replace the source images, ignoring the prefix and the directory
+    pub in_place: bool,
+    /// keeps a copy of every replaced image with this added to its name
+    pub backup_suffix: Option<String>,
+    /// leave the EXIF data of the images out of the sorted ones
+    pub strip_metadata: bool,
+    /// exact path of a single sorted image, replaces the two above
+    #[serde(skip)]
+    pub file: Option<PathBuf>,
+    /// format of the sorted images, picked by their extension when not set
+    #[serde(skip)]
+    pub format: Option<image::ImageFormat>,
+    /// jpeg quality, chroma subsampling and png compression
+    #[serde(skip)]
+    pub encoding: EncodeOptions,
+}
+
+impl Default for Output {
+    fn default() -> Self {
+        Output {
+            prefix: "sorted-".to_string(),
+            directory: None,
+            force: false,
+            in_place: false,
+            backup_suffix: None,
+            strip_metadata: false,
+            file: None,
+            format: None,
+            encoding: EncodeOptions::default(),
+        }
+    }
+}
+
+impl Output {
+    /// where the sorted version of the image at `image_path` goes, `-` being standard
+    /// input and output. `relative_path` is kept under the output directory
+    pub fn path(&self, image_path: &str, relative_path: &Path) -> PathBuf {
+        if self.in_place || (image_path == "-" && self.file.is_none()) {
+            return PathBuf::from(image_path);
+        }
+        if let Some(file) = &self.file {
+            return file.clone();
+        }
+
+        let image_name = relative_path.file_name().unwrap_or_default();
+        let name = format!("{}{}", self.prefix, image_name.to_string_lossy());
+        match &self.directory {
+            Some(directory) => directory.join(relative_path).with_file_name(name),
+            None => PathBuf::from(name),
+        }
+    }
+
+    /// where the backup of a replaced image goes, if any
+    pub fn backup_path(&self, image_path: &str) -> Option<PathBuf> {
+        self.backup_suffix
+            .as_ref()
+            .filter(|_| self.in_place)
+            .map(|suffix| PathBuf::from(format!("{}{}", image_path, suffix)))
+    }
+}
+
+impl Pipeline {
+    /// json files are picked by their extension, everything else is read as yaml
+    pub fn load(path: &Path) -> Result<Pipeline, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        let pipeline: Pipeline = if path.extension().is_some_and(|e| e == "json") {
+            serde_json::from_str(&source).map_err(|e| e.to_string())?
+        } else {
+            serde_yaml::from_str(&source).map_err(|e| e.to_string())?
+        };
+
+        if pipeline.passes.is_empty() {
+            return Err("the pipeline has no passes".to_string());
+        }
+        Ok(pipeline)
+    }
+}
+
+/// turns a pass of a pipeline file into command line arguments, the key and the thresholds
+/// come first and the options follow
+pub fn pass_arguments(pass: &BTreeMap<String, serde_json::Value>) -> Result<Vec<String>, String> {
+    let mut args = match pass.get("key") {
+        Some(key) => vec![scalar(key).ok_or("the key of a pass must be a string")?],
+        // the preset brings the key along
+        None if pass.contains_key("preset") => Vec::new(),
+        None => return Err("every pass needs a key".to_string()),
+    };
+
+    match pass.get("thresholds") {
+        Some(serde_json::Value::Array(thresholds)) if thresholds.len() == 2 => {
+            for threshold in thresholds {
+                args.push(scalar(threshold).ok_or("thresholds must be numbers or percentages")?);
+            }
+        }
+        Some(_) => return Err("thresholds must be a list of two values".to_string()),
+        None => {}
+    }
+
+    args.extend(option_arguments(pass)?);
+    Ok(args)
+}
+
+/// the options of a pass as command line arguments, leaving out the key and the thresholds
+pub fn option_arguments(pass: &BTreeMap<String, serde_json::Value>) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    for (name, value) in pass {
+        if name == "key" || name == "thresholds" {
+            continue;
+        }
+
+        let option = format!("--{}", name);
+        match value {
+            serde_json::Value::Bool(true) => args.push(option),
+            serde_json::Value::Bool(false) | serde_json::Value::Null => {}
+            serde_json::Value::Array(values) => {
+                let values: Option<Vec<String>> = values.iter().map(scalar).collect();
+                let values = values.ok_or(format!("{} must be a list of plain values", name))?;
+                args.extend([option, values.join(",")]);
+            }
+            value => {
+                let value = scalar(value).ok_or(format!("{} must be a plain value", name))?;
+                args.extend([option, value]);
+            }
+        }
+    }
+
+    Ok(args)
+}
+
+/// the `psorter sort` command line sorting `image` by `passes`, quoted for a shell
+pub fn command(
+    passes: &[BTreeMap<String, serde_json::Value>],
+    image: &str,
+) -> Result<String, String> {
+    let mut args = vec!["psorter".to_string(), "sort".to_string()];
+    for (i, pass) in passes.iter().enumerate() {
+        if i > 0 {
+            args.push("--pass".to_string());
+        }
+        args.extend(pass_arguments(pass)?);
+    }
+    args.push(image.to_string());
+    Ok(args
+        .iter()
+        .map(|arg| shell_quote(arg))
+        .collect::<Vec<_>>()
+        .join(" "))
+}
+
+/// `arg` in single quotes unless it's only made of characters no shell treats specially
+fn shell_quote(arg: &str) -> String {
+    let plain = |c: char| c.is_ascii_alphanumeric() || "-_./,:%+=@".contains(c);
+    if !arg.is_empty() && arg.chars().all(plain) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+pub fn scalar(value: &serde_json::Value) -> Option<String> {
+    match value {
+        serde_json::Value::String(string) => Some(string.clone()),
+        serde_json::Value::Number(number) => Some(number.to_string()),
+        _ => None,
+    }
+}
@@ -0,0 +1,340 @@
+use crate::config::{Config, Threshold};
+use crate::pipeline;
+use psorter::intervals::{IntervalOperation, IntervalSource, ScanOrder, SmearPixel, Traversal};
+use psorter::io::{encode_image, EncodeOptions};
+use psorter::keys::{AutoThreshold, SortBy};
+use psorter::mask;
+use psorter::sort::Pass;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// options of a pass saved under a name, in the same form as the passes of a pipeline file
+pub type Preset = BTreeMap<String, Value>;
+
+/// every preset lives in `~/.config/porter/presets.yaml`, e.g.
+///
+/// ```yaml
+/// vaporwave:
+///   key: h
+///   thresholds: ["20%", "80%"]
+///   traversal: radial
+///   descending: true
+///   mask: [blur:3]
+/// ```
+pub fn path() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("porter").join("presets.yaml"))
+}
+
+/// none when there's no presets file
+pub fn load_all() -> Result<BTreeMap<String, Preset>, String> {
+    match path().filter(|path| path.exists()) {
+        Some(path) => load_file(&path),
+        None => Ok(BTreeMap::new()),
+    }
+}
+
+pub fn save_all(presets: &BTreeMap<String, Preset>) -> Result<(), String> {
+    let path = path().ok_or("cannot find the config directory")?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).map_err(|e| e.to_string())?;
+    }
+    save_file(presets, &path)
+}
+
+/// presets in the form of the presets file, json being yaml too
+pub fn load_file(path: &Path) -> Result<BTreeMap<String, Preset>, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    serde_yaml::from_str(&source).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+pub fn save_file(presets: &BTreeMap<String, Preset>, path: &Path) -> Result<(), String> {
+    let source = serde_yaml::to_string(presets).map_err(|e| e.to_string())?;
+    std::fs::write(path, source).map_err(|e| e.to_string())
+}
+
+/// replaces `--preset NAME` among the arguments of a pass with the options saved under that
+/// name, the options given next to it win. the key and the thresholds of the preset are
+/// returned as the defaults of the config, so they're only used when the pass has none
+pub fn expand(args: &[String], config: &Config) -> Result<(Vec<String>, Config), String> {
+    let mut rest = Vec::new();
+    let mut name = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        let value = match arg.strip_prefix("--preset") {
+            Some("") => iter
+                .next()
+                .cloned()
+                .ok_or("--preset needs the name of a preset")?,
+            Some(value) if value.starts_with('=') => value[1..].to_string(),
+            _ => {
+                rest.push(arg.clone());
+                continue;
+            }
+        };
+        if name.replace(value).is_some() {
+            return Err("only one --preset can be given per pass".to_string());
+        }
+    }
+    let Some(name) = name else {
+        return Ok((rest, config.clone()));
+    };
+
+    let preset = load_all()?
+        .remove(&name)
+        .ok_or_else(|| format!("no preset named {}", name))?;
+
+    let given: Vec<&str> = rest
+        .iter()
+        .filter_map(|arg| arg.strip_prefix("--"))
+        .map(|arg| arg.split('=').next().unwrap_or(arg))
+        .collect();
+    let options: Preset = preset
+        .iter()
+        .filter(|(option, _)| *option != "preset" && !given.contains(&option.as_str()))
+        .map(|(option, value)| (option.clone(), value.clone()))
+        .collect();
+    let mut expanded = pipeline::option_arguments(&options)?;
+    expanded.extend(rest);
+
+    let mut config = config.clone();
+    if let Some(key) = preset.get("key") {
+        config.key = Some(pipeline::scalar(key).ok_or("the key of a preset must be a string")?);
+    }
+    match preset.get("thresholds") {
+        Some(Value::Array(thresholds)) if thresholds.len() == 2 => {
+            let thresholds: Option<Vec<String>> = thresholds.iter().map(pipeline::scalar).collect();
+            let thresholds = thresholds.ok_or("thresholds must be numbers or percentages")?;
+            config.thresholds = Some([
+                Threshold::from(thresholds[0].as_str()),
+                Threshold::from(thresholds[1].as_str()),
+            ]);
+        }
+        Some(_) => return Err("thresholds must be a list of two values".to_string()),
+        None => {}
+    }
+
+    Ok((expanded, config))
+}
+
+/// the options of a pass as a preset, leaving out the ones at their defaults. `script_source`
+/// is kept along when sorting by a script
+pub fn from_pass(pass: &Pass, script_source: &str) -> Result<Preset, String> {
+    let keys = [
+        Some(&pass.sort_by),
+        pass.thresholds.key.as_ref(),
+        pass.order.tie_breaker.as_ref(),
+    ];
+    if keys
+        .into_iter()
+        .flatten()
+        .any(|key| matches!(key, SortBy::Custom(_)))
+    {
+        return Err("keys of other crates cannot be kept in a preset".to_string());
+    }
+
+    let mut preset = Preset::new();
+    let mut set = |name: &str, value: Value| {
+        preset.insert(name.to_string(), value);
+    };
+
+    set("key", json!(pass.sort_by.name()));
+    if let SortBy::Script(_) = pass.sort_by {
+        set("key-script", json!(script_source));
+    }
+    let reference_color = [Some(&pass.sort_by), pass.thresholds.key.as_ref()]
+        .into_iter()
+        .flatten()
+        .find_map(SortBy::reference_color);
+    if let Some(color) = reference_color {
+        set(
+            "ref-color",
+            json!(format!(
+                "#{:02x}{:02x}{:02x}",
+                color.r(),
+                color.g(),
+                color.b()
+            )),
+        );
+    }
+
+    let thresholds = &pass.thresholds;
+    match pass.auto_threshold {
+        Some(AutoThreshold::Otsu) => set("auto-threshold", json!("otsu")),
+        Some(AutoThreshold::Percentile(percent)) => {
+            set("auto-threshold", json!(format!("percentile:{}", percent)))
+        }
+        None if matches!(thresholds.source, IntervalSource::Threshold) => {
+            set("thresholds", json!([thresholds.lower, thresholds.higher]))
+        }
+        None => {}
+    }
+    if let Some(key) = &thresholds.key {
+        set("threshold-by", json!(key.name()));
+    }
+    if thresholds.invert {
+        set("invert", json!(true));
+    }
+    if !thresholds.mask_operations.is_empty() {
+        let operations: Vec<String> = thresholds
+            .mask_operations
+            .iter()
+            .map(mask::MaskOperation::to_string)
+            .collect();
+        set("mask", json!(operations));
+    }
+    if thresholds.soft {
+        set("soft-mask", json!(true));
+    }
+    match &thresholds.region {
+        Some(mask::Region::Rect([x, y, width, height])) => {
+            set("region", json!(format!("{},{},{},{}", x, y, width, height)))
+        }
+        Some(mask::Region::Mask(_)) => {
+            return Err("region masks cannot be kept in a preset".to_string())
+        }
+        None => {}
+    }
+
+    let options = &pass.interval_options;
+    match &thresholds.source {
+        IntervalSource::Threshold => {}
+        IntervalSource::Noise(noise) => {
+            set("noise", number(noise.level));
+            set("noise-scale", number(noise.scale));
+            set("noise-octaves", json!(noise.octaves));
+        }
+        IntervalSource::Edges(edges) => {
+            set(
+                "edges",
+                json!(match edges.detector {
+                    psorter::convolution::EdgeDetector::Sobel => "sobel",
+                    psorter::convolution::EdgeDetector::Canny => "canny",
+                }),
+            );
+            set("edge-threshold", number(edges.threshold));
+        }
+        IntervalSource::Control(_) => {
+            return Err("control images cannot be kept in a preset".to_string())
+        }
+    }
+
+    let (traversal, center) = match &pass.traversal {
+        Traversal::Rows => (None, None),
+        Traversal::Lines { angle } => {
+            set("angle", number(*angle));
+            (None, None)
+        }
+        Traversal::Radial { center } => (Some("radial"), *center),
+        Traversal::Spiral { center } => (Some("spiral"), *center),
+        Traversal::Hilbert => (Some("hilbert"), None),
+        Traversal::Whole(ScanOrder::RowMajor) => (Some("whole-rows"), None),
+        Traversal::Whole(ScanOrder::ColumnMajor) => (Some("whole-columns"), None),
+        Traversal::Svg(_) => return Err("svg paths cannot be kept in a preset".to_string()),
+    };
+    if let Some(traversal) = traversal {
+        set("traversal", json!(traversal));
+    }
+    if let Some((x, y)) = center {
+        set("center", json!(format!("{},{}", x, y)));
+    }
+    if let Some(size) = pass.block_size {
+        set("block", json!(size));
+    }
+    if options.wrap_around {
+        set("wrap", json!(true));
+    }
+
+    if options.min_length > 0 {
+        set("min-length", json!(options.min_length));
+    }
+    if options.gap_tolerance > 0 {
+        set("gap", json!(options.gap_tolerance));
+    }
+    if let Some(max_length) = options.max_length {
+        set("max-length", json!(max_length));
+    }
+    if options.random_chunks {
+        set("random-chunks", json!(true));
+    }
+    if options.split_probability > 0.0 {
+        set("split-chance", json!(options.split_probability));
+    }
+    if options.seed != 0 {
+        set("seed", json!(options.seed));
+    }
+
+    match options.operation {
+        IntervalOperation::Sort => {}
+        IntervalOperation::Shuffle => set("operation", json!("shuffle")),
+        IntervalOperation::Smear(SmearPixel::First) => set("operation", json!("smear")),
+        IntervalOperation::Smear(SmearPixel::Brightest) => {
+            set("operation", json!("smear-brightest"))
+        }
+    }
+    if let Some(key) = &pass.order.tie_breaker {
+        set("then", json!(key.name()));
+    }
+    for (flag, on) in [
+        ("descending", pass.order.descending),
+        ("zigzag", pass.order.zigzag),
+        ("alternate", pass.order.alternate_paths),
+        ("random-shift", options.random_shift),
+        ("per-channel", pass.per_channel),
+    ] {
+        if on {
+            set(flag, json!(true));
+        }
+    }
+    if options.shift != 0 {
+        set("shift", json!(options.shift));
+    }
+    if pass.strength < 1.0 {
+        set("strength", number(pass.strength * 100.0));
+    }
+
+    Ok(preset)
+}
+
+/// `pass` as a preset like `from_pass`, its region mask and control image written into
+/// `directory` as `{name}-mask.png` and `{name}-control.png`
+pub fn from_pass_with_files(
+    pass: &Pass,
+    script_source: &str,
+    directory: &Path,
+    name: &str,
+) -> Result<Preset, String> {
+    let mut pass = pass.clone();
+    let mut files = Vec::new();
+    if let Some(mask::Region::Mask(painted)) = &pass.thresholds.region {
+        let path = directory.join(format!("{}-mask.png", name));
+        write_png(&painted.to_image(), &path)?;
+        files.push(("region-mask", path));
+        pass.thresholds.region = None;
+    }
+    if let IntervalSource::Control(control) = &pass.thresholds.source {
+        let path = directory.join(format!("{}-control.png", name));
+        write_png(control, &path)?;
+        files.push(("control", path));
+        pass.thresholds.source = IntervalSource::Threshold;
+        // the command line compares control images by luminance unless told otherwise
+        pass.thresholds.key.get_or_insert(pass.sort_by.clone());
+    }
+
+    let mut preset = from_pass(&pass, script_source)?;
+    for (option, path) in files {
+        preset.insert(option.to_string(), json!(path.display().to_string()));
+    }
+    Ok(preset)
+}
+
+/// `image` as a png file at `path`
+pub fn write_png(image: &egui::ColorImage, path: &Path) -> Result<(), String> {
+    let bytes = encode_image(image, path, None, &EncodeOptions::default())?;
+    std::fs::write(path, bytes).map_err(|e| format!("cannot write {}: {}", path.display(), e))
+}
+
+/// an f32 written the way it's read, without the noise of widening it to an f64
+fn number(value: f32) -> Value {
+    json!(value.to_string().parse::<f64>().unwrap_or_default())
+}
@@ -0,0 +1,234 @@
+//! sorting the preview of the gui away from the ui, so the window keeps responding while a
+//! big image is sorted. only the newest settings are worth sorting, a job is given up between
+//! two steps once there's a newer one
+use psorter::keys::key_histogram;
+use psorter::sort::Pass;
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+
+pub struct Job {
+    pub image: egui::ColorImage,
+    /// the passes before the one shown
+    pub passes: Vec<Pass>,
+    pub pass: Pass,
+    /// also tint the pixels `pass` selects
+    pub selection: bool,
+}
+
+pub struct Preview {
+    pub sorted: egui::ColorImage,
+    /// of the key `pass` compares against its thresholds, after the passes before it
+    pub histogram: Vec<u64>,
+    pub selection: Option<egui::ColorImage>,
+}
+
+impl Job {
+    /// the job on a copy of the image scaled down to fit in `max_size` pixels both ways, for a
+    /// quick look while the settings are being dragged. centers and blocks are scaled along
+    pub fn downscaled(mut self, max_size: usize) -> Job {
+        let Some((small, scale)) = downscale(&self.image, max_size) else {
+            return self;
+        };
+        self.image = small;
+        for pass in self.passes.iter_mut().chain([&mut self.pass]) {
+            scale_pass(pass, scale);
+        }
+        self
+    }
+}
+
+/// `image` scaled down to fit in `max_size` pixels both ways, along with the scale. none
+/// when it fits already
+pub fn downscale(image: &egui::ColorImage, max_size: usize) -> Option<(egui::ColorImage, f32)> {
+    let [width, height] = image.size;
+    if width.max(height) <= max_size {
+        return None;
+    }
+    let scale = max_size as f32 / width.max(height) as f32;
+    let scaled = |size: usize| ((size as f32 * scale).round() as u32).max(1);
+    let buffer = image::RgbaImage::from_raw(width as u32, height as u32, image.as_raw().to_vec())
+        .expect("a color image has 4 bytes for every pixel");
+    let small = image::imageops::thumbnail(&buffer, scaled(width), scaled(height));
+    let small = egui::ColorImage::from_rgba_premultiplied(
+        [small.width() as usize, small.height() as usize],
+        small.as_raw(),
+    );
+    Some((small, scale))
+}
+
+/// `pass` for the image scaled by `scale`, its center, blocks and region scaled along
+pub fn scale_pass(pass: &mut Pass, scale: f32) {
+    let scaled = |size: usize| ((size as f32 * scale).round() as usize).max(1);
+    if let Some(Some((x, y))) = pass.traversal.center_mut() {
+        (*x, *y) = (scaled(*x), scaled(*y));
+    }
+    pass.block_size = pass.block_size.map(scaled);
+    if let Some(region) = &mut pass.thresholds.region {
+        *region = region.scaled(scale);
+    }
+}
+
+#[derive(Default)]
+struct Status {
+    /// counts the jobs, the one being sorted is given up once it's behind
+    latest: AtomicUsize,
+    /// how much of the job being sorted is done, the bits of an f32 from 0 to 1
+    progress: AtomicU32,
+}
+
+pub struct Sorter {
+    #[cfg(not(target_arch = "wasm32"))]
+    jobs: mpsc::Sender<(usize, Job)>,
+    // the browser has no threads, there jobs are sorted right away and sent to themselves
+    #[cfg(target_arch = "wasm32")]
+    finished: mpsc::Sender<(usize, Preview)>,
+    previews: mpsc::Receiver<(usize, Preview)>,
+    status: Arc<Status>,
+    /// the job of the last preview taken
+    shown: usize,
+}
+
+impl Sorter {
+    /// starts the thread sorting the jobs
+    pub fn start() -> Sorter {
+        let status: Arc<Status> = Default::default();
+        let (finished, previews) = mpsc::channel();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let jobs = {
+            let (jobs, queued) = mpsc::channel::<(usize, Job)>();
+            let status = status.clone();
+            std::thread::spawn(move || {
+                while let Ok(mut next) = queued.recv() {
+                    // the ones sent in the meantime are already outdated
+                    while let Ok(newer) = queued.try_recv() {
+                        next = newer;
+                    }
+                    let (id, job) = next;
+                    if let Some(preview) = sort(job, id, &status) {
+                        if finished.send((id, preview)).is_err() {
+                            break;
+                        }
+                    }
+                }
+            });
+            jobs
+        };
+
+        Sorter {
+            #[cfg(not(target_arch = "wasm32"))]
+            jobs,
+            #[cfg(target_arch = "wasm32")]
+            finished,
+            previews,
+            status,
+            shown: 0,
+        }
+    }
+
+    /// sorts `job` instead of the one being sorted
+    pub fn sort(&mut self, job: Job) {
+        let id = self.status.latest.fetch_add(1, Ordering::Relaxed) + 1;
+        #[cfg(not(target_arch = "wasm32"))]
+        let _ = self.jobs.send((id, job));
+        #[cfg(target_arch = "wasm32")]
+        if let Some(preview) = sort(job, id, &self.status) {
+            let _ = self.finished.send((id, preview));
+        }
+    }
+
+    /// the preview of the newest job, once it's sorted
+    pub fn finished(&mut self) -> Option<Preview> {
+        let latest = self.status.latest.load(Ordering::Relaxed);
+        let (id, preview) = self
+            .previews
+            .try_iter()
+            .filter(|(id, _)| *id == latest)
+            .last()?;
+        self.shown = id;
+        Some(preview)
+    }
+
+    pub fn busy(&self) -> bool {
+        self.shown != self.status.latest.load(Ordering::Relaxed)
+    }
+
+    /// how much of the newest job is done, from 0 to 1
+    pub fn progress(&self) -> f32 {
+        f32::from_bits(self.status.progress.load(Ordering::Relaxed))
+    }
+}
+
+/// the passes and the selection are a step each, passes go forward with every sorted path.
+/// none once a newer job than `id` comes
+fn sort(job: Job, id: usize, status: &Status) -> Option<Preview> {
+    let steps = (job.passes.len() + 1 + job.selection as usize) as f32;
+    // records `share` of the step after `done` ones, false once the job is outdated
+    let report = |done: usize, share: f32| {
+        let progress = (done as f32 + share) / steps;
+        status.progress.store(progress.to_bits(), Ordering::Relaxed);
+        status.latest.load(Ordering::Relaxed) == id
+    };
+    if !report(0, 0.0) {
+        return None;
+    }
+
+    let mut sorted = job.image;
+    for (done, pass) in job.passes.iter().enumerate() {
+        if !pass.apply_with_progress(&mut sorted, &|share| report(done, share)) {
+            return None;
+        }
+    }
+    let done = job.passes.len();
+    let key = job
+        .pass
+        .thresholds
+        .key
+        .as_ref()
+        .unwrap_or(&job.pass.sort_by);
+    let histogram = key_histogram(job.pass.thresholds.source.threshold_image(&sorted), key);
+    let mut selection = None;
+    if job.selection {
+        selection = Some(tint_selection(&sorted, &job.pass.selection(&sorted)));
+        if !report(done, 1.0) {
+            return None;
+        }
+    }
+    let done = done + job.selection as usize;
+    if !job
+        .pass
+        .apply_with_progress(&mut sorted, &|share| report(done, share))
+    {
+        return None;
+    }
+    Some(Preview {
+        sorted,
+        histogram,
+        selection,
+    })
+}
+
+/// `image` with the `selected` pixels tinted halfway to magenta
+fn tint_selection(image: &egui::ColorImage, selected: &[bool]) -> egui::ColorImage {
+    let tint = egui::Color32::from_rgb(255, 0, 255);
+    let mix = |from: u8, to: u8| ((from as u16 + to as u16) / 2) as u8;
+    egui::ColorImage {
+        size: image.size,
+        pixels: image
+            .pixels
+            .iter()
+            .zip(selected)
+            .map(|(&pixel, &selected)| {
+                if !selected {
+                    return pixel;
+                }
+                egui::Color32::from_rgba_premultiplied(
+                    mix(pixel.r(), tint.r()),
+                    mix(pixel.g(), tint.g()),
+                    mix(pixel.b(), tint.b()),
+                    mix(pixel.a(), tint.a()),
+                )
+            })
+            .collect(),
+    }
+}
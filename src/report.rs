@@ -0,0 +1,52 @@
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// what `--report` writes, one entry per image that was sorted or failed, in the order they
+/// were given. images skipped after a failure without `--keep-going` are left out
+#[derive(Serialize)]
+pub struct Report {
+    /// the command line the images were sorted with
+    pub arguments: Vec<String>,
+    pub images: Vec<ImageReport>,
+}
+
+#[derive(Serialize)]
+pub struct ImageReport {
+    pub input: String,
+    pub output: PathBuf,
+    pub width: Option<usize>,
+    pub height: Option<usize>,
+    /// time spent decoding, sorting and encoding
+    pub seconds: f64,
+    pub error: Option<String>,
+}
+
+impl ImageReport {
+    pub fn new(
+        input: String,
+        output: PathBuf,
+        result: Result<[usize; 2], String>,
+        duration: Duration,
+    ) -> ImageReport {
+        let (size, error) = match result {
+            Ok([width, height]) => (Some((width, height)), None),
+            Err(e) => (None, Some(e)),
+        };
+        ImageReport {
+            input,
+            output,
+            width: size.map(|(width, _)| width),
+            height: size.map(|(_, height)| height),
+            seconds: duration.as_secs_f64(),
+            error,
+        }
+    }
+}
+
+impl Report {
+    pub fn save(&self, path: &Path) -> Result<(), String> {
+        let json = serde_json::to_string_pretty(self).map_err(|e| e.to_string())?;
+        std::fs::write(path, json).map_err(|e| e.to_string())
+    }
+}
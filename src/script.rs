@@ -0,0 +1,72 @@
+use crate::keys;
+use image::Rgba;
+use std::sync::Arc;
+
+/// upper bound on the work a single key evaluation may do, so scripts can't hang the sorter
+const MAX_OPERATIONS: u64 = 10_000;
+
+/// a user-written rhai expression computing the sort key of a pixel, e.g. `r*0.5 + b - g`
+///
+/// the expression sees the channels `r`, `g`, `b` and `a` (0-255) as well as `h` (hue, 0-360),
+/// `s` (saturation), `l` (luminance) and `v` (value), all as floats. the result is clamped
+/// to 0-255.
+#[derive(Clone)]
+pub struct KeyScript {
+    engine: Arc<rhai::Engine>,
+    ast: Arc<rhai::AST>,
+}
+
+impl KeyScript {
+    pub fn compile(source: &str) -> Result<KeyScript, String> {
+        let mut engine = rhai::Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        let ast = engine
+            .compile_expression(source)
+            .map_err(|e| e.to_string())?;
+        let script = KeyScript {
+            engine: Arc::new(engine),
+            ast: Arc::new(ast),
+        };
+
+        // catch type errors and the like up front instead of silently sorting by zeros
+        for pixel in [Rgba([0, 0, 0, 255]), Rgba([255, 255, 255, 255])] {
+            script.evaluate(pixel)?;
+        }
+
+        Ok(script)
+    }
+
+    fn evaluate(&self, pixel: Rgba<u8>) -> Result<f64, String> {
+        let mut scope = rhai::Scope::new();
+        scope.push("r", pixel[0] as f64);
+        scope.push("g", pixel[1] as f64);
+        scope.push("b", pixel[2] as f64);
+        scope.push("a", pixel[3] as f64);
+        scope.push("h", keys::hue(pixel) as f64);
+        scope.push("s", keys::saturation(pixel) as f64);
+        scope.push("l", keys::luminance(pixel) as f64);
+        scope.push("v", keys::value(pixel) as f64);
+
+        let result: rhai::Dynamic = self
+            .engine
+            .eval_ast_with_scope(&mut scope, &self.ast)
+            .map_err(|e| e.to_string())?;
+
+        if let Ok(value) = result.as_float() {
+            Ok(value)
+        } else if let Ok(value) = result.as_int() {
+            Ok(value as f64)
+        } else {
+            Err(format!(
+                "the expression must produce a number, got {}",
+                result.type_name()
+            ))
+        }
+    }
+
+    pub fn key(&self, pixel: Rgba<u8>) -> u16 {
+        self.evaluate(pixel)
+            .map_or(0, |value| value.clamp(0.0, 255.0) as u16)
+    }
+}
@@ -0,0 +1,252 @@
+use crate::{cli, config, pipeline, preset};
+use psorter::io::{color_image, encode_image, EncodeOptions};
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response};
+
+const USAGE: &str = "POST an image to /sort?key=l&thresholds=0,69 to get it back sorted.
+every option of the command line is a parameter, without the leading dashes: flags take no
+value and lists are separated by commas. format picks the format of the answer, png by default.
+";
+
+/// the options of a pass that can be given as parameters. the ones reading files on the
+/// server, like control, svg and region-mask, are left out. presets are the server's own
+const PASS_OPTIONS: [&str; 34] = [
+    "preset",
+    "traversal",
+    "angle",
+    "center",
+    "block",
+    "wrap",
+    "threshold-by",
+    "auto-threshold",
+    "invert",
+    "mask",
+    "soft-mask",
+    "region",
+    "noise",
+    "noise-scale",
+    "noise-octaves",
+    "edges",
+    "edge-threshold",
+    "min-length",
+    "gap",
+    "max-length",
+    "random-chunks",
+    "split-chance",
+    "seed",
+    "operation",
+    "ref-color",
+    "key-script",
+    "then",
+    "descending",
+    "zigzag",
+    "alternate",
+    "shift",
+    "random-shift",
+    "per-channel",
+    "strength",
+];
+
+/// answers requests on `args.host:args.port` until the process is stopped
+pub fn serve(args: &cli::ServeArgs, config: &config::Config) -> Result<(), String> {
+    let address = format!("{}:{}", args.host, args.port);
+    let server = Arc::new(
+        tiny_http::Server::http(&address)
+            .map_err(|e| format!("cannot listen on {}: {}", address, e))?,
+    );
+    log::info!("listening on http://{}", address);
+
+    let threads = match args.threads {
+        Some(threads) => threads as usize,
+        None => std::thread::available_parallelism().map_or(1, |threads| threads.get()),
+    };
+    let workers: Vec<_> = (0..threads)
+        .map(|_| {
+            let server = server.clone();
+            let config = config.clone();
+            let args = args.clone();
+            std::thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    answer(request, &config, &args);
+                }
+            })
+        })
+        .collect();
+    for worker in workers {
+        worker
+            .join()
+            .map_err(|_| "a worker thread crashed".to_string())?;
+    }
+    Ok(())
+}
+
+fn answer(mut request: Request, config: &config::Config, args: &cli::ServeArgs) {
+    let start = std::time::Instant::now();
+    let url = request.url().to_string();
+    let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+    let result = match (request.method(), path) {
+        (Method::Post, "/sort") => sort_request(&mut request, query, config, args),
+        (Method::Get, "/") => Ok((USAGE.as_bytes().to_vec(), "text/plain; charset=utf-8")),
+        (_, "/sort") => Err((405, "images are sorted with POST".to_string())),
+        _ => Err((404, "not found, images are sorted at /sort".to_string())),
+    };
+
+    let (status, body, content_type) = match result {
+        Ok((body, content_type)) => (200, body, content_type),
+        Err((status, message)) => (status, message.into_bytes(), "text/plain; charset=utf-8"),
+    };
+    log::info!(
+        "{} {} {} in {:.2?}",
+        request.method(),
+        url,
+        status,
+        start.elapsed()
+    );
+    let response = Response::from_data(body)
+        .with_status_code(status)
+        .with_header(
+            Header::from_bytes("Content-Type", content_type).expect("content types are valid"),
+        );
+    if let Err(e) = request.respond(response) {
+        log::warn!("cannot answer {}: {}", url, e);
+    }
+}
+
+/// the sorted image and its content type, or the status and message of what went wrong
+fn sort_request(
+    request: &mut Request,
+    query: &str,
+    config: &config::Config,
+    args: &cli::ServeArgs,
+) -> Result<(Vec<u8>, &'static str), (u16, String)> {
+    let bad_request = |message: String| (400, message);
+
+    let mut positional = Vec::new();
+    let mut thresholds = Vec::new();
+    let mut options = preset::Preset::new();
+    let mut format = image::ImageFormat::Png;
+    for parameter in query.split('&').filter(|parameter| !parameter.is_empty()) {
+        let (name, value) = parameter.split_once('=').unwrap_or((parameter, ""));
+        let value = percent_decode(value)
+            .ok_or_else(|| bad_request(format!("{} is not valid utf-8", name)))?;
+        match name {
+            "key" => positional.push(value),
+            "thresholds" => thresholds = value.split(',').map(str::to_string).collect(),
+            "format" => {
+                format = image::ImageFormat::from_extension(&value)
+                    .filter(|format| format.can_write())
+                    .ok_or_else(|| bad_request(format!("cannot write {} images", value)))?
+            }
+            _ if !PASS_OPTIONS.contains(&name) => {
+                return Err(bad_request(format!("{} is not available over http", name)))
+            }
+            "key-script" if !args.allow_scripts => {
+                return Err((
+                    403,
+                    "key-script is only available when the server runs with --allow-scripts"
+                        .to_string(),
+                ))
+            }
+            _ if value.is_empty() || value == "true" => {
+                options.insert(name.to_string(), serde_json::Value::Bool(true));
+            }
+            _ => {
+                options.insert(name.to_string(), serde_json::Value::String(value));
+            }
+        }
+    }
+    positional.extend(thresholds);
+    positional.extend(pipeline::option_arguments(&options).map_err(bad_request)?);
+    let pass = cli::PassOnly::parse_pass(&positional, config).map_err(bad_request)?;
+
+    let max_upload = args.max_upload * 1024 * 1024;
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(max_upload as u64 + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| bad_request(e.to_string()))?;
+    if body.len() > max_upload {
+        return Err((413, "the image is bigger than --max-upload".to_string()));
+    }
+
+    let mut image = decode(&body, args.max_megapixels)?;
+    pass.apply(&mut image);
+    let sorted = encode_image(
+        &image,
+        Path::new("-"),
+        Some(format),
+        &EncodeOptions::default(),
+    )
+    .map_err(|e| (500, e))?;
+    Ok((sorted, content_type(format)))
+}
+
+/// decodes an uploaded image, refusing ones of more than `max_megapixels` before they're
+/// decoded
+fn decode(body: &[u8], max_megapixels: u64) -> Result<egui::ColorImage, (u16, String)> {
+    let too_big = || {
+        let message = format!("the image is bigger than {} megapixels", max_megapixels);
+        (413, message)
+    };
+    let cannot_load = |e: image::ImageError| match e {
+        image::ImageError::Limits(_) => too_big(),
+        e => (400, format!("cannot load the image: {}", e)),
+    };
+    let reader = || {
+        image::io::Reader::new(Cursor::new(body))
+            .with_guessed_format()
+            .map_err(|e| (400, format!("cannot load the image: {}", e)))
+    };
+
+    let max_pixels = max_megapixels * 1_000_000;
+    let (width, height) = reader()?.into_dimensions().map_err(cannot_load)?;
+    if width as u64 * height as u64 > max_pixels {
+        return Err(too_big());
+    }
+    let mut reader = reader()?;
+    let mut limits = image::io::Limits::default();
+    // in case the header lies, a pixel takes at most 16 bytes, four 32 bit floats
+    limits.max_alloc = Some(max_pixels * 16);
+    reader.limits(limits);
+    reader.decode().map(color_image).map_err(cannot_load)
+}
+
+/// decodes `%xx` escapes, `+` is kept as it is since it's part of weighted keys
+fn percent_decode(value: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        let escaped = (byte == b'%')
+            .then(|| tail.get(..2))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(escaped) => {
+                bytes.push(escaped);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(byte);
+                rest = tail;
+            }
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+fn content_type(format: image::ImageFormat) -> &'static str {
+    match format {
+        image::ImageFormat::Png => "image/png",
+        image::ImageFormat::Jpeg => "image/jpeg",
+        image::ImageFormat::Gif => "image/gif",
+        image::ImageFormat::WebP => "image/webp",
+        image::ImageFormat::Avif => "image/avif",
+        image::ImageFormat::Bmp => "image/bmp",
+        image::ImageFormat::Tiff => "image/tiff",
+        image::ImageFormat::Ico => "image/x-icon",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,152 @@
+//! the gui keeps its session in `~/.config/porter/session/` as it goes, so it can be picked
+//! up again after a crash: the image shown, its passes with the region masks and control
+//! images they use, and the zoom
+use crate::preset::{self, Preset};
+use crate::{cli, config, pipeline};
+use psorter::intervals::Traversal;
+use psorter::io::{basename, load_image_from_path};
+use psorter::sort::Pass;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+pub fn directory() -> Option<PathBuf> {
+    dirs::config_dir().map(|directory| directory.join("porter").join("session"))
+}
+
+/// the form of `session.json`, the passes in the form of presets
+#[derive(Serialize, Deserialize)]
+pub struct Saved {
+    pub image: PathBuf,
+    /// the earlier passes, then the settings shown
+    passes: Vec<Preset>,
+    zoom: Option<f32>,
+    pan: [f32; 2],
+}
+
+pub struct Session {
+    /// where the image was opened from, none when it only ever was in memory
+    pub image_path: Option<PathBuf>,
+    /// saved along when there's no path to it
+    pub image: Option<egui::ColorImage>,
+    pub passes: Vec<Pass>,
+    pub pass: Pass,
+    pub script_source: String,
+    pub zoom: Option<f32>,
+    pub pan: egui::Vec2,
+}
+
+/// a saved session read back
+pub struct Restored {
+    pub image: egui::ColorImage,
+    pub image_path: PathBuf,
+    pub passes: Vec<Pass>,
+    pub pass: Pass,
+    /// of the passes sorting by a script
+    pub script_source: Option<String>,
+    pub zoom: Option<f32>,
+    pub pan: egui::Vec2,
+}
+
+impl Session {
+    /// replaces the session saved before
+    pub fn save(self) -> Result<(), String> {
+        let directory = directory().ok_or("cannot find the config directory")?;
+        std::fs::create_dir_all(&directory).map_err(|e| e.to_string())?;
+
+        let image = match (self.image_path, &self.image) {
+            (Some(path), _) => path,
+            (None, Some(image)) => {
+                let path = directory.join("image.png");
+                preset::write_png(image, &path)?;
+                path
+            }
+            (None, None) => return Err("the session has no image".to_string()),
+        };
+        let passes = self
+            .passes
+            .iter()
+            .chain([&self.pass])
+            .enumerate()
+            .map(|(i, pass)| {
+                to_preset(
+                    pass,
+                    &self.script_source,
+                    &directory,
+                    &format!("pass-{}", i + 1),
+                )
+            })
+            .collect::<Result<_, _>>()?;
+        let saved = Saved {
+            image,
+            passes,
+            zoom: self.zoom,
+            pan: [self.pan.x, self.pan.y],
+        };
+        let source = serde_json::to_string_pretty(&saved).map_err(|e| e.to_string())?;
+        std::fs::write(directory.join("session.json"), source).map_err(|e| e.to_string())
+    }
+}
+
+/// none when no session was saved
+pub fn load() -> Result<Option<Saved>, String> {
+    let Some(path) = directory()
+        .map(|directory| directory.join("session.json"))
+        .filter(|path| path.exists())
+    else {
+        return Ok(None);
+    };
+    let source = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    serde_json::from_str(&source)
+        .map(Some)
+        .map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+impl Saved {
+    /// loads the image and the files the passes use, the key and the thresholds of `config`
+    /// filling in for the ones missing
+    pub fn restore(&self, config: &config::Config) -> Result<Restored, String> {
+        let path = self.image.display().to_string();
+        let image =
+            load_image_from_path(&path).map_err(|e| format!("cannot load {}: {}", path, e))?;
+        let mut script_source = None;
+        let mut passes = Vec::new();
+        for preset in &self.passes {
+            let args = pipeline::pass_arguments(preset)?;
+            passes.push(cli::PassOnly::parse_pass(&args, config)?);
+            if let Some(source) = preset.get("key-script").and_then(|s| s.as_str()) {
+                script_source = Some(source.to_string());
+            }
+        }
+        let pass = passes.pop().ok_or("the session has no settings")?;
+        Ok(Restored {
+            image,
+            image_path: self.image.clone(),
+            passes,
+            pass,
+            script_source,
+            zoom: self.zoom,
+            pan: egui::vec2(self.pan[0], self.pan[1]),
+        })
+    }
+
+    /// the name of the image, to tell which session it is
+    pub fn image_name(&self) -> String {
+        let path = self.image.display().to_string();
+        basename(&path).unwrap_or(path)
+    }
+}
+
+/// `pass` as a preset, its region mask and control image written next to the session. svg
+/// paths are left out, they'd need the file they came from
+fn to_preset(
+    pass: &Pass,
+    script_source: &str,
+    directory: &Path,
+    name: &str,
+) -> Result<Preset, String> {
+    let mut pass = pass.clone();
+    if let Traversal::Svg(_) = pass.traversal {
+        pass.traversal = Traversal::Rows;
+    }
+    preset::from_pass_with_files(&pass, script_source, directory, name)
+}
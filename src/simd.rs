@@ -0,0 +1,210 @@
+//! the luminance, hue and saturation of many pixels at once, four at a time with SSE2 on
+//! x86_64 where it's always there, as long as the four are opaque. other targets and the
+//! pixels left over use the functions of `keys`, the values are the same either way. the
+//! values are appended to `values`
+use crate::keys::{self, straight};
+use egui::Color32;
+use image::Rgba;
+
+pub fn luminance(pixels: &[Color32], values: &mut Vec<u16>) {
+    #[cfg(target_arch = "x86_64")]
+    by_fours(pixels, values, sse2::luminance, keys::luminance);
+    #[cfg(not(target_arch = "x86_64"))]
+    values.extend(pixels.iter().map(|pixel| keys::luminance(straight(pixel))));
+}
+
+pub fn hue(pixels: &[Color32], values: &mut Vec<u16>) {
+    #[cfg(target_arch = "x86_64")]
+    by_fours(pixels, values, sse2::hue, keys::hue);
+    #[cfg(not(target_arch = "x86_64"))]
+    values.extend(pixels.iter().map(|pixel| keys::hue(straight(pixel))));
+}
+
+pub fn saturation(pixels: &[Color32], values: &mut Vec<u16>) {
+    #[cfg(target_arch = "x86_64")]
+    by_fours(pixels, values, sse2::saturation, keys::saturation);
+    #[cfg(not(target_arch = "x86_64"))]
+    values.extend(pixels.iter().map(|pixel| keys::saturation(straight(pixel))));
+}
+
+/// the channels of opaque pixels are the same premultiplied or not, the others are
+/// unpremultiplied one by one
+#[cfg(target_arch = "x86_64")]
+fn by_fours(
+    pixels: &[Color32],
+    values: &mut Vec<u16>,
+    four_keys: fn(&[Color32; 4]) -> [u16; 4],
+    key: fn(Rgba<u8>) -> u16,
+) {
+    values.reserve(pixels.len());
+    let mut fours = pixels.chunks_exact(4);
+    for four in &mut fours {
+        if four.iter().all(|pixel| pixel.a() == 255) {
+            values.extend(four_keys(four.try_into().expect("chunks of four")));
+        } else {
+            values.extend(four.iter().map(|pixel| key(straight(pixel))));
+        }
+    }
+    values.extend(fours.remainder().iter().map(|pixel| key(straight(pixel))));
+}
+
+/// every function follows the steps of its scalar version in `keys`, so the rounding is the
+/// same. SSE2 is part of x86_64, so its intrinsics are always safe to call there
+#[cfg(target_arch = "x86_64")]
+mod sse2 {
+    use egui::Color32;
+    use std::arch::x86_64::*;
+
+    /// red, green and blue of four pixels, a pixel in every 32 bit lane
+    fn channels(pixels: &[Color32; 4]) -> [__m128i; 3] {
+        // SAFETY: the load is an unaligned one of the 16 bytes of the pixels
+        unsafe {
+            let pixels = _mm_loadu_si128(pixels.as_ptr().cast());
+            let byte = _mm_set1_epi32(0xff);
+            [
+                _mm_and_si128(pixels, byte),
+                _mm_and_si128(_mm_srli_epi32::<8>(pixels), byte),
+                _mm_and_si128(_mm_srli_epi32::<16>(pixels), byte),
+            ]
+        }
+    }
+
+    fn float_channels(pixels: &[Color32; 4]) -> [__m128; 3] {
+        // SAFETY: see the module
+        channels(pixels).map(|channel| unsafe { _mm_cvtepi32_ps(channel) })
+    }
+
+    fn lanes(keys: __m128i) -> [u16; 4] {
+        let mut lanes = [0i32; 4];
+        // SAFETY: the store is an unaligned one into the 16 bytes of `lanes`
+        unsafe { _mm_storeu_si128(lanes.as_mut_ptr().cast(), keys) };
+        lanes.map(|lane| lane as u16)
+    }
+
+    pub fn luminance(pixels: &[Color32; 4]) -> [u16; 4] {
+        let [red, green, blue] = channels(pixels);
+        // SAFETY: see the module
+        lanes(unsafe {
+            let sum = _mm_add_epi32(_mm_add_epi32(red, green), blue);
+            // a third is (sum * 43691) >> 17 for sums up to 765, which fit in the lower halves
+            // of the lanes
+            let high_bits = _mm_mulhi_epu16(sum, _mm_set1_epi32(43691));
+            _mm_srli_epi32::<1>(high_bits)
+        })
+    }
+
+    pub fn hue(pixels: &[Color32; 4]) -> [u16; 4] {
+        let [red, green, blue] = float_channels(pixels);
+        // SAFETY: see the module
+        lanes(unsafe {
+            let min = _mm_min_ps(blue, _mm_min_ps(red, green));
+            let max = _mm_max_ps(blue, _mm_max_ps(red, green));
+            let range = _mm_sub_ps(max, min);
+
+            let from_red = _mm_div_ps(_mm_sub_ps(green, blue), range);
+            let from_green = _mm_add_ps(_mm_set1_ps(2.0), _mm_div_ps(_mm_sub_ps(blue, red), range));
+            let from_blue = _mm_add_ps(_mm_set1_ps(4.0), _mm_div_ps(_mm_sub_ps(red, green), range));
+            let is_red = _mm_cmpeq_ps(max, red);
+            let is_green = _mm_andnot_ps(is_red, _mm_cmpeq_ps(max, green));
+            let hue = _mm_or_ps(
+                _mm_or_ps(
+                    _mm_and_ps(is_red, from_red),
+                    _mm_and_ps(is_green, from_green),
+                ),
+                _mm_andnot_ps(_mm_or_ps(is_red, is_green), from_blue),
+            );
+            let hue = _mm_mul_ps(hue, _mm_set1_ps(60.0));
+
+            let negative = _mm_cmplt_ps(hue, _mm_setzero_ps());
+            let hue = _mm_add_ps(hue, _mm_and_ps(negative, _mm_set1_ps(360.0)));
+
+            // grays have no hue, their divisions by zero are dropped here
+            let gray = _mm_castps_si128(_mm_cmpeq_ps(max, min));
+            _mm_andnot_si128(gray, _mm_cvttps_epi32(hue))
+        })
+    }
+
+    pub fn saturation(pixels: &[Color32; 4]) -> [u16; 4] {
+        let [red, green, blue] = float_channels(pixels);
+        // SAFETY: see the module
+        lanes(unsafe {
+            let full = _mm_set1_ps(255.0);
+            let red = _mm_div_ps(red, full);
+            let green = _mm_div_ps(green, full);
+            let blue = _mm_div_ps(blue, full);
+
+            let min = _mm_min_ps(blue, _mm_min_ps(red, green));
+            let max = _mm_max_ps(blue, _mm_max_ps(red, green));
+
+            let one = _mm_set1_ps(1.0);
+            let luminance = _mm_div_ps(_mm_add_ps(max, min), _mm_set1_ps(2.0));
+            let distance = _mm_sub_ps(_mm_mul_ps(_mm_set1_ps(2.0), luminance), one);
+            let saturation = _mm_sub_ps(one, _mm_andnot_ps(_mm_set1_ps(-0.0), distance));
+
+            let gray = _mm_castps_si128(_mm_cmpeq_ps(max, min));
+            _mm_andnot_si128(gray, _mm_cvttps_epi32(_mm_mul_ps(saturation, full)))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type Batch = fn(&[Color32], &mut Vec<u16>);
+    type Scalar = fn(Rgba<u8>) -> u16;
+
+    const KEYS: [(&str, Batch, Scalar); 3] = [
+        ("luminance", luminance, keys::luminance),
+        ("hue", hue, keys::hue),
+        ("saturation", saturation, keys::saturation),
+    ];
+
+    fn check(pixels: &[Color32]) {
+        for (name, batch, scalar) in KEYS {
+            let mut values = Vec::new();
+            batch(pixels, &mut values);
+            for (pixel, value) in pixels.iter().zip(values) {
+                assert_eq!(value, scalar(straight(pixel)), "{} of {:?}", name, pixel);
+            }
+        }
+    }
+
+    #[test]
+    fn opaque_pixels_match_the_scalar_keys() {
+        // every third level of every channel, the ends included
+        let levels: Vec<u8> = (0..=255).step_by(3).collect();
+        let pixels: Vec<Color32> = levels
+            .iter()
+            .flat_map(|&r| {
+                let levels = &levels;
+                levels
+                    .iter()
+                    .flat_map(move |&g| levels.iter().map(move |&b| Color32::from_rgb(r, g, b)))
+            })
+            .collect();
+        assert_eq!(pixels.len() % 4, 0);
+        check(&pixels);
+    }
+
+    #[test]
+    fn pixels_left_over_match_the_scalar_keys() {
+        let pixels: Vec<Color32> = (0..7)
+            .map(|i| Color32::from_rgb(i * 40, 255 - i * 30, i * i))
+            .collect();
+        for length in 0..pixels.len() {
+            check(&pixels[..length]);
+        }
+    }
+
+    #[test]
+    fn translucent_pixels_match_the_scalar_keys() {
+        let pixels: Vec<Color32> = (0..64)
+            .map(|i| {
+                let alpha = if i % 5 == 0 { 255 } else { i * 4 };
+                Color32::from_rgba_unmultiplied(i * 4, 255 - i * 2, 128, alpha)
+            })
+            .collect();
+        check(&pixels);
+    }
+}
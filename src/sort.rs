@@ -0,0 +1,935 @@
+//! sorting whole images, one pass at a time
+use crate::hdr::HdrImage;
+use crate::intervals::{
+    block_paths, into_intervals, process_intervals, selection_mask, selection_values,
+    traversal_paths, IntervalOperation, IntervalOptions, IntervalSource, SmearPixel, Thresholds,
+    Traversal,
+};
+use crate::io::straight_rgba;
+use crate::keys::{key_histogram, luminance, straight, AutoThreshold, SortBy};
+use crate::mask::{MaskOperation, Region};
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+use rayon::prelude::*;
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use web_time::Instant;
+
+/// the direction pixels are sorted in
+#[derive(Clone, Default)]
+pub struct SortOrder {
+    /// highest keys first
+    pub descending: bool,
+    /// reverse every other interval of a path
+    pub zigzag: bool,
+    /// sort every other path (every other row when sorting rows) in the opposite direction
+    pub alternate_paths: bool,
+    /// orders pixels with the same primary key
+    pub tie_breaker: Option<SortBy>,
+}
+
+/// mixes the original pixels back into the sorted image where the selection is partial,
+/// only does anything for soft masks
+pub(crate) fn blend_soft_mask(
+    thresholds: &Thresholds,
+    original: &egui::ColorImage,
+    sorted: &mut egui::ColorImage,
+    sorting_method: &SortBy,
+) {
+    if !thresholds.soft {
+        return;
+    }
+
+    let values = selection_values(thresholds, original, sorting_method);
+    mix_images(original, sorted, values);
+}
+
+/// linearly blends the sorted image with the original, 1 keeps it as is and 0 undoes the sorting
+pub(crate) fn apply_strength(
+    original: &egui::ColorImage,
+    sorted: &mut egui::ColorImage,
+    strength: f32,
+) {
+    if strength < 1.0 {
+        mix_images(original, sorted, std::iter::repeat(strength));
+    }
+}
+
+/// mixes every pixel of `sorted` with the one of `original` by the matching weight
+pub(crate) fn mix_images(
+    original: &egui::ColorImage,
+    sorted: &mut egui::ColorImage,
+    weights: impl IntoIterator<Item = f32>,
+) {
+    for ((pixel, original), value) in sorted.pixels.iter_mut().zip(&original.pixels).zip(weights) {
+        let value = value.clamp(0.0, 1.0);
+        let mix =
+            |from: u8, to: u8| (from as f32 + (to as f32 - from as f32) * value).round() as u8;
+        *pixel = egui::Color32::from_rgba_premultiplied(
+            mix(original.r(), pixel.r()),
+            mix(original.g(), pixel.g()),
+            mix(original.b(), pixel.b()),
+            mix(original.a(), pixel.a()),
+        );
+    }
+}
+
+/// `mix_images` for the pixels of an `HdrImage`
+fn mix_linear(
+    original: &[[f32; 4]],
+    sorted: &mut [[f32; 4]],
+    weights: impl IntoIterator<Item = f32>,
+) {
+    for ((pixel, original), value) in sorted.iter_mut().zip(original).zip(weights) {
+        let value = value.clamp(0.0, 1.0);
+        for (channel, original) in pixel.iter_mut().zip(original) {
+            *channel = original + (*channel - original) * value;
+        }
+    }
+}
+
+/// sorts the intervals `thresholds` selects along `traversal`, split into blocks of
+/// `block_size` pixels when given
+pub fn sort_image(
+    thresholds: &Thresholds,
+    image: &mut egui::ColorImage,
+    sorting_method: &SortBy,
+    traversal: &Traversal,
+    block_size: Option<usize>,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+) {
+    let paths = numbered_paths(image, traversal, block_size);
+    sort_paths(
+        thresholds,
+        image,
+        sorting_method,
+        paths,
+        order,
+        interval_options,
+        &|| true,
+    );
+}
+
+/// the paths of `traversal` along with their index, which picks their random stream and
+/// whether they're alternated
+pub(crate) fn numbered_paths(
+    image: &egui::ColorImage,
+    traversal: &Traversal,
+    block_size: Option<usize>,
+) -> Vec<(usize, Vec<usize>)> {
+    let paths = match block_size {
+        Some(size) => block_paths(traversal, image.width(), image.height(), size.max(1)),
+        None => traversal_paths(traversal, image.width(), image.height()),
+    };
+    paths.into_iter().enumerate().collect()
+}
+
+/// false when `path_done` gave up on the paths left, see `move_pixels`
+fn sort_paths(
+    thresholds: &Thresholds,
+    image: &mut egui::ColorImage,
+    sorting_method: &SortBy,
+    paths: Vec<(usize, Vec<usize>)>,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+    path_done: PathDone,
+) -> bool {
+    let mask = selection_mask(thresholds, image, sorting_method);
+    move_pixels(
+        &mut image.pixels,
+        &mask,
+        sorting_method,
+        paths,
+        order,
+        interval_options,
+        path_done,
+    )
+}
+
+/// called after every path is sorted, the paths left are given up once it returns false
+pub(crate) type PathDone<'a> = &'a (dyn Fn() -> bool + Sync);
+
+/// what pixels are sorted by, so images of other pixels than `Color32` can be sorted
+pub(crate) trait SortPixel: Copy + Default + Send + Sync {
+    /// the color keys are computed on
+    fn color(&self) -> egui::Color32;
+}
+
+impl SortPixel for egui::Color32 {
+    fn color(&self) -> egui::Color32 {
+        *self
+    }
+}
+
+/// a pixel of an `HdrImage`, sorted by its color in the key image
+#[derive(Clone, Copy, Default)]
+struct HdrPixel {
+    color: egui::Color32,
+    linear: [f32; 4],
+}
+
+impl SortPixel for HdrPixel {
+    fn color(&self) -> egui::Color32 {
+        self.color
+    }
+}
+
+/// sorts, shuffles or smears the intervals of `mask` along `paths`. false when `path_done`
+/// gave up, the paths it didn't get to are left as they are
+fn move_pixels<P: SortPixel>(
+    pixels: &mut [P],
+    mask: &[bool],
+    sorting_method: &SortBy,
+    paths: Vec<(usize, Vec<usize>)>,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+    path_done: PathDone,
+) -> bool {
+    let given_up = AtomicBool::new(false);
+    // paths never share pixels, so they're sorted in parallel and written back afterwards
+    let source = &*pixels;
+    let sorted: Vec<(Vec<usize>, Vec<P>)> = paths
+        .into_par_iter()
+        .map_init(Scratch::default, |scratch, (path_index, mut path)| {
+            if given_up.load(Ordering::Relaxed) {
+                return (Vec::new(), Vec::new());
+            }
+            if interval_options.wrap_around {
+                // starting the path at an unselected pixel joins the intervals touching both ends
+                if let Some(first_unselected) = path.iter().position(|&i| !mask[i]) {
+                    path.rotate_left(first_unselected);
+                }
+            }
+
+            let mut pixels: Vec<P> = path.iter().map(|&i| source[i]).collect();
+            sort_path(
+                &mut pixels,
+                path.iter().map(|&i| mask[i]).collect(),
+                path_index,
+                sorting_method,
+                order,
+                interval_options,
+                scratch,
+            );
+            if !path_done() {
+                given_up.store(true, Ordering::Relaxed);
+            }
+            (path, pixels)
+        })
+        .collect();
+
+    for (path, sorted) in sorted {
+        for (i, pixel) in path.into_iter().zip(sorted) {
+            pixels[i] = pixel;
+        }
+    }
+    !given_up.into_inner()
+}
+
+/// buffers the intervals of a thread reuse, instead of allocating them for every interval
+#[derive(Default)]
+struct Scratch<P> {
+    interval: Vec<P>,
+    sorted: Vec<P>,
+    /// the colors of `interval`, for computing keys
+    colors: Vec<egui::Color32>,
+    keys: Vec<u16>,
+    /// positions in `interval`, in the order it's sorted in
+    order: Vec<usize>,
+    spare_order: Vec<usize>,
+    counts: Vec<usize>,
+}
+
+impl<P: SortPixel> Scratch<P> {
+    /// sorts `interval` by the key, then the tie breaker. keys are mostly small so they're
+    /// counted instead of compared, the tie breaker first since the last sort decides the order
+    fn sort(&mut self, sorting_method: &SortBy, tie_breaker: Option<&SortBy>) {
+        self.order.clear();
+        self.order.extend(0..self.interval.len());
+        self.colors.clear();
+        self.colors
+            .extend(self.interval.iter().map(SortPixel::color));
+        for key in tie_breaker.into_iter().chain([sorting_method]) {
+            key.keys(&self.colors, &mut self.keys);
+            self.counting_sort();
+        }
+
+        self.sorted.clear();
+        self.sorted
+            .extend(self.order.iter().map(|&position| self.interval[position]));
+        std::mem::swap(&mut self.interval, &mut self.sorted);
+    }
+
+    /// sorts `order` by `keys`, keeping the order of equal keys
+    fn counting_sort(&mut self) {
+        let keys = &self.keys;
+        let highest = keys.iter().copied().max().unwrap_or(0) as usize;
+        // counting costs a step for every possible key, too many for short intervals with
+        // keys going up to 65535
+        if highest > keys.len() * 16 {
+            self.order.sort_by_key(|&position| keys[position]);
+            return;
+        }
+        // where every key starts once sorted, shifted by one while counting
+        self.counts.clear();
+        self.counts.resize(highest + 2, 0);
+        for &key in keys {
+            self.counts[key as usize + 1] += 1;
+        }
+        for key in 1..self.counts.len() {
+            self.counts[key] += self.counts[key - 1];
+        }
+
+        self.spare_order.clear();
+        self.spare_order.resize(self.order.len(), 0);
+        for &position in &self.order {
+            let start = &mut self.counts[keys[position] as usize];
+            self.spare_order[*start] = position;
+            *start += 1;
+        }
+        std::mem::swap(&mut self.order, &mut self.spare_order);
+    }
+}
+
+/// sorts the pixels along one path, `selected` tells which of them intervals are made of
+fn sort_path<P: SortPixel>(
+    pixels: &mut [P],
+    selected: Vec<bool>,
+    path_index: usize,
+    sorting_method: &SortBy,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+    scratch: &mut Scratch<P>,
+) {
+    let reversed_path = order.alternate_paths && path_index % 2 == 1;
+
+    // every path gets its own stream so the result doesn't depend on the processing order
+    let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(interval_options.seed);
+    rng.set_stream(path_index as u64);
+
+    let intervals = process_intervals(into_intervals(selected), interval_options, &mut rng);
+
+    // shifted intervals are written last so they aren't picked up by the following ones
+    let mut shifted = Vec::new();
+
+    for (interval_index, (start, end)) in intervals.into_iter().enumerate() {
+        scratch.interval.clear();
+        scratch.interval.extend_from_slice(&pixels[start..end]);
+
+        match interval_options.operation {
+            IntervalOperation::Sort => {
+                scratch.sort(sorting_method, order.tie_breaker.as_ref());
+
+                let reversed_interval = order.zigzag && interval_index % 2 == 1;
+                if order.descending ^ reversed_path ^ reversed_interval {
+                    scratch.interval.reverse();
+                }
+            }
+            IntervalOperation::Shuffle => scratch.interval.shuffle(&mut rng),
+            IntervalOperation::Smear(smear_pixel) => {
+                let pixel = match smear_pixel {
+                    SmearPixel::First => scratch.interval[0],
+                    SmearPixel::Brightest => *scratch
+                        .interval
+                        .iter()
+                        .max_by_key(|pixel| luminance(straight(&pixel.color())))
+                        .expect("intervals are never empty"),
+                };
+                scratch.interval.fill(pixel);
+            }
+        }
+
+        let offset = match interval_options.shift {
+            0 => 0,
+            shift if interval_options.random_shift => rng.gen_range(-shift.abs()..=shift.abs()),
+            shift => shift,
+        };
+
+        if offset == 0 {
+            pixels[start..end].copy_from_slice(&scratch.interval);
+        } else {
+            for (k, &pixel) in scratch.interval.iter().enumerate() {
+                let position = (start + k) as isize + offset;
+                if (0..pixels.len() as isize).contains(&position) {
+                    shifted.push((position as usize, pixel));
+                }
+            }
+        }
+    }
+
+    for (position, pixel) in shifted {
+        pixels[position] = pixel;
+    }
+}
+
+/// one channel of `image` as a gray image, with alpha taken out of the colors
+fn channel_plane(image: &egui::ColorImage, channel: usize) -> egui::ColorImage {
+    egui::ColorImage {
+        size: image.size,
+        pixels: image
+            .pixels
+            .iter()
+            .map(|pixel| {
+                let value = straight(pixel)[channel];
+                egui::Color32::from_rgb(value, value, value)
+            })
+            .collect(),
+    }
+}
+
+/// the thresholds and the order of a pass for the planes of per channel sorting. a plane is
+/// gray, so it's selected and ordered by the level of its channel whatever the keys are,
+/// control images are still compared by the threshold key
+fn plane_settings(thresholds: &Thresholds, order: &SortOrder) -> (Thresholds, SortOrder) {
+    let mut thresholds = thresholds.clone();
+    if !matches!(thresholds.source, IntervalSource::Control(_)) {
+        thresholds.key = None;
+    }
+    let order = SortOrder {
+        tie_breaker: None,
+        ..order.clone()
+    };
+    (thresholds, order)
+}
+
+/// sorts the red, green and blue planes separately, each with intervals of its own,
+/// so the channels drift apart. every plane is sorted by the level of its channel, see
+/// `SortBy::is_gray_level` for the keys that mean the same
+pub fn sort_image_per_channel(
+    thresholds: &Thresholds,
+    image: &mut egui::ColorImage,
+    traversal: &Traversal,
+    block_size: Option<usize>,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+) {
+    let paths = numbered_paths(image, traversal, block_size);
+    sort_paths_per_channel(thresholds, image, paths, order, interval_options, &|| true);
+}
+
+/// `path_done` is called for the paths of every plane, false when it gave up and the image
+/// is left as it is
+fn sort_paths_per_channel(
+    thresholds: &Thresholds,
+    image: &mut egui::ColorImage,
+    paths: Vec<(usize, Vec<usize>)>,
+    order: &SortOrder,
+    interval_options: &IntervalOptions,
+    path_done: PathDone,
+) -> bool {
+    let (thresholds, order) = plane_settings(thresholds, order);
+    let mut planes = Vec::with_capacity(3);
+    for channel in 0..3 {
+        let mut plane = channel_plane(image, channel);
+        if !sort_paths(
+            &thresholds,
+            &mut plane,
+            &SortBy::Luminance,
+            paths.clone(),
+            &order,
+            interval_options,
+            path_done,
+        ) {
+            return false;
+        }
+        planes.push(plane);
+    }
+
+    for (i, pixel) in image.pixels.iter_mut().enumerate() {
+        *pixel = egui::Color32::from_rgba_unmultiplied(
+            planes[0].pixels[i].r(),
+            planes[1].pixels[i].g(),
+            planes[2].pixels[i].b(),
+            pixel.a(),
+        );
+    }
+    true
+}
+
+/// one sorting pass over an image, several of them can be chained
+#[derive(Clone)]
+pub struct Pass {
+    /// which pixels are sorted
+    pub thresholds: Thresholds,
+    /// picks the thresholds for every image separately, replacing the ones above
+    pub auto_threshold: Option<AutoThreshold>,
+    /// the key intervals are sorted by
+    pub sort_by: SortBy,
+    /// the paths intervals are found along
+    pub traversal: Traversal,
+    /// runs the traversal in square tiles of this many pixels instead of the whole image
+    pub block_size: Option<usize>,
+    /// the direction intervals are sorted in
+    pub order: SortOrder,
+    /// how intervals are merged, cut and shifted, and what's done with them
+    pub interval_options: IntervalOptions,
+    /// sorts every channel by its own level, leaving the keys aside
+    pub per_channel: bool,
+    /// from 0 to 1, how much of the sorted image is mixed into the original
+    pub strength: f32,
+}
+
+impl Pass {
+    /// sorts `image` in place
+    pub fn apply(&self, image: &mut egui::ColorImage) {
+        self.apply_with_progress(image, &|_| true);
+    }
+
+    /// `apply`, calling `progress` with the share of the paths sorted so far after every one
+    /// of them. once it returns false the rest is given up and false is returned, `image` is
+    /// then left partly sorted
+    pub fn apply_with_progress(
+        &self,
+        image: &mut egui::ColorImage,
+        progress: &(dyn Fn(f32) -> bool + Sync),
+    ) -> bool {
+        let paths = numbered_paths(image, &self.traversal, self.block_size);
+        // every plane goes over the paths when sorting per channel
+        let total = paths.len() * if self.per_channel { 3 } else { 1 };
+        let done = AtomicUsize::new(0);
+        let path_done = || {
+            let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+            progress(done as f32 / total as f32)
+        };
+        self.apply_to_paths(image, paths, &path_done)
+    }
+
+    /// which pixels of `image` the pass may sort, before they're split into intervals
+    pub fn selection(&self, image: &egui::ColorImage) -> Vec<bool> {
+        selection_mask(&self.thresholds_for(image), image, &self.sort_by)
+    }
+
+    /// the thresholds of the pass, picked for `image` when they're automatic
+    fn thresholds_for(&self, image: &egui::ColorImage) -> Thresholds {
+        let mut thresholds = self.thresholds.clone();
+        if let Some(auto_threshold) = &self.auto_threshold {
+            let key = thresholds.key.as_ref().unwrap_or(&self.sort_by);
+            (thresholds.lower, thresholds.higher) = auto_threshold.thresholds(&key_histogram(
+                thresholds.source.threshold_image(image),
+                key,
+            ));
+        }
+        thresholds
+    }
+
+    /// sorts a high dynamic range image without tone mapping it, keys and selections see it
+    /// as its `key_image`
+    pub fn apply_hdr(&self, image: &mut HdrImage) {
+        let key_image = image.key_image();
+        self.apply_linear(image, &key_image);
+    }
+
+    /// sorts `image` by the colors of `key_image`, the same image in other colors, like the
+    /// colors of a wide gamut image converted to sRGB
+    pub fn apply_with_keys(&self, image: &mut egui::ColorImage, key_image: &egui::ColorImage) {
+        let mut linear = HdrImage::from_color_image(image);
+        self.apply_linear(&mut linear, key_image);
+        *image = linear.to_color_image();
+    }
+
+    fn apply_linear(&self, image: &mut HdrImage, key_image: &egui::ColorImage) {
+        let start = Instant::now();
+        let thresholds = self.thresholds_for(key_image);
+        let paths = numbered_paths(key_image, &self.traversal, self.block_size);
+        let original = image.pixels.clone();
+
+        let (plane_thresholds, plane_order) = plane_settings(&thresholds, &self.order);
+        let sort_plane = |plane: &egui::ColorImage, per_channel: bool| {
+            let (thresholds, sort_by, order) = if per_channel {
+                (&plane_thresholds, &SortBy::Luminance, &plane_order)
+            } else {
+                (&thresholds, &self.sort_by, &self.order)
+            };
+            let mask = selection_mask(thresholds, plane, sort_by);
+            let mut pixels: Vec<HdrPixel> = plane
+                .pixels
+                .iter()
+                .zip(&original)
+                .map(|(&color, &linear)| HdrPixel { color, linear })
+                .collect();
+            move_pixels(
+                &mut pixels,
+                &mask,
+                sort_by,
+                paths.clone(),
+                order,
+                &self.interval_options,
+                &|| true,
+            );
+            pixels
+        };
+        if self.per_channel {
+            let planes: Vec<Vec<HdrPixel>> = (0..3)
+                .map(|channel| sort_plane(&channel_plane(key_image, channel), true))
+                .collect();
+            for (i, pixel) in image.pixels.iter_mut().enumerate() {
+                for (channel, plane) in planes.iter().enumerate() {
+                    pixel[channel] = plane[i].linear[channel];
+                }
+            }
+        } else {
+            for (pixel, sorted) in image.pixels.iter_mut().zip(sort_plane(key_image, false)) {
+                *pixel = sorted.linear;
+            }
+        }
+
+        if thresholds.soft {
+            let values = selection_values(&thresholds, key_image, &self.sort_by);
+            mix_linear(&original, &mut image.pixels, values);
+        }
+        if self.strength < 1.0 {
+            mix_linear(
+                &original,
+                &mut image.pixels,
+                std::iter::repeat(self.strength),
+            );
+        }
+        log::debug!(
+            "sorted a pass of {}x{} pixels by their key image in {:.2?}",
+            image.size[0],
+            image.size[1],
+            start.elapsed()
+        );
+    }
+
+    /// sorts along `paths` only, which are numbered as in the whole image. false when
+    /// `path_done` gave up
+    pub(crate) fn apply_to_paths(
+        &self,
+        image: &mut egui::ColorImage,
+        paths: Vec<(usize, Vec<usize>)>,
+        path_done: PathDone,
+    ) -> bool {
+        let start = Instant::now();
+        let thresholds = self.thresholds_for(image);
+        let original = (thresholds.soft || self.strength < 1.0).then(|| image.clone());
+
+        let finished = if self.per_channel {
+            sort_paths_per_channel(
+                &thresholds,
+                image,
+                paths,
+                &self.order,
+                &self.interval_options,
+                path_done,
+            )
+        } else {
+            sort_paths(
+                &thresholds,
+                image,
+                &self.sort_by,
+                paths,
+                &self.order,
+                &self.interval_options,
+                path_done,
+            )
+        };
+        if !finished {
+            return false;
+        }
+
+        if let Some(original) = &original {
+            blend_soft_mask(&thresholds, original, image, &self.sort_by);
+            apply_strength(original, image, self.strength);
+        }
+        log::debug!(
+            "sorted a pass of {}x{} pixels in {:.2?}",
+            image.width(),
+            image.height(),
+            start.elapsed()
+        );
+        true
+    }
+}
+
+/// which way rows of pixels are sorted, the common traversals without their options
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    /// along the rows, left to right
+    Horizontal,
+    /// down the columns
+    Vertical,
+}
+
+/// builds a `Pass` one option at a time, starting from luminance between 0 and 255 along
+/// rows, like `PixelSorter::new().key(SortBy::Hue).range(20..=200).run(&mut image)`
+#[derive(Clone)]
+pub struct PixelSorter {
+    pass: Pass,
+}
+
+impl Default for PixelSorter {
+    fn default() -> PixelSorter {
+        PixelSorter {
+            pass: Pass {
+                thresholds: Thresholds {
+                    lower: 0,
+                    higher: 255,
+                    key: None,
+                    invert: false,
+                    source: IntervalSource::Threshold,
+                    mask_operations: Vec::new(),
+                    soft: false,
+                    region: None,
+                },
+                auto_threshold: None,
+                sort_by: SortBy::Luminance,
+                traversal: Traversal::Rows,
+                block_size: None,
+                order: SortOrder::default(),
+                interval_options: IntervalOptions::default(),
+                per_channel: false,
+                strength: 1.0,
+            },
+        }
+    }
+}
+
+impl PixelSorter {
+    /// the defaults, see `PixelSorter`
+    pub fn new() -> PixelSorter {
+        PixelSorter::default()
+    }
+
+    /// the key intervals are sorted by, and compared against the range unless `threshold_by`
+    pub fn key(mut self, key: SortBy) -> PixelSorter {
+        self.pass.sort_by = key;
+        self
+    }
+
+    /// the pixels whose key is within `range` are sorted, in the key's native range (see
+    /// `threshold_upper_boundary`)
+    pub fn range(mut self, range: RangeInclusive<u16>) -> PixelSorter {
+        self.pass.thresholds.lower = *range.start();
+        self.pass.thresholds.higher = *range.end();
+        self
+    }
+
+    /// compares another key than the sort key against the range
+    pub fn threshold_by(mut self, key: SortBy) -> PixelSorter {
+        self.pass.thresholds.key = Some(key);
+        self
+    }
+
+    /// picks the range for every image, replacing the one given
+    pub fn auto_threshold(mut self, auto_threshold: AutoThreshold) -> PixelSorter {
+        self.pass.auto_threshold = Some(auto_threshold);
+        self
+    }
+
+    /// sorts the pixels outside of the range instead
+    pub fn invert(mut self) -> PixelSorter {
+        self.pass.thresholds.invert = true;
+        self
+    }
+
+    /// selects the pixels by something else than their key
+    pub fn source(mut self, source: IntervalSource) -> PixelSorter {
+        self.pass.thresholds.source = source;
+        self
+    }
+
+    /// applied to the selection after the earlier ones
+    pub fn mask(mut self, operation: MaskOperation) -> PixelSorter {
+        self.pass.thresholds.mask_operations.push(operation);
+        self
+    }
+
+    /// blends the sorted pixels in by how much of the selection they're in
+    pub fn soft(mut self) -> PixelSorter {
+        self.pass.thresholds.soft = true;
+        self
+    }
+
+    /// leaves the image outside of `region` as it is
+    pub fn region(mut self, region: Region) -> PixelSorter {
+        self.pass.thresholds.region = Some(region);
+        self
+    }
+
+    /// the paths intervals are found along, rows by default
+    pub fn traversal(mut self, traversal: Traversal) -> PixelSorter {
+        self.pass.traversal = traversal;
+        self
+    }
+
+    /// sorts along rows or columns, see `traversal` for the others
+    pub fn direction(self, direction: Direction) -> PixelSorter {
+        self.traversal(match direction {
+            Direction::Horizontal => Traversal::Rows,
+            Direction::Vertical => Traversal::Lines { angle: 90.0 },
+        })
+    }
+
+    /// runs the traversal in tiles of `size` pixels
+    pub fn blocks(mut self, size: usize) -> PixelSorter {
+        self.pass.block_size = Some(size);
+        self
+    }
+
+    /// highest keys first
+    pub fn descending(mut self) -> PixelSorter {
+        self.pass.order.descending = true;
+        self
+    }
+
+    /// replaces the whole order, along with `descending`
+    pub fn order(mut self, order: SortOrder) -> PixelSorter {
+        self.pass.order = order;
+        self
+    }
+
+    /// shuffles or smears the intervals instead of sorting them
+    pub fn operation(mut self, operation: IntervalOperation) -> PixelSorter {
+        self.pass.interval_options.operation = operation;
+        self
+    }
+
+    /// replaces the whole post-processing of the intervals, along with `operation` and `seed`
+    pub fn intervals(mut self, options: IntervalOptions) -> PixelSorter {
+        self.pass.interval_options = options;
+        self
+    }
+
+    /// the seed of the random choices, the same one gives the same result
+    pub fn seed(mut self, seed: u64) -> PixelSorter {
+        self.pass.interval_options.seed = seed;
+        self
+    }
+
+    /// sorts every channel by its own level, see `sort_image_per_channel`
+    pub fn per_channel(mut self) -> PixelSorter {
+        self.pass.per_channel = true;
+        self
+    }
+
+    /// from 0 to 1, how much of the sorted image is mixed into the original
+    pub fn strength(mut self, strength: f32) -> PixelSorter {
+        self.pass.strength = strength.clamp(0.0, 1.0);
+        self
+    }
+
+    /// the pass, to be applied to many images or chained with others
+    pub fn build(self) -> Pass {
+        self.pass
+    }
+
+    /// sorts `image` in place
+    pub fn run(&self, image: &mut egui::ColorImage) {
+        self.pass.apply(image);
+    }
+
+    /// sorts an image of the image crate in place, translucent colors can move by a level
+    /// since they're premultiplied while sorting
+    pub fn run_rgba(&self, image: &mut image::RgbaImage) {
+        let size = [image.width() as usize, image.height() as usize];
+        let mut colors = egui::ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+        self.pass.apply(&mut colors);
+        image.copy_from_slice(&straight_rgba(&colors.pixels));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// runs `counting_sort` on `keys` and checks it against the standard stable sort
+    fn check_counting_sort(keys: Vec<u16>) {
+        let mut scratch = Scratch::<egui::Color32>::default();
+        scratch.order.extend(0..keys.len());
+        scratch.keys = keys;
+        scratch.counting_sort();
+
+        let mut expected: Vec<usize> = (0..scratch.keys.len()).collect();
+        expected.sort_by_key(|&position| scratch.keys[position]);
+        assert_eq!(scratch.order, expected);
+    }
+
+    #[test]
+    fn counting_sort_matches_a_stable_sort() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        check_counting_sort(Vec::new());
+        check_counting_sort(vec![7]);
+        // few distinct keys, so most of them tie
+        check_counting_sort((0..1000).map(|_| rng.gen_range(0..4)).collect());
+        check_counting_sort((0..1000).map(|_| rng.gen_range(0..=255)).collect());
+        check_counting_sort(vec![0; 100]);
+    }
+
+    #[test]
+    fn counting_sort_falls_back_for_high_keys() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(1);
+        // highest key past 16 times the length, these go through `sort_by_key`
+        check_counting_sort(vec![u16::MAX, 0, u16::MAX, 1]);
+        check_counting_sort((0..100).map(|_| rng.gen_range(0..=u16::MAX)).collect());
+        // right at the limit, still counted
+        check_counting_sort(vec![64, 0, 64, 3]);
+    }
+
+    #[test]
+    fn sort_breaks_ties_with_the_tie_breaker() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(2);
+        // few levels of red so green decides between many of them
+        let interval: Vec<egui::Color32> = (0..500)
+            .map(|_| {
+                let level = rng.gen_range(0..4) * 60;
+                egui::Color32::from_rgb(level, rng.gen_range(0..=level), 0)
+            })
+            .collect();
+        let mut expected = interval.clone();
+        expected.sort_by_key(|pixel| (SortBy::Red.key(pixel), SortBy::Green.key(pixel)));
+
+        let mut scratch = Scratch {
+            interval,
+            ..Default::default()
+        };
+        scratch.sort(&SortBy::Red, Some(&SortBy::Green));
+        assert_eq!(scratch.interval, expected);
+    }
+
+    #[test]
+    fn directions_sort_rows_and_columns() {
+        // a single bright pixel in the corner of a dark image
+        let mut image = image::RgbaImage::from_pixel(3, 3, image::Rgba([0, 0, 0, 255]));
+        image.put_pixel(0, 0, image::Rgba([255, 255, 255, 255]));
+        let sorted = |direction| {
+            let mut image = image.clone();
+            PixelSorter::new().direction(direction).run_rgba(&mut image);
+            image
+        };
+        let white = image::Rgba([255, 255, 255, 255]);
+        assert_eq!(*sorted(Direction::Horizontal).get_pixel(2, 0), white);
+        assert_eq!(*sorted(Direction::Vertical).get_pixel(0, 2), white);
+    }
+
+    #[test]
+    fn progress_counts_paths_and_gives_up() {
+        let image = egui::ColorImage::new([8, 6], egui::Color32::RED);
+        for per_channel in [false, true] {
+            let pass = PixelSorter::new().range(0..=255).build();
+            let pass = Pass {
+                per_channel,
+                ..pass
+            };
+            let calls = AtomicUsize::new(0);
+            let last = std::sync::Mutex::new(0.0);
+            assert!(pass.apply_with_progress(&mut image.clone(), &|share| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                *last.lock().unwrap() = share;
+                true
+            }));
+            assert_eq!(calls.into_inner(), if per_channel { 18 } else { 6 });
+            assert_eq!(last.into_inner().unwrap(), 1.0);
+
+            let calls = AtomicUsize::new(0);
+            assert!(!pass.apply_with_progress(&mut image.clone(), &|_| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                false
+            }));
+            // every thread finishes the path it's on
+            assert!(calls.into_inner() <= rayon::current_num_threads());
+        }
+    }
+}
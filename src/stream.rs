@@ -0,0 +1,146 @@
+//! sorting images too big to be loaded whole, a band of rows at a time
+use crate::icc::iccp_data;
+use crate::intervals::{row_paths, IntervalSource, Traversal};
+use crate::io::{straight_rgba, PngCompression};
+use crate::sort::Pass;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
+
+/// roughly how many bytes sorting takes for every pixel: the decoded image and its copies,
+/// the position of the pixel on its path, the selection and the sorted pixels
+pub const BYTES_PER_PIXEL: usize = 32;
+
+/// how many rows of `width` pixels can be sorted at once in `max_memory` bytes, at least one
+pub fn band_height(width: usize, max_memory: usize) -> usize {
+    (max_memory / (width.max(1) * BYTES_PER_PIXEL)).max(1)
+}
+
+/// whether every row of `pass` is sorted on its own, so sorting band by band gives the same
+/// result as sorting the whole image
+pub fn check_bands(pass: &Pass) -> Result<(), String> {
+    if !matches!(pass.traversal, Traversal::Rows) || pass.block_size.is_some() {
+        return Err("only rows can be sorted in bands".to_string());
+    }
+    if !matches!(pass.thresholds.source, IntervalSource::Threshold) {
+        return Err("noise, edges and control images need the whole image".to_string());
+    }
+    if !pass.thresholds.mask_operations.is_empty() {
+        return Err("mask operations need the whole image".to_string());
+    }
+    if pass.thresholds.region.is_some() {
+        return Err("regions need the whole image".to_string());
+    }
+    if pass.auto_threshold.is_some() {
+        return Err("automatic thresholds need the whole image".to_string());
+    }
+    Ok(())
+}
+
+/// sorts an image of `size` pixels `band_height` rows at a time. `read_rows` fills in the
+/// pixels of the next rows and `write_rows` gets them back sorted
+pub fn sort_bands(
+    passes: &[Pass],
+    size: [usize; 2],
+    band_height: usize,
+    mut read_rows: impl FnMut(&mut [egui::Color32]) -> Result<(), String>,
+    mut write_rows: impl FnMut(&[egui::Color32]) -> Result<(), String>,
+) -> Result<(), String> {
+    for pass in passes {
+        check_bands(pass)?;
+    }
+
+    let [width, height] = size;
+    let mut first_row = 0;
+    while first_row < height {
+        let rows = band_height.max(1).min(height - first_row);
+        let mut band = egui::ColorImage::new([width, rows], egui::Color32::TRANSPARENT);
+        read_rows(&mut band.pixels)?;
+        for pass in passes {
+            // numbered as in the whole image, for the random streams and alternating rows
+            let paths = row_paths(width, rows)
+                .into_iter()
+                .enumerate()
+                .map(|(row, path)| (first_row + row, path))
+                .collect();
+            pass.apply_to_paths(&mut band, paths, &|| true);
+        }
+        write_rows(&band.pixels)?;
+        first_row += rows;
+    }
+    Ok(())
+}
+
+/// sorts the png at `input` into a png at `output`, holding `band_height` rows in memory at
+/// most and keeping its color profile and `exif`. interlaced pngs can't be read a row at a
+/// time
+pub fn sort_png(
+    passes: &[Pass],
+    input: &Path,
+    output: &Path,
+    band_height: usize,
+    exif: Option<&[u8]>,
+    compression: PngCompression,
+) -> Result<(), String> {
+    let file = File::open(input).map_err(|e| format!("cannot open {}: {}", input.display(), e))?;
+    let mut decoder = png::Decoder::new(BufReader::new(file));
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    if reader.info().interlaced {
+        return Err("interlaced pngs cannot be sorted in bands".to_string());
+    }
+    let (width, height) = reader.info().size();
+    let samples = reader.output_color_type().0.samples();
+    let profile = reader.info().icc_profile.clone();
+
+    let file =
+        File::create(output).map_err(|e| format!("cannot create {}: {}", output.display(), e))?;
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_compression(compression.png());
+    let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+    if let Some(profile) = profile {
+        writer
+            .write_chunk(png::chunk::iCCP, &iccp_data(&profile)?)
+            .map_err(|e| e.to_string())?;
+    }
+    if let Some(exif) = exif {
+        writer
+            .write_chunk(png::chunk::ChunkType(*b"eXIf"), exif)
+            .map_err(|e| e.to_string())?;
+    }
+    let mut stream = writer.stream_writer().map_err(|e| e.to_string())?;
+
+    sort_bands(
+        passes,
+        [width as usize, height as usize],
+        band_height,
+        |pixels| {
+            for row in pixels.chunks_mut(width as usize) {
+                let data = reader
+                    .next_row()
+                    .map_err(|e| e.to_string())?
+                    .ok_or("the png ends early")?
+                    .data();
+                for (pixel, channels) in row.iter_mut().zip(data.chunks(samples)) {
+                    *pixel = match *channels {
+                        [l] => egui::Color32::from_rgba_unmultiplied(l, l, l, 255),
+                        [l, a] => egui::Color32::from_rgba_unmultiplied(l, l, l, a),
+                        [r, g, b] => egui::Color32::from_rgba_unmultiplied(r, g, b, 255),
+                        [r, g, b, a] => egui::Color32::from_rgba_unmultiplied(r, g, b, a),
+                        _ => unreachable!("pngs have one to four channels"),
+                    };
+                }
+            }
+            Ok(())
+        },
+        |pixels| {
+            stream
+                .write_all(&straight_rgba(pixels))
+                .map_err(|e| e.to_string())
+        },
+    )?;
+    stream.finish().map_err(|e| e.to_string())?;
+    writer.finish().map_err(|e| e.to_string())
+}
@@ -0,0 +1,294 @@
+use std::path::Path;
+
+/// straight-line paths read from an svg file, used as custom sorting paths
+#[derive(Clone)]
+pub struct SvgPaths {
+    /// `min x, min y, width, height`, stretched over the whole image when present
+    view_box: Option<[f32; 4]>,
+    polylines: Vec<Vec<(f32, f32)>>,
+}
+
+impl SvgPaths {
+    pub fn load(path: &Path) -> Result<SvgPaths, String> {
+        let source = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+        SvgPaths::parse(&source)
+    }
+
+    pub fn parse(source: &str) -> Result<SvgPaths, String> {
+        let mut view_box = None;
+        let mut polylines = Vec::new();
+
+        for (name, attributes) in tags(source) {
+            let attribute = |key: &str| {
+                attributes
+                    .iter()
+                    .find(|(k, _)| *k == key)
+                    .map(|(_, value)| *value)
+            };
+
+            match name {
+                "svg" => {
+                    if let Some(value) = attribute("viewBox") {
+                        let numbers = parse_numbers(value)?;
+                        if numbers.len() != 4 || numbers[2] <= 0.0 || numbers[3] <= 0.0 {
+                            return Err(format!("invalid viewBox \"{}\"", value));
+                        }
+                        view_box = Some([numbers[0], numbers[1], numbers[2], numbers[3]]);
+                    }
+                }
+                "polyline" | "polygon" => {
+                    let numbers = parse_numbers(attribute("points").unwrap_or_default())?;
+                    let mut points: Vec<(f32, f32)> =
+                        numbers.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+                    if name == "polygon" && !points.is_empty() {
+                        points.push(points[0]);
+                    }
+                    polylines.push(points);
+                }
+                "line" => {
+                    let coordinate = |key| {
+                        attribute(key)
+                            .unwrap_or("0")
+                            .trim()
+                            .parse::<f32>()
+                            .map_err(|_| format!("invalid line coordinate {}", key))
+                    };
+                    polylines.push(vec![
+                        (coordinate("x1")?, coordinate("y1")?),
+                        (coordinate("x2")?, coordinate("y2")?),
+                    ]);
+                }
+                "path" => polylines.extend(parse_path_data(attribute("d").unwrap_or_default())?),
+                _ => {}
+            }
+        }
+
+        polylines.retain(|polyline| !polyline.is_empty());
+        if polylines.is_empty() {
+            return Err("no polyline, polygon, line or path elements found".to_string());
+        }
+
+        Ok(SvgPaths {
+            view_box,
+            polylines,
+        })
+    }
+
+    /// rasterizes every polyline into pixel indices, pixels already covered by an earlier
+    /// path are skipped so nothing gets sorted twice
+    pub fn rasterize(&self, width: usize, height: usize) -> Vec<Vec<usize>> {
+        let (offset_x, offset_y, scale_x, scale_y) = match self.view_box {
+            Some([x, y, w, h]) => (x, y, width as f32 / w, height as f32 / h),
+            None => (0.0, 0.0, 1.0, 1.0),
+        };
+        let to_pixel = |(x, y): (f32, f32)| {
+            (
+                ((x - offset_x) * scale_x).floor() as isize,
+                ((y - offset_y) * scale_y).floor() as isize,
+            )
+        };
+
+        let mut visited = vec![false; width * height];
+        let mut paths = Vec::new();
+
+        for polyline in &self.polylines {
+            let mut path = Vec::new();
+            let mut visit = |(x, y): (isize, isize)| {
+                if (0..width as isize).contains(&x) && (0..height as isize).contains(&y) {
+                    let index = y as usize * width + x as usize;
+                    if !visited[index] {
+                        visited[index] = true;
+                        path.push(index);
+                    }
+                }
+            };
+
+            visit(to_pixel(polyline[0]));
+            for segment in polyline.windows(2) {
+                line(to_pixel(segment[0]), to_pixel(segment[1]), &mut visit);
+            }
+
+            if !path.is_empty() {
+                paths.push(path);
+            }
+        }
+
+        paths
+    }
+}
+
+/// bresenham's line, `from` itself is not visited
+fn line(from: (isize, isize), to: (isize, isize), visit: &mut impl FnMut((isize, isize))) {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    while (x, y) != to {
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+        visit((x, y));
+    }
+}
+
+/// a very small tag scanner, good enough to pick shapes out of an svg file
+fn tags(source: &str) -> Vec<(&str, Vec<(&str, &str)>)> {
+    let mut result = Vec::new();
+
+    for chunk in source.split('<').skip(1) {
+        if chunk.starts_with(['/', '!', '?']) {
+            continue;
+        }
+
+        let tag = chunk.split('>').next().unwrap_or_default();
+        let name_end = tag
+            .find(|c: char| c.is_whitespace() || c == '/')
+            .unwrap_or(tag.len());
+        let (name, mut rest) = tag.split_at(name_end);
+
+        let mut attributes = Vec::new();
+        while let Some(equals) = rest.find('=') {
+            let key = rest[..equals].trim();
+            let value = rest[equals + 1..].trim_start();
+            let Some(quote) = value.chars().next().filter(|c| *c == '"' || *c == '\'') else {
+                break;
+            };
+            let Some(value_end) = value[1..].find(quote) else {
+                break;
+            };
+            attributes.push((key, &value[1..value_end + 1]));
+            rest = &value[value_end + 2..];
+        }
+
+        // namespaced tags like `svg:path` are treated like plain ones
+        let name = name.rsplit(':').next().unwrap_or(name);
+        result.push((name, attributes));
+    }
+
+    result
+}
+
+fn parse_numbers(value: &str) -> Result<Vec<f32>, String> {
+    value
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|number| !number.is_empty())
+        .map(|number| {
+            number
+                .parse::<f32>()
+                .map_err(|_| format!("invalid number \"{}\"", number))
+        })
+        .collect()
+}
+
+enum Token {
+    Command(char),
+    Number(f32),
+}
+
+fn tokenize_path_data(data: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut number = String::new();
+
+    let flush = |number: &mut String, tokens: &mut Vec<Token>| -> Result<(), String> {
+        if !number.is_empty() {
+            let value = number
+                .parse::<f32>()
+                .map_err(|_| format!("invalid number \"{}\" in path data", number))?;
+            tokens.push(Token::Number(value));
+            number.clear();
+        }
+        Ok(())
+    };
+
+    for c in data.chars() {
+        if c.is_ascii_alphabetic() && c != 'e' && c != 'E' {
+            flush(&mut number, &mut tokens)?;
+            tokens.push(Token::Command(c));
+        } else if c.is_whitespace() || c == ',' {
+            flush(&mut number, &mut tokens)?;
+        } else {
+            // a sign or a second decimal point starts a new number, as in "10-5" or "0.5.5"
+            let starts_new = (c == '-' || c == '+') && !number.ends_with(['e', 'E'])
+                || c == '.' && number.contains('.');
+            if starts_new {
+                flush(&mut number, &mut tokens)?;
+            }
+            number.push(c);
+        }
+    }
+    flush(&mut number, &mut tokens)?;
+
+    Ok(tokens)
+}
+
+/// supports the straight line commands (M, L, H, V, Z) in absolute and relative form
+fn parse_path_data(data: &str) -> Result<Vec<Vec<(f32, f32)>>, String> {
+    let tokens = tokenize_path_data(data)?;
+    let number = |i: usize| match tokens.get(i) {
+        Some(Token::Number(value)) => Ok(*value),
+        _ => Err("path data is missing a coordinate".to_string()),
+    };
+
+    let mut polylines: Vec<Vec<(f32, f32)>> = Vec::new();
+    let mut current = (0.0, 0.0);
+    let mut start = (0.0, 0.0);
+    let mut command = 'M';
+    let mut i = 0;
+
+    while i < tokens.len() {
+        if let Token::Command(c) = tokens[i] {
+            command = c;
+            i += 1;
+            if command.eq_ignore_ascii_case(&'z') {
+                if let Some(polyline) = polylines.last_mut() {
+                    polyline.push(start);
+                }
+                current = start;
+                continue;
+            }
+        }
+
+        let relative = command.is_ascii_lowercase();
+        let base = if relative { current } else { (0.0, 0.0) };
+        match command.to_ascii_uppercase() {
+            'M' => {
+                current = (base.0 + number(i)?, base.1 + number(i + 1)?);
+                start = current;
+                polylines.push(vec![current]);
+                // further coordinate pairs after a moveto are implicit linetos
+                command = if relative { 'l' } else { 'L' };
+                i += 2;
+                continue;
+            }
+            'L' => {
+                current = (base.0 + number(i)?, base.1 + number(i + 1)?);
+                i += 2;
+            }
+            'H' => {
+                current.0 = base.0 + number(i)?;
+                i += 1;
+            }
+            'V' => {
+                current.1 = base.1 + number(i)?;
+                i += 1;
+            }
+            other => return Err(format!("unsupported path command '{}'", other)),
+        }
+
+        match polylines.last_mut() {
+            Some(polyline) => polyline.push(current),
+            None => return Err("path data must start with a moveto".to_string()),
+        }
+    }
+
+    Ok(polylines)
+}
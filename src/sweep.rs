@@ -0,0 +1,86 @@
+//! animations of a single image sorted over and over while a parameter of the last pass moves
+//! from one value to another, one frame per step, the simplest kind of `Timeline`
+use crate::keys::threshold_upper_boundary;
+use crate::sort::Pass;
+use crate::timeline::Timeline;
+use std::time::Duration;
+
+/// what changes from frame to frame
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SweepParameter {
+    LowerThreshold,
+    HigherThreshold,
+    /// in pixels
+    Shift,
+    /// in percent
+    Strength,
+    /// in pixels
+    MaxLength,
+    /// from 0 to 1
+    SplitChance,
+}
+
+impl SweepParameter {
+    /// with the names shown in the gui
+    pub const ALL: [(SweepParameter, &'static str); 6] = [
+        (SweepParameter::LowerThreshold, "Lower threshold"),
+        (SweepParameter::HigherThreshold, "Higher threshold"),
+        (SweepParameter::Shift, "Shift"),
+        (SweepParameter::Strength, "Strength"),
+        (SweepParameter::MaxLength, "Max length"),
+        (SweepParameter::SplitChance, "Split chance"),
+    ];
+
+    /// the value `set` would have given the pass, none for intervals without a max length
+    pub fn get(self, pass: &Pass) -> Option<f32> {
+        Some(match self {
+            SweepParameter::LowerThreshold => pass.thresholds.lower as f32,
+            SweepParameter::HigherThreshold => pass.thresholds.higher as f32,
+            SweepParameter::Shift => pass.interval_options.shift as f32,
+            SweepParameter::Strength => pass.strength * 100.0,
+            SweepParameter::MaxLength => pass.interval_options.max_length? as f32,
+            SweepParameter::SplitChance => pass.interval_options.split_probability as f32,
+        })
+    }
+
+    /// `value` is rounded and clamped to what the parameter can be
+    pub fn set(self, pass: &mut Pass, value: f32) {
+        let upper_boundary =
+            threshold_upper_boundary(pass.thresholds.key.as_ref().unwrap_or(&pass.sort_by));
+        let threshold = value.round().clamp(0.0, upper_boundary as f32) as u16;
+        match self {
+            SweepParameter::LowerThreshold => pass.thresholds.lower = threshold,
+            SweepParameter::HigherThreshold => pass.thresholds.higher = threshold,
+            SweepParameter::Shift => pass.interval_options.shift = value.round() as isize,
+            SweepParameter::Strength => pass.strength = value.clamp(0.0, 100.0) / 100.0,
+            SweepParameter::MaxLength => {
+                pass.interval_options.max_length = Some(value.round().max(1.0) as usize)
+            }
+            SweepParameter::SplitChance => {
+                pass.interval_options.split_probability = value.clamp(0.0, 1.0) as f64
+            }
+        }
+    }
+}
+
+pub struct Sweep {
+    pub parameter: SweepParameter,
+    /// the value on the first frame
+    pub from: f32,
+    /// the value on the last frame
+    pub to: f32,
+    pub frames: usize,
+    /// how long every frame is shown
+    pub delay: Duration,
+}
+
+impl Sweep {
+    /// a keyframe on the first frame and one on the last, the values in between evenly spaced
+    pub fn timeline(&self) -> Timeline {
+        let mut timeline = Timeline::new(self.frames, self.delay);
+        let track = timeline.track_mut(self.parameter);
+        track.set(0, self.from);
+        track.set(self.frames.saturating_sub(1), self.to);
+        timeline
+    }
+}
@@ -0,0 +1,40 @@
+//! several images open at once in the gui. each one keeps its image, history and view, the ui
+//! shows the active one and only holds the settings it's sorted with
+use crate::history::{History, Snapshot};
+use std::path::PathBuf;
+
+pub struct Tab {
+    pub image: egui::ColorImage,
+    pub name: String,
+    /// where the image was opened from, none when it's only in memory
+    pub path: Option<PathBuf>,
+    /// as it was last shown, kept to be saved without sorting it again
+    pub sorted: egui::ColorImage,
+    /// what it was sorted with while another tab is shown, the ui has them while it's shown.
+    /// none until it's first shown, it takes the settings of the tab shown before it then
+    pub settings: Option<Snapshot>,
+    pub history: History,
+    /// the settings of the last step of `history`
+    pub recorded: Option<Snapshot>,
+    /// none fits the image in the panel
+    pub zoom: Option<f32>,
+    /// how far the image is moved away from the middle of the panel
+    pub pan: egui::Vec2,
+}
+
+impl Tab {
+    /// `image` opened, not sorted yet
+    pub fn new(image: egui::ColorImage, name: String, path: Option<PathBuf>) -> Tab {
+        Tab {
+            sorted: image.clone(),
+            image,
+            name,
+            path,
+            settings: None,
+            history: History::default(),
+            recorded: None,
+            zoom: None,
+            pan: egui::Vec2::ZERO,
+        }
+    }
+}
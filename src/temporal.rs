@@ -0,0 +1,68 @@
+//! keeping sorted frame sequences from flickering. every frame is sorted with the same seeds
+//! already, on top of that the pixels compared against the thresholds are a running average
+//! of the frames so far, so interval boundaries move smoothly instead of jumping around
+use crate::intervals::IntervalSource;
+use crate::sort::Pass;
+use std::sync::Arc;
+
+pub struct TemporalSmoothing {
+    /// how much of the earlier frames is kept in the average, from 0 (nothing, every frame
+    /// on its own) to 1 (only the first frame)
+    pub amount: f32,
+    /// the average every pass compared against its thresholds on the last frame
+    averages: Vec<Option<egui::ColorImage>>,
+}
+
+impl TemporalSmoothing {
+    pub fn new(amount: f32) -> TemporalSmoothing {
+        TemporalSmoothing {
+            amount: amount.clamp(0.0, 1.0),
+            averages: Vec::new(),
+        }
+    }
+
+    /// sorts the next frame with `passes`. passes selecting by noise, edges or a control
+    /// image, and passes sorting every channel on its own, are applied as they are
+    pub fn apply(&mut self, passes: &[Pass], frame: &mut egui::ColorImage) {
+        self.averages.resize(passes.len(), None);
+        for (pass, average) in passes.iter().zip(&mut self.averages) {
+            if !matches!(pass.thresholds.source, IntervalSource::Threshold) || pass.per_channel {
+                pass.apply(frame);
+                continue;
+            }
+
+            let smoothed = match average.take() {
+                Some(average) if average.size == frame.size => blend(&average, frame, self.amount),
+                // the first frame, or one of another size starting over
+                _ => frame.clone(),
+            };
+            let smoothed = Arc::new(smoothed);
+            let mut pass = pass.clone();
+            pass.thresholds.source = IntervalSource::Control(smoothed.clone());
+            pass.apply(frame);
+            drop(pass);
+            *average = Some(Arc::unwrap_or_clone(smoothed));
+        }
+    }
+}
+
+/// `amount` of `average` and the rest of `frame`
+fn blend(average: &egui::ColorImage, frame: &egui::ColorImage, amount: f32) -> egui::ColorImage {
+    let mix = |from: u8, to: u8| (from as f32 * amount + to as f32 * (1.0 - amount)).round() as u8;
+    egui::ColorImage {
+        size: frame.size,
+        pixels: average
+            .pixels
+            .iter()
+            .zip(&frame.pixels)
+            .map(|(average, pixel)| {
+                egui::Color32::from_rgba_premultiplied(
+                    mix(average.r(), pixel.r()),
+                    mix(average.g(), pixel.g()),
+                    mix(average.b(), pixel.b()),
+                    mix(average.a(), pixel.a()),
+                )
+            })
+            .collect(),
+    }
+}
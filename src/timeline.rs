@@ -0,0 +1,158 @@
+//! animations of a single image where parameters of the last pass follow keyframes, moving in
+//! a straight line from the value of one keyframe to the value of the next
+use crate::animation::{Animation, Frame};
+use crate::sort::Pass;
+use crate::sweep::SweepParameter;
+use rayon::prelude::*;
+use std::time::Duration;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Keyframe {
+    /// counted from 0
+    pub frame: usize,
+    /// in the units of `SweepParameter::set`
+    pub value: f32,
+}
+
+/// the keyframes of one parameter
+#[derive(Clone, Debug)]
+pub struct Track {
+    pub parameter: SweepParameter,
+    /// by frame, at most one on every frame
+    keyframes: Vec<Keyframe>,
+}
+
+impl Track {
+    pub fn new(parameter: SweepParameter) -> Track {
+        Track {
+            parameter,
+            keyframes: Vec::new(),
+        }
+    }
+
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// adds a keyframe, replacing the one already on `frame`
+    pub fn set(&mut self, frame: usize, value: f32) {
+        match self
+            .keyframes
+            .binary_search_by_key(&frame, |keyframe| keyframe.frame)
+        {
+            Ok(i) => self.keyframes[i].value = value,
+            Err(i) => self.keyframes.insert(i, Keyframe { frame, value }),
+        }
+    }
+
+    pub fn remove(&mut self, frame: usize) {
+        self.keyframes.retain(|keyframe| keyframe.frame != frame);
+    }
+
+    pub fn has_keyframe(&self, frame: usize) -> bool {
+        self.keyframes
+            .iter()
+            .any(|keyframe| keyframe.frame == frame)
+    }
+
+    /// the value on `frame`, held before the first keyframe and after the last one. none
+    /// without keyframes
+    pub fn value(&self, frame: usize) -> Option<f32> {
+        let next = self
+            .keyframes
+            .partition_point(|keyframe| keyframe.frame <= frame);
+        let previous = next.checked_sub(1).map(|i| self.keyframes[i]);
+        match (previous, self.keyframes.get(next)) {
+            (Some(previous), Some(next)) => Some(
+                previous.value
+                    + (next.value - previous.value) * (frame - previous.frame) as f32
+                        / (next.frame - previous.frame) as f32,
+            ),
+            (Some(keyframe), None) | (None, Some(&keyframe)) => Some(keyframe.value),
+            (None, None) => None,
+        }
+    }
+}
+
+pub struct Timeline {
+    /// at most one for every parameter, the ones without keyframes keep the value of the pass
+    pub tracks: Vec<Track>,
+    pub frames: usize,
+    /// how long every frame is shown
+    pub delay: Duration,
+}
+
+impl Timeline {
+    pub fn new(frames: usize, delay: Duration) -> Timeline {
+        Timeline {
+            tracks: Vec::new(),
+            frames,
+            delay,
+        }
+    }
+
+    pub fn track(&self, parameter: SweepParameter) -> Option<&Track> {
+        self.tracks
+            .iter()
+            .find(|track| track.parameter == parameter)
+    }
+
+    /// the track of `parameter`, added without keyframes when there's none yet
+    pub fn track_mut(&mut self, parameter: SweepParameter) -> &mut Track {
+        match self
+            .tracks
+            .iter()
+            .position(|track| track.parameter == parameter)
+        {
+            Some(i) => &mut self.tracks[i],
+            None => {
+                self.tracks.push(Track::new(parameter));
+                self.tracks.last_mut().unwrap()
+            }
+        }
+    }
+
+    /// sets every parameter with keyframes to its value on `frame`
+    pub fn apply(&self, pass: &mut Pass, frame: usize) {
+        for track in &self.tracks {
+            if let Some(value) = track.value(frame) {
+                track.parameter.set(pass, value);
+            }
+        }
+    }
+
+    /// sorts `image` with `passes` once for every frame, the last pass gets the values of the
+    /// frame and the ones before it are only applied once
+    pub fn render(&self, image: &egui::ColorImage, passes: &[Pass]) -> Result<Animation, String> {
+        let (last, earlier) = passes.split_last().ok_or("an animation needs a pass")?;
+        let moves_thresholds = self.tracks.iter().any(|track| {
+            !track.keyframes.is_empty()
+                && matches!(
+                    track.parameter,
+                    SweepParameter::LowerThreshold | SweepParameter::HigherThreshold
+                )
+        });
+        if last.auto_threshold.is_some() && moves_thresholds {
+            return Err("automatic thresholds would replace the animated ones".to_string());
+        }
+
+        let mut start = image.clone();
+        for pass in earlier {
+            pass.apply(&mut start);
+        }
+        let frames = (0..self.frames)
+            .into_par_iter()
+            .map(|frame| {
+                let mut pass = last.clone();
+                self.apply(&mut pass, frame);
+                let mut image = start.clone();
+                pass.apply(&mut image);
+                Frame {
+                    image,
+                    delay: self.delay,
+                }
+            })
+            .collect();
+        Ok(Animation { frames })
+    }
+}
@@ -0,0 +1,387 @@
+//! videos, read and written by piping frames through ffmpeg, which has to be installed
+use crate::animation::Animation;
+use crate::io::straight_rgba;
+use crate::sort::Pass;
+use crate::temporal::TemporalSmoothing;
+use rayon::prelude::*;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+
+/// the files written as videos instead of images
+pub const VIDEO_EXTENSIONS: [&str; 4] = ["mp4", "mov", "mkv", "webm"];
+
+pub fn is_video_path(path: &Path) -> bool {
+    path.extension().is_some_and(|extension| {
+        VIDEO_EXTENSIONS.contains(&extension.to_string_lossy().to_lowercase().as_str())
+    })
+}
+
+/// the part of a video to sort, in any time format ffmpeg takes, like 90 or 00:01:30.5
+#[derive(Clone, Debug, Default)]
+pub struct TimeRange {
+    pub start: Option<String>,
+    pub end: Option<String>,
+}
+
+impl TimeRange {
+    /// the options of ffmpeg cutting the input that follows them
+    fn arguments(&self) -> Vec<&str> {
+        let mut arguments = Vec::new();
+        if let Some(start) = &self.start {
+            arguments.extend(["-ss", start.as_str()]);
+        }
+        if let Some(end) = &self.end {
+            arguments.extend(["-to", end.as_str()]);
+        }
+        arguments
+    }
+}
+
+/// starts ffmpeg encoding the frames of `size` written to its standard input into the video
+/// at `path`, at `frame_rate` frames per second or, without one, timed by when they're
+/// written. the audio of `audio` is copied along
+fn start_encoder(
+    size: [usize; 2],
+    frame_rate: Option<&str>,
+    audio: Option<(&Path, &TimeRange)>,
+    path: &Path,
+) -> Result<(Child, ChildStdin), String> {
+    let [width, height] = size;
+    let mut command = Command::new("ffmpeg");
+    command
+        .args(["-y", "-loglevel", "error"])
+        .args(["-f", "rawvideo", "-pix_fmt", "rgba"])
+        .args(["-s", &format!("{}x{}", width, height)]);
+    match frame_rate {
+        Some(frame_rate) => command.args(["-framerate", frame_rate]),
+        None => command.args(["-use_wallclock_as_timestamps", "1"]),
+    };
+    command.args(["-i", "-"]);
+    if frame_rate.is_none() {
+        command.args(["-fps_mode", "vfr"]);
+    }
+    if let Some((input, range)) = audio {
+        command
+            .args(range.arguments())
+            .arg("-i")
+            .arg(input)
+            .args(["-map", "0:v", "-map", "1:a?"])
+            .args(["-c:a", "copy", "-shortest"]);
+    }
+    let mut ffmpeg = command
+        // most players only take 4:2:0 videos, which need an even width and height
+        .args(["-vf", "pad=ceil(iw/2)*2:ceil(ih/2)*2"])
+        .args(["-pix_fmt", "yuv420p"])
+        .arg(path)
+        .stdin(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run ffmpeg, is it installed? {}", e))?;
+    let stdin = ffmpeg.stdin.take().expect("stdin is piped");
+    Ok((ffmpeg, stdin))
+}
+
+/// waits for ffmpeg to finish, once its standard input is closed
+fn finish(mut ffmpeg: Child) -> Result<(), String> {
+    let status = ffmpeg.wait().map_err(|e| e.to_string())?;
+    if !status.success() {
+        return Err(format!("ffmpeg failed with {}", status));
+    }
+    Ok(())
+}
+
+/// encodes `animation` into the video at `path`, in whatever codec ffmpeg picks for its
+/// extension. videos have a single frame rate, the one of the first frame, and no alpha
+pub fn write_video(animation: &Animation, path: &Path) -> Result<(), String> {
+    let delay = animation.frames[0].delay.as_millis().max(1);
+    let frame_rate = format!("1000/{}", delay);
+    let (ffmpeg, mut stdin) = start_encoder(animation.size(), Some(&frame_rate), None, path)?;
+    for frame in &animation.frames {
+        if let Err(e) = stdin.write_all(&straight_rgba(&frame.image.pixels)) {
+            drop(stdin);
+            let _ = finish(ffmpeg);
+            return Err(format!("ffmpeg stopped reading frames: {}", e));
+        }
+    }
+    drop(stdin);
+    finish(ffmpeg)
+}
+
+/// the frame rate of the first video stream of `path` as ffprobe gives it, like 30000/1001
+fn frame_rate(path: &Path) -> Result<String, String> {
+    let probe = Command::new("ffprobe")
+        .args(["-v", "error", "-select_streams", "v:0"])
+        .args(["-show_entries", "stream=r_frame_rate"])
+        .args(["-of", "default=noprint_wrappers=1:nokey=1"])
+        .arg(path)
+        .stderr(Stdio::inherit())
+        .output()
+        .map_err(|e| format!("cannot run ffprobe, is ffmpeg installed? {}", e))?;
+    let frame_rate = String::from_utf8_lossy(&probe.stdout).trim().to_string();
+    if !probe.status.success() || frame_rate.is_empty() || frame_rate.starts_with('0') {
+        return Err(format!("cannot find a video stream in {}", path.display()));
+    }
+    Ok(frame_rate)
+}
+
+/// the next frame written by ffmpeg as a PAM image, none once there are no frames left. every
+/// frame says how big it is, so rotated videos come out upright
+fn read_frame(reader: &mut impl BufRead) -> Result<Option<egui::ColorImage>, String> {
+    let mut line = String::new();
+    let mut read_line = |line: &mut String| {
+        line.clear();
+        reader.read_line(line).map_err(|e| e.to_string())
+    };
+    if read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.trim_end() != "P7" {
+        return Err("ffmpeg wrote something other than a PAM frame".to_string());
+    }
+
+    let (mut width, mut height) = (0, 0);
+    loop {
+        if read_line(&mut line)? == 0 {
+            return Err("ffmpeg stopped in the middle of a frame".to_string());
+        }
+        match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["ENDHDR"] => break,
+            ["WIDTH", value] => width = value.parse().map_err(|_| "invalid frame width")?,
+            ["HEIGHT", value] => height = value.parse().map_err(|_| "invalid frame height")?,
+            ["DEPTH", depth] if *depth != "4" => return Err("frames must be rgba".to_string()),
+            _ => {}
+        }
+    }
+
+    let mut pixels = vec![0; width * height * 4];
+    reader
+        .read_exact(&mut pixels)
+        .map_err(|_| "ffmpeg stopped in the middle of a frame")?;
+    Ok(Some(egui::ColorImage::from_rgba_unmultiplied(
+        [width, height],
+        &pixels,
+    )))
+}
+
+/// sorts every frame of the video at `input` within `range` into a video at `output`,
+/// keeping its frame rate and audio. as many frames as there are threads are sorted at once,
+/// unless they're smoothed, which goes one frame after the other. `progress` gets how many
+/// frames are done after every batch
+pub fn sort_video(
+    passes: &[Pass],
+    input: &Path,
+    output: &Path,
+    range: &TimeRange,
+    mut smoothing: Option<TemporalSmoothing>,
+    mut progress: impl FnMut(usize),
+) -> Result<usize, String> {
+    let frame_rate = frame_rate(input)?;
+    let mut decoder = Command::new("ffmpeg")
+        .args(["-loglevel", "error"])
+        .args(range.arguments())
+        .arg("-i")
+        .arg(input)
+        .args(["-map", "0:v:0", "-f", "image2pipe"])
+        .args(["-c:v", "pam", "-pix_fmt", "rgba", "-"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("cannot run ffmpeg, is it installed? {}", e))?;
+    let mut frames = BufReader::new(decoder.stdout.take().expect("stdout is piped"));
+
+    let batch_size = rayon::current_num_threads();
+    let mut encoder: Option<(Child, ChildStdin)> = None;
+    let mut sorted = 0;
+    let result = 'frames: loop {
+        let mut batch = Vec::with_capacity(batch_size);
+        while batch.len() < batch_size {
+            match read_frame(&mut frames) {
+                Ok(Some(frame)) => batch.push(frame),
+                Ok(None) => break,
+                Err(e) => break 'frames Err(e),
+            }
+        }
+        if batch.is_empty() {
+            break Ok(());
+        }
+
+        if let Some(smoothing) = &mut smoothing {
+            for frame in &mut batch {
+                smoothing.apply(passes, frame);
+            }
+        } else {
+            batch.par_iter_mut().for_each(|frame| {
+                for pass in passes {
+                    pass.apply(frame);
+                }
+            });
+        }
+
+        // started once the size of the frames is known
+        let stdin = match &mut encoder {
+            Some((_, stdin)) => stdin,
+            None => {
+                let audio = Some((input, range));
+                match start_encoder(batch[0].size, Some(&frame_rate), audio, output) {
+                    Ok(started) => &mut encoder.insert(started).1,
+                    Err(e) => break Err(e),
+                }
+            }
+        };
+        if let Some(e) = batch
+            .iter()
+            .find_map(|frame| stdin.write_all(&straight_rgba(&frame.pixels)).err())
+        {
+            break Err(format!("ffmpeg stopped reading frames: {}", e));
+        }
+        sorted += batch.len();
+        progress(sorted);
+    };
+
+    // closing the pipe stops the decoder when the frames aren't all read
+    drop(frames);
+    let decoded = decoder.wait().map_err(|e| e.to_string())?;
+    let encoded = match encoder {
+        Some((ffmpeg, stdin)) => {
+            drop(stdin);
+            finish(ffmpeg)
+        }
+        None => Ok(()),
+    };
+    result?;
+    if !decoded.success() {
+        return Err(format!("ffmpeg cannot decode {}", input.display()));
+    }
+    encoded?;
+    if sorted == 0 {
+        return Err(format!("no frames found in {}", input.display()));
+    }
+    Ok(sorted)
+}
+
+/// the input format of ffmpeg for webcams, and the device opened unless another one is given.
+/// windows names its webcams, there's no first one to fall back to
+fn webcam_input() -> (&'static str, Option<&'static str>) {
+    if cfg!(target_os = "macos") {
+        ("avfoundation", Some("0"))
+    } else if cfg!(target_os = "windows") {
+        ("dshow", None)
+    } else {
+        ("v4l2", Some("/dev/video0"))
+    }
+}
+
+/// a webcam captured by ffmpeg in the background. only the newest frame is kept, the ones
+/// that come in while the last one is sorted are dropped
+pub struct Webcam {
+    ffmpeg: Child,
+    latest: Arc<Mutex<Option<egui::ColorImage>>>,
+    /// why the frames stopped coming
+    error: Arc<Mutex<Option<String>>>,
+}
+
+impl Webcam {
+    /// starts capturing from `device`, or the first webcam, scaled down to `width` pixels wide
+    pub fn open(device: Option<&str>, width: usize) -> Result<Webcam, String> {
+        let (format, default_device) = webcam_input();
+        let device = device
+            .or(default_device)
+            .ok_or("pick a webcam in the settings, like video=Integrated Camera".to_string())?;
+        let mut command = Command::new("ffmpeg");
+        command.args(["-loglevel", "error", "-f", format]);
+        // avfoundation turns down the rate it picks by itself with most webcams
+        if format == "avfoundation" {
+            command.args(["-framerate", "30"]);
+        }
+        let mut ffmpeg = command
+            .args(["-i", device])
+            .args(["-vf", &format!("scale={}:-2", width)])
+            .args(["-f", "image2pipe", "-c:v", "pam", "-pix_fmt", "rgba", "-"])
+            .stdout(Stdio::piped())
+            .spawn()
+            .map_err(|e| format!("cannot run ffmpeg, is it installed? {}", e))?;
+        let mut frames = BufReader::new(ffmpeg.stdout.take().expect("stdout is piped"));
+
+        let latest: Arc<Mutex<Option<egui::ColorImage>>> = Default::default();
+        let error: Arc<Mutex<Option<String>>> = Default::default();
+        let (latest_frame, capture_error) = (latest.clone(), error.clone());
+        std::thread::spawn(move || loop {
+            match read_frame(&mut frames) {
+                Ok(Some(frame)) => *latest_frame.lock().unwrap() = Some(frame),
+                Ok(None) => {
+                    *capture_error.lock().unwrap() =
+                        Some("the webcam stopped sending frames".to_string());
+                    break;
+                }
+                Err(e) => {
+                    *capture_error.lock().unwrap() = Some(e);
+                    break;
+                }
+            }
+        });
+        Ok(Webcam {
+            ffmpeg,
+            latest,
+            error,
+        })
+    }
+
+    /// the newest frame, none when it was already taken
+    pub fn frame(&self) -> Option<egui::ColorImage> {
+        self.latest.lock().unwrap().take()
+    }
+
+    pub fn error(&self) -> Option<String> {
+        self.error.lock().unwrap().clone()
+    }
+}
+
+impl Drop for Webcam {
+    fn drop(&mut self) {
+        let _ = self.ffmpeg.kill();
+        let _ = self.ffmpeg.wait();
+    }
+}
+
+/// records frames into a video as they come, every frame lasting until the next one
+pub struct Recorder {
+    path: PathBuf,
+    /// started with the first frame, whose size the other frames must have
+    encoder: Option<(Child, ChildStdin, [usize; 2])>,
+}
+
+impl Recorder {
+    pub fn new(path: PathBuf) -> Recorder {
+        Recorder {
+            path,
+            encoder: None,
+        }
+    }
+
+    pub fn write(&mut self, frame: &egui::ColorImage) -> Result<(), String> {
+        let (_, stdin, size) = match &mut self.encoder {
+            Some(encoder) => encoder,
+            None => {
+                let (ffmpeg, stdin) = start_encoder(frame.size, None, None, &self.path)?;
+                self.encoder.insert((ffmpeg, stdin, frame.size))
+            }
+        };
+        if frame.size != *size {
+            return Err("the frames of a recording must all have the same size".to_string());
+        }
+        stdin
+            .write_all(&straight_rgba(&frame.pixels))
+            .map_err(|e| format!("ffmpeg stopped reading frames: {}", e))
+    }
+
+    /// waits for the video to be written
+    pub fn finish(self) -> Result<(), String> {
+        match self.encoder {
+            Some((ffmpeg, stdin, _)) => {
+                drop(stdin);
+                finish(ffmpeg)
+            }
+            None => Err("nothing was recorded".to_string()),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::batch::{sort_image_file, InputImage};
+use crate::pipeline;
+use notify::event::ModifyKind;
+use notify::{EventKind, RecursiveMode, Watcher};
+use psorter::io::is_image_file;
+use psorter::sort::Pass;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::{Duration, Instant};
+
+/// how long a file has to go without changes before it's sorted, so images that are still
+/// being written aren't read halfway
+const SETTLE_TIME: Duration = Duration::from_millis(500);
+
+/// sorts the images created or changed in `directories` until the watcher stops. failed
+/// images are logged and skipped, an image changing again replaces its earlier result
+pub fn watch(
+    passes: &[Pass],
+    directories: &[String],
+    recursive: bool,
+    extensions: &[String],
+    output: &pipeline::Output,
+) -> Result<(), String> {
+    let (sender, events) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(|e| e.to_string())?;
+    let mode = if recursive {
+        RecursiveMode::Recursive
+    } else {
+        RecursiveMode::NonRecursive
+    };
+
+    let mut roots = Vec::new();
+    for directory in directories {
+        let root = Path::new(directory)
+            .canonicalize()
+            .ok()
+            .filter(|root| root.is_dir())
+            .ok_or_else(|| format!("{} is not a directory", directory))?;
+        watcher
+            .watch(&root, mode)
+            .map_err(|e| format!("cannot watch {}: {}", directory, e))?;
+        log::info!("watching {}", directory);
+        roots.push(root);
+    }
+
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+    // sorted images saved into a watched directory mustn't be sorted again
+    let mut written: HashSet<PathBuf> = HashSet::new();
+    loop {
+        match events.recv_timeout(SETTLE_TIME / 4) {
+            Ok(Ok(event)) => {
+                let changed = match event.kind {
+                    EventKind::Modify(ModifyKind::Metadata(_)) => false,
+                    EventKind::Create(_) | EventKind::Modify(_) => true,
+                    _ => false,
+                };
+                for path in event.paths.into_iter().filter(|_| changed) {
+                    let hidden = path
+                        .file_name()
+                        .is_some_and(|name| name.to_string_lossy().starts_with('.'));
+                    if !hidden && !written.contains(&path) && is_image_file(&path, extensions) {
+                        pending.insert(path, Instant::now());
+                    }
+                }
+            }
+            Ok(Err(e)) => log::warn!("{}", e),
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, changed)| changed.elapsed() >= SETTLE_TIME)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            let root = roots
+                .iter()
+                .filter(|root| path.starts_with(root))
+                .max_by_key(|root| root.components().count());
+            let image = InputImage {
+                relative_path: root
+                    .and_then(|root| path.strip_prefix(root).ok())
+                    .unwrap_or(&path)
+                    .to_path_buf(),
+                path: path.display().to_string(),
+            };
+
+            match sort_watched_image(passes, &image, output, &written) {
+                Ok(saved) => {
+                    written.insert(saved);
+                }
+                Err(e) => log::error!("{}", e),
+            }
+        }
+    }
+}
+
+/// returns the absolute path of the sorted image
+fn sort_watched_image(
+    passes: &[Pass],
+    image: &InputImage,
+    output: &pipeline::Output,
+    written: &HashSet<PathBuf>,
+) -> Result<PathBuf, String> {
+    let output_path = output.path(&image.path, &image.relative_path);
+    let earlier_result = output_path
+        .canonicalize()
+        .is_ok_and(|path| written.contains(&path));
+    if output_path.exists() && !output.force && !earlier_result {
+        return Err(format!(
+            "{} already exists, use --force to overwrite it",
+            output_path.display()
+        ));
+    }
+    if let Some(directory) = output_path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::create_dir_all(directory)
+            .map_err(|e| format!("cannot create {}: {}", directory.display(), e))?;
+    }
+
+    sort_image_file(
+        passes,
+        image,
+        output,
+        None,
+        false,
+        None,
+        &indicatif::ProgressBar::hidden(),
+    )?;
+    output_path.canonicalize().map_err(|e| e.to_string())
+}
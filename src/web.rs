@@ -0,0 +1,121 @@
+use crate::config;
+use crate::gui::{gui_update, PickFor, PickedFile, PickedFiles};
+use eframe::wasm_bindgen::{JsCast, JsValue};
+use psorter::io::{encode_image, EncodeOptions};
+use psorter::sort::Pass;
+use psorter::timeline::Timeline;
+use std::path::Path;
+
+/// the id of the canvas in `index.html` the gui is drawn on
+const CANVAS_ID: &str = "psorter";
+
+struct WebApp<U> {
+    update: U,
+}
+
+impl<U: FnMut(&egui::Context, &mut eframe::Frame)> eframe::App for WebApp<U> {
+    fn update(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
+        (self.update)(ctx, frame);
+    }
+}
+
+/// runs the gui in the page, logging to the console of the browser
+pub fn start() {
+    eframe::WebLogger::init(log::LevelFilter::Debug).ok();
+    wasm_bindgen_futures::spawn_local(async {
+        let app = WebApp {
+            update: gui_update(config::Config::default()),
+        };
+        let started = eframe::WebRunner::new()
+            .start(
+                CANVAS_ID,
+                eframe::WebOptions::default(),
+                Box::new(|_cc| Box::new(app)),
+            )
+            .await;
+        if let Err(e) = started {
+            log::error!("cannot start the gui: {}", js_error(e));
+        }
+    });
+}
+
+/// opens the file picker of the browser, the file is queued once it's read
+pub fn pick_file(purpose: PickFor, picked: &PickedFiles, ctx: &egui::Context) {
+    let picked = picked.clone();
+    let ctx = ctx.clone();
+    wasm_bindgen_futures::spawn_local(async move {
+        let (filter, extensions) = purpose.filter();
+        let Some(file) = rfd::AsyncFileDialog::new()
+            .add_filter(filter, extensions)
+            .pick_file()
+            .await
+        else {
+            return;
+        };
+        let bytes = file.read().await;
+        picked.lock().unwrap().push(PickedFile {
+            purpose,
+            name: file.file_name(),
+            bytes: Ok(bytes),
+        });
+        ctx.request_repaint();
+    });
+}
+
+/// hands the image to the browser as a download, there's no directory to save into
+pub fn save_image(
+    image: &egui::ColorImage,
+    name: &str,
+    _directory: Option<&Path>,
+    options: &EncodeOptions,
+) {
+    // images without a known extension are saved as png
+    let path = Path::new(name);
+    let (name, bytes) = match encode_image(image, path, None, options) {
+        Ok(bytes) => (name.to_string(), Ok(bytes)),
+        Err(_) => (
+            path.with_extension("png").display().to_string(),
+            encode_image(image, path, Some(image::ImageFormat::Png), options),
+        ),
+    };
+    if let Err(e) = bytes.and_then(|bytes| download(&bytes, &name)) {
+        log::error!("cannot save {}: {}", name, e);
+    }
+}
+
+/// renders `timeline` and hands it to the browser as a gif, videos need ffmpeg
+pub fn save_animation(
+    timeline: &Timeline,
+    image: &egui::ColorImage,
+    passes: &[Pass],
+    name: &str,
+    _directory: Option<&Path>,
+    options: &EncodeOptions,
+) -> Result<(), String> {
+    let name = format!("{}.gif", name);
+    let bytes = timeline
+        .render(image, passes)?
+        .encode(image::ImageFormat::Gif, options.png_compression.png())?;
+    download(&bytes, &name).map_err(|e| format!("cannot save {}: {}", name, e))
+}
+
+fn download(bytes: &[u8], name: &str) -> Result<(), String> {
+    let parts = js_sys::Array::of1(&js_sys::Uint8Array::from(bytes));
+    let blob = web_sys::Blob::new_with_u8_array_sequence(&parts).map_err(js_error)?;
+    let url = web_sys::Url::create_object_url_with_blob(&blob).map_err(js_error)?;
+    let anchor = web_sys::window()
+        .and_then(|window| window.document())
+        .ok_or("the page has no document")?
+        .create_element("a")
+        .map_err(js_error)?
+        .dyn_into::<web_sys::HtmlAnchorElement>()
+        .map_err(|_| "cannot create a link")?;
+    anchor.set_href(&url);
+    anchor.set_download(name);
+    anchor.click();
+    web_sys::Url::revoke_object_url(&url).map_err(js_error)
+}
+
+fn js_error(value: JsValue) -> String {
+    value.as_string().unwrap_or_else(|| format!("{:?}", value))
+}
@@ -0,0 +1,416 @@
+//! a lossless webp encoder, the image crate only decodes webp. pixels are written with the
+//! subtract green transform and backward references to earlier pixels, sorted images repeat
+//! a lot of them
+use crate::keys;
+use std::collections::BinaryHeap;
+
+/// the longest backward reference
+const MAX_LENGTH: usize = 4096;
+/// the farthest one, longer distances don't fit in the distance codes
+const MAX_DISTANCE: usize = (1 << 20) - 120;
+/// literal green values, then the prefixes of the lengths of backward references
+const GREEN_SYMBOLS: usize = 256 + 24;
+const DISTANCE_SYMBOLS: usize = 40;
+/// the order code lengths of the code of the code lengths are written in
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+];
+
+/// bits packed into bytes from the lowest bit up
+#[derive(Default)]
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u64,
+    count: u32,
+}
+
+impl BitWriter {
+    fn write(&mut self, bits: u32, length: u32) {
+        self.bits |= (bits as u64) << self.count;
+        self.count += length;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+/// a pixel as it's written, or a copy of `length` pixels `distance` pixels back
+#[derive(Clone, Copy)]
+enum Token {
+    Pixel([u8; 4]),
+    Copy { length: usize, distance: usize },
+}
+
+/// the prefix of a length or a distance, and its extra bits and their count
+fn prefix(value: usize) -> (usize, u32, u32) {
+    let value = value - 1;
+    if value < 4 {
+        return (value, 0, 0);
+    }
+    let highest_bit = usize::BITS - 1 - value.leading_zeros();
+    let second_bit = (value >> (highest_bit - 1)) & 1;
+    let extra_bits = highest_bit - 1;
+    (
+        2 * highest_bit as usize + second_bit,
+        extra_bits,
+        (value & ((1 << extra_bits) - 1)) as u32,
+    )
+}
+
+/// a huffman code, as the length and the bit reversed code of every symbol
+struct PrefixCode {
+    lengths: Vec<u8>,
+    codes: Vec<u16>,
+    /// the symbol of codes with a single symbol, which takes no bits at all
+    single: Option<usize>,
+}
+
+impl PrefixCode {
+    fn new(histogram: &[u32], max_length: u8) -> PrefixCode {
+        let mut histogram = histogram.to_vec();
+        let used: Vec<usize> = (0..histogram.len()).filter(|&i| histogram[i] > 0).collect();
+        match *used.as_slice() {
+            [] => return PrefixCode::single(histogram.len(), 0),
+            [symbol] if symbol < 256 => return PrefixCode::single(histogram.len(), symbol),
+            // a second symbol that's never written makes it an ordinary code
+            [symbol] => histogram[usize::from(symbol == 0)] = 1,
+            _ => {}
+        }
+
+        let lengths = code_lengths(&histogram, max_length);
+        let mut next_code = [0u16; 16];
+        let mut code = 0;
+        for (length, next_code) in next_code.iter_mut().enumerate().skip(1) {
+            let count = lengths.iter().filter(|&&l| l as usize == length).count() as u16;
+            *next_code = code;
+            code = (code + count) << 1;
+        }
+        let codes = lengths
+            .iter()
+            .map(|&length| {
+                if length == 0 {
+                    return 0;
+                }
+                let code = next_code[length as usize];
+                next_code[length as usize] += 1;
+                code.reverse_bits() >> (16 - length)
+            })
+            .collect();
+        PrefixCode {
+            lengths,
+            codes,
+            single: None,
+        }
+    }
+
+    fn single(symbols: usize, symbol: usize) -> PrefixCode {
+        PrefixCode {
+            lengths: vec![0; symbols],
+            codes: vec![0; symbols],
+            single: Some(symbol),
+        }
+    }
+
+    fn write_symbol(&self, writer: &mut BitWriter, symbol: usize) {
+        writer.write(self.codes[symbol] as u32, self.lengths[symbol] as u32);
+    }
+
+    /// writes the code itself, its lengths compressed with a code of their own
+    fn write(&self, writer: &mut BitWriter) {
+        if let Some(symbol) = self.single {
+            // a simple code of one symbol, which is written in 1 or 8 bits
+            writer.write(1, 1);
+            writer.write(0, 1);
+            if symbol < 2 {
+                writer.write(0, 1);
+                writer.write(symbol as u32, 1);
+            } else {
+                writer.write(1, 1);
+                writer.write(symbol as u32, 8);
+            }
+            return;
+        }
+
+        writer.write(0, 1);
+        let mut histogram = [0u32; 19];
+        for &length in &self.lengths {
+            histogram[length as usize] += 1;
+        }
+        if histogram.iter().filter(|&&count| count > 0).count() < 2 {
+            histogram[usize::from(histogram[0] > 0)] += 1;
+        }
+        let length_code = PrefixCode::new(&histogram, 7);
+        let written = CODE_LENGTH_ORDER
+            .iter()
+            .rposition(|&symbol| length_code.lengths[symbol] > 0)
+            .map_or(4, |last| (last + 1).max(4));
+        writer.write(written as u32 - 4, 4);
+        for &symbol in &CODE_LENGTH_ORDER[..written] {
+            writer.write(length_code.lengths[symbol] as u32, 3);
+        }
+        // every symbol has a length, none is left out at the end
+        writer.write(0, 1);
+        for &length in &self.lengths {
+            length_code.write_symbol(writer, length as usize);
+        }
+    }
+}
+
+/// huffman code lengths of at most `max_length` bits, the counts are halved until they fit
+fn code_lengths(histogram: &[u32], max_length: u8) -> Vec<u8> {
+    let mut weights = histogram.to_vec();
+    loop {
+        // leaves are the symbols, the nodes after them join two others
+        let mut parents = vec![usize::MAX; histogram.len()];
+        let mut heap: BinaryHeap<std::cmp::Reverse<(u64, usize)>> = weights
+            .iter()
+            .enumerate()
+            .filter(|(_, &weight)| weight > 0)
+            .map(|(symbol, &weight)| std::cmp::Reverse((weight as u64, symbol)))
+            .collect();
+        while heap.len() > 1 {
+            let std::cmp::Reverse((first_weight, first)) = heap.pop().unwrap();
+            let std::cmp::Reverse((second_weight, second)) = heap.pop().unwrap();
+            let node = parents.len();
+            parents.push(usize::MAX);
+            parents[first] = node;
+            parents[second] = node;
+            heap.push(std::cmp::Reverse((first_weight + second_weight, node)));
+        }
+
+        let lengths: Vec<u8> = (0..histogram.len())
+            .map(|symbol| {
+                if weights[symbol] == 0 {
+                    return 0;
+                }
+                let mut length = 0;
+                let mut node = symbol;
+                while parents[node] != usize::MAX {
+                    node = parents[node];
+                    length += 1;
+                }
+                length
+            })
+            .collect();
+        if lengths.iter().all(|&length| length <= max_length) {
+            return lengths;
+        }
+        for weight in weights.iter_mut().filter(|weight| **weight > 0) {
+            *weight = (*weight / 2).max(1);
+        }
+    }
+}
+
+/// the pixels as tokens, with backward references to the pixel on the left, the one above
+/// and the last pixel that started like this one
+fn tokens(pixels: &[[u8; 4]], width: usize) -> Vec<Token> {
+    let hash = |i: usize| {
+        let pair = [pixels[i], pixels[i + 1]].concat();
+        (u64::from_le_bytes(pair.try_into().unwrap()).wrapping_mul(0x9e37_79b9_7f4a_7c15) >> 48)
+            as usize
+    };
+    let mut last_seen = vec![usize::MAX; 1 << 16];
+    let mut tokens = Vec::new();
+
+    let mut i = 0;
+    while i < pixels.len() {
+        let earlier = (i + 1 < pixels.len()).then(|| last_seen[hash(i)]);
+        let candidates = [
+            Some(1),
+            Some(width),
+            earlier.map(|start| i.wrapping_sub(start)),
+        ];
+        let longest = pixels.len() - i;
+        let (length, distance) = candidates
+            .into_iter()
+            .flatten()
+            .filter(|&distance| distance >= 1 && distance <= i.min(MAX_DISTANCE))
+            .map(|distance| {
+                let length = (0..longest.min(MAX_LENGTH))
+                    .take_while(|&j| pixels[i + j] == pixels[i + j - distance])
+                    .count();
+                (length, distance)
+            })
+            .max_by_key(|&(length, _)| length)
+            .unwrap_or((0, 0));
+
+        let step = if length >= 3 {
+            tokens.push(Token::Copy { length, distance });
+            length
+        } else {
+            tokens.push(Token::Pixel(pixels[i]));
+            1
+        };
+        for j in i..(i + step).min(pixels.len() - 1) {
+            last_seen[hash(j)] = j;
+        }
+        i += step;
+    }
+    tokens
+}
+
+/// the distance code of `distance`: the pixel above and the one on the left have short ones
+fn distance_code(distance: usize, width: usize) -> usize {
+    if distance == width {
+        1
+    } else if distance == 1 {
+        2
+    } else {
+        distance + 120
+    }
+}
+
+/// encodes `image` as a lossless webp
+pub fn encode_lossless(image: &egui::ColorImage) -> Result<Vec<u8>, String> {
+    let [width, height] = image.size;
+    if width == 0 || height == 0 || width > 1 << 14 || height > 1 << 14 {
+        return Err(format!(
+            "webp images are 1 to 16384 pixels wide and high, not {}x{}",
+            width, height
+        ));
+    }
+
+    // subtract green: red and blue are stored as their difference to green
+    let pixels: Vec<[u8; 4]> = image
+        .pixels
+        .iter()
+        .map(|pixel| {
+            let [red, green, blue, alpha] = keys::straight(pixel).0;
+            [
+                red.wrapping_sub(green),
+                green,
+                blue.wrapping_sub(green),
+                alpha,
+            ]
+        })
+        .collect();
+    let tokens = tokens(&pixels, width);
+
+    // green, red, blue, alpha and distance
+    let mut histograms = [
+        vec![0u32; GREEN_SYMBOLS],
+        vec![0; 256],
+        vec![0; 256],
+        vec![0; 256],
+        vec![0; DISTANCE_SYMBOLS],
+    ];
+    for token in &tokens {
+        match *token {
+            Token::Pixel([red, green, blue, alpha]) => {
+                histograms[0][green as usize] += 1;
+                histograms[1][red as usize] += 1;
+                histograms[2][blue as usize] += 1;
+                histograms[3][alpha as usize] += 1;
+            }
+            Token::Copy { length, distance } => {
+                histograms[0][256 + prefix(length).0] += 1;
+                histograms[4][prefix(distance_code(distance, width)).0] += 1;
+            }
+        }
+    }
+    let codes = histograms.map(|histogram| PrefixCode::new(&histogram, 15));
+
+    let mut writer = BitWriter::default();
+    writer.write(0x2f, 8);
+    writer.write(width as u32 - 1, 14);
+    writer.write(height as u32 - 1, 14);
+    let has_alpha = image.pixels.iter().any(|pixel| pixel.a() < 255);
+    writer.write(has_alpha as u32, 1);
+    writer.write(0, 3);
+    // the subtract green transform and no other
+    writer.write(1, 1);
+    writer.write(2, 2);
+    writer.write(0, 1);
+    // no color cache and a single set of codes for the whole image
+    writer.write(0, 1);
+    writer.write(0, 1);
+    for code in &codes {
+        code.write(&mut writer);
+    }
+    for token in &tokens {
+        match *token {
+            Token::Pixel([red, green, blue, alpha]) => {
+                codes[0].write_symbol(&mut writer, green as usize);
+                codes[1].write_symbol(&mut writer, red as usize);
+                codes[2].write_symbol(&mut writer, blue as usize);
+                codes[3].write_symbol(&mut writer, alpha as usize);
+            }
+            Token::Copy { length, distance } => {
+                let (symbol, extra_bits, extra) = prefix(length);
+                codes[0].write_symbol(&mut writer, 256 + symbol);
+                writer.write(extra, extra_bits);
+                let (symbol, extra_bits, extra) = prefix(distance_code(distance, width));
+                codes[4].write_symbol(&mut writer, symbol);
+                writer.write(extra, extra_bits);
+            }
+        }
+    }
+
+    let data = writer.finish();
+    // chunks are padded to an even size
+    let padding = data.len() % 2;
+    let mut bytes = b"RIFF".to_vec();
+    bytes.extend((12 + data.len() as u32 + padding as u32).to_le_bytes());
+    bytes.extend(b"WEBPVP8L");
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+    bytes.extend(&[0][..padding]);
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::io::straight_rgba;
+    use rand::{Rng, SeedableRng};
+
+    /// encodes `image` and decodes it back with the image crate
+    fn round_trip(image: &egui::ColorImage) {
+        let bytes = encode_lossless(image).unwrap();
+        let decoded = image::load_from_memory_with_format(&bytes, image::ImageFormat::WebP)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(
+            [decoded.width() as usize, decoded.height() as usize],
+            image.size
+        );
+        assert_eq!(decoded.into_raw(), straight_rgba(&image.pixels));
+    }
+
+    #[test]
+    fn lossless_webps_decode_to_the_same_pixels() {
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(0);
+        // noise, nothing to refer back to
+        let pixels = (0..37 * 23)
+            .map(|_| egui::Color32::from_rgba_unmultiplied(rng.gen(), rng.gen(), rng.gen(), 255))
+            .collect();
+        round_trip(&egui::ColorImage {
+            size: [37, 23],
+            pixels,
+        });
+
+        // sorted looking runs, repeated along the rows and down the columns, some translucent
+        let pixels = (0..64 * 48)
+            .map(|i| {
+                let level = (i % 64 / 8 * 30) as u8;
+                let alpha = if i % 7 == 0 { 128 } else { 255 };
+                egui::Color32::from_rgba_unmultiplied(level, 255 - level, 40, alpha)
+            })
+            .collect();
+        round_trip(&egui::ColorImage {
+            size: [64, 48],
+            pixels,
+        });
+
+        round_trip(&egui::ColorImage::new([1, 1], egui::Color32::TRANSPARENT));
+    }
+}
@@ -0,0 +1,263 @@
+//! gui widgets egui doesn't come with
+use egui::emath::Numeric;
+use std::ops::RangeInclusive;
+
+/// a slider with a handle for each end of a band. the lower handle stops at the higher one
+/// and the other way around, and both are kept within `range`. once focused the arrow keys
+/// move the last handle dragged by 1, or by 10 with shift, and the values next to the slider
+/// can be dragged or typed in
+pub struct RangeSlider<'a, Num: Numeric> {
+    lower: &'a mut Num,
+    higher: &'a mut Num,
+    range: RangeInclusive<Num>,
+    suffix: &'static str,
+    /// drawn above the track, spread over the whole range
+    histogram: Option<&'a [u64]>,
+}
+
+/// the handle being dragged, kept in the memory of the ui between frames
+#[derive(Clone, Copy)]
+enum Handle {
+    Lower,
+    Higher,
+}
+
+impl<'a, Num: Numeric> RangeSlider<'a, Num> {
+    pub fn new(lower: &'a mut Num, higher: &'a mut Num, range: RangeInclusive<Num>) -> Self {
+        RangeSlider {
+            lower,
+            higher,
+            range,
+            suffix: "",
+            histogram: None,
+        }
+    }
+
+    /// how many pixels have every value, the handles can be dragged on it too
+    pub fn histogram(mut self, histogram: &'a [u64]) -> Self {
+        self.histogram = Some(histogram).filter(|histogram| !histogram.is_empty());
+        self
+    }
+
+    /// shown after the values
+    pub fn suffix(mut self, suffix: &'static str) -> Self {
+        self.suffix = suffix;
+        self
+    }
+
+    /// typed in, or dragged a step per point like the slider
+    fn drag_value<'v>(
+        &self,
+        value: &'v mut f64,
+        range: RangeInclusive<f64>,
+    ) -> egui::DragValue<'v> {
+        egui::DragValue::new(value)
+            .clamp_range(range)
+            .speed(if Num::INTEGRAL { 1.0 } else { 0.1 })
+            .max_decimals(if Num::INTEGRAL { 0 } else { 1 })
+            .suffix(self.suffix)
+    }
+}
+
+impl<'a, Num: Numeric> egui::Widget for RangeSlider<'a, Num> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let (min, max) = (self.range.start().to_f64(), self.range.end().to_f64());
+        let mut higher = self.higher.to_f64().clamp(min, max);
+        let mut lower = self.lower.to_f64().clamp(min, higher);
+
+        let row_height = ui.spacing().interact_size.y;
+        let histogram_height = if self.histogram.is_some() {
+            row_height * 1.5
+        } else {
+            0.0
+        };
+        let size = egui::vec2(
+            ui.spacing().slider_width * 2.0,
+            row_height + histogram_height,
+        );
+        let (rect, mut response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        let (histogram_rect, handles_rect) =
+            rect.split_top_bottom_at_y(rect.top() + histogram_height);
+        let radius = row_height / 2.5;
+        let track = rect.shrink2(egui::vec2(radius, 0.0));
+        let to_x = |value: f64| {
+            let t = if max > min {
+                (value - min) / (max - min)
+            } else {
+                0.0
+            };
+            track.left() + track.width() * t as f32
+        };
+        let to_value = |x: f32| {
+            let value =
+                min + ((x - track.left()) / track.width()).clamp(0.0, 1.0) as f64 * (max - min);
+            if Num::INTEGRAL {
+                value.round()
+            } else {
+                value
+            }
+        };
+
+        if let Some(pointer) = response.interact_pointer_pos() {
+            if ui.input(|input| input.pointer.any_pressed()) {
+                response.request_focus();
+                // the closer handle, when they overlap the one the pointer is past
+                let (lower_x, higher_x) = (to_x(lower), to_x(higher));
+                let handle = if (pointer.x - lower_x).abs() < (pointer.x - higher_x).abs()
+                    || (lower_x == higher_x && pointer.x < lower_x)
+                {
+                    Handle::Lower
+                } else {
+                    Handle::Higher
+                };
+                ui.data_mut(|data| data.insert_temp(response.id, handle));
+            }
+            let value = to_value(pointer.x);
+            match ui.data(|data| data.get_temp(response.id)) {
+                Some(Handle::Lower) => lower = value.min(higher),
+                Some(Handle::Higher) => higher = value.max(lower),
+                None => {}
+            }
+        }
+        if response.has_focus() {
+            let step = if ui.input(|input| input.modifiers.shift) {
+                10.0
+            } else {
+                1.0
+            };
+            let nudge = ui.input(|input| {
+                (input.key_pressed(egui::Key::ArrowRight) as i8
+                    - input.key_pressed(egui::Key::ArrowLeft) as i8) as f64
+            }) * step;
+            match ui.data(|data| data.get_temp(response.id)) {
+                Some(Handle::Lower) => lower = (lower + nudge).clamp(min, higher),
+                // the higher one unless the lower one was dragged last
+                _ => higher = (higher + nudge).clamp(lower, max),
+            }
+        }
+
+        ui.add(self.drag_value(&mut lower, min..=higher));
+        ui.label("–");
+        ui.add(self.drag_value(&mut higher, lower..=max));
+
+        let (new_lower, new_higher) = (Num::from_f64(lower), Num::from_f64(higher));
+        if (new_lower, new_higher) != (*self.lower, *self.higher) {
+            *self.lower = new_lower;
+            *self.higher = new_higher;
+            response.mark_changed();
+        }
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let center_y = handles_rect.center().y;
+            let rail = egui::Rect::from_min_max(
+                egui::pos2(track.left(), center_y - radius / 2.0),
+                egui::pos2(track.right(), center_y + radius / 2.0),
+            );
+            let painter = ui.painter();
+            if let Some(histogram) = self.histogram {
+                let highest = histogram.iter().max().copied().unwrap_or(0).max(1) as f32;
+                let bin_width = track.width() / histogram.len() as f32;
+                let (lower_x, higher_x) = (to_x(lower), to_x(higher));
+                for (i, &count) in histogram.iter().enumerate() {
+                    let left = track.left() + bin_width * i as f32;
+                    let bar = egui::Rect::from_min_max(
+                        egui::pos2(
+                            left,
+                            histogram_rect.bottom()
+                                - histogram_rect.height() * count as f32 / highest,
+                        ),
+                        egui::pos2(left + bin_width, histogram_rect.bottom()),
+                    );
+                    // the values between the thresholds stand out
+                    let color = if left + bin_width >= lower_x && left <= higher_x {
+                        ui.visuals().selection.bg_fill
+                    } else {
+                        ui.visuals().widgets.inactive.bg_fill
+                    };
+                    painter.rect_filled(bar, 0.0, color);
+                }
+                for x in [lower_x, higher_x] {
+                    painter.vline(x, histogram_rect.top()..=center_y, visuals.fg_stroke);
+                }
+            }
+            painter.rect_filled(rail, radius / 2.0, ui.visuals().widgets.inactive.bg_fill);
+            let band = egui::Rect::from_x_y_ranges(to_x(lower)..=to_x(higher), rail.y_range());
+            painter.rect_filled(band, radius / 2.0, ui.visuals().selection.bg_fill);
+            for value in [lower, higher] {
+                painter.circle(
+                    egui::pos2(to_x(value), center_y),
+                    radius,
+                    visuals.bg_fill,
+                    visuals.fg_stroke,
+                );
+            }
+        }
+        response
+    }
+}
+
+/// a dial pointing `angle` degrees clockwise from the right. dragging it turns it by whole
+/// degrees, or by 15 with shift, and the angle next to it can be dragged or typed in
+pub struct AngleDial<'a> {
+    angle: &'a mut f32,
+}
+
+impl<'a> AngleDial<'a> {
+    pub fn new(angle: &'a mut f32) -> Self {
+        AngleDial { angle }
+    }
+}
+
+impl<'a> egui::Widget for AngleDial<'a> {
+    fn ui(self, ui: &mut egui::Ui) -> egui::Response {
+        let start = self.angle.rem_euclid(360.0);
+        let mut angle = start;
+        let size = ui.spacing().interact_size.y * 1.5;
+        let (rect, mut response) =
+            ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::click_and_drag());
+        let center = rect.center();
+
+        if let Some(pointer) = response.interact_pointer_pos() {
+            let offset = pointer - center;
+            if offset != egui::Vec2::ZERO {
+                let step = if ui.input(|input| input.modifiers.shift) {
+                    15.0
+                } else {
+                    1.0
+                };
+                let degrees = offset.y.atan2(offset.x).to_degrees().rem_euclid(360.0);
+                angle = ((degrees / step).round() * step).rem_euclid(360.0);
+            }
+        }
+
+        ui.add(
+            egui::DragValue::new(&mut angle)
+                .clamp_range(0.0..=359.0)
+                .speed(1.0)
+                .max_decimals(1)
+                .suffix("°"),
+        );
+
+        if angle != start {
+            *self.angle = angle;
+            response.mark_changed();
+        }
+
+        if ui.is_rect_visible(rect) {
+            let visuals = ui.style().interact(&response);
+            let radius = size / 2.0 - visuals.fg_stroke.width;
+            let tip = center + egui::Vec2::angled(angle.to_radians()) * radius;
+            let painter = ui.painter();
+            painter.circle(
+                center,
+                radius,
+                ui.visuals().widgets.inactive.bg_fill,
+                visuals.bg_stroke,
+            );
+            painter.line_segment([center, tip], visuals.fg_stroke);
+            painter.circle_filled(tip, radius / 4.0, ui.visuals().selection.bg_fill);
+        }
+        response
+    }
+}